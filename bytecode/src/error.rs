@@ -0,0 +1,140 @@
+use std::fmt;
+
+// what the reader was looking at when it failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Field {
+	Header,
+	Source,
+	LineDefined,
+	LastLineDefined,
+	ProtoInfo, // nupvals, nparams, vararg flag & max stack size
+	InstructionCount,
+	Instruction(usize),
+	ConstantCount,
+	ConstantTag(usize),
+	Constant(usize),
+	ProtoCount,
+	SourceLineCount,
+	SourceLine(usize),
+	LocalCount,
+	Local(usize),
+	UpvalueCount,
+	Upvalue(usize),
+	UpvalueDescCount,
+	UpvalueDesc(usize),
+	AbsLineInfoCount,
+	AbsLineInfo(usize),
+	NumConstant(usize),
+	String(usize),
+	TypeInfo,
+	MainProto
+}
+
+impl fmt::Display for Field {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::Header => write!(f, "header"),
+			Self::Source => write!(f, "source name"),
+			Self::LineDefined => write!(f, "line defined"),
+			Self::LastLineDefined => write!(f, "last line defined"),
+			Self::ProtoInfo => write!(f, "proto info"),
+			Self::InstructionCount => write!(f, "instruction count"),
+			Self::Instruction(i) => write!(f, "instruction #{}", i),
+			Self::ConstantCount => write!(f, "constant count"),
+			Self::ConstantTag(i) => write!(f, "tag of constant #{}", i),
+			Self::Constant(i) => write!(f, "constant #{}", i),
+			Self::ProtoCount => write!(f, "nested proto count"),
+			Self::SourceLineCount => write!(f, "source line count"),
+			Self::SourceLine(i) => write!(f, "source line #{}", i),
+			Self::LocalCount => write!(f, "local count"),
+			Self::Local(i) => write!(f, "local #{}", i),
+			Self::UpvalueCount => write!(f, "upvalue name count"),
+			Self::Upvalue(i) => write!(f, "upvalue name #{}", i),
+			Self::UpvalueDescCount => write!(f, "upvalue count"),
+			Self::UpvalueDesc(i) => write!(f, "upvalue #{}", i),
+			Self::AbsLineInfoCount => write!(f, "absolute line info count"),
+			Self::AbsLineInfo(i) => write!(f, "absolute line info #{}", i),
+			Self::NumConstant(i) => write!(f, "number constant #{}", i),
+			Self::String(i) => write!(f, "string #{}", i),
+			Self::TypeInfo => write!(f, "type info"),
+			Self::MainProto => write!(f, "main proto index")
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DecodeErrorKind {
+	UnexpectedEof { needed: usize, remaining: usize },
+	BadSignature,
+	BadTail,
+	BadCheckValue(&'static str),
+	UnsupportedVersion(u8),
+	UnsupportedFormat(u8),
+	UnsupportedEndianness(u8),
+	UnsupportedSize { what: &'static str, size: u8 },
+	InvalidIntegralFlag(u8),
+	IntegerOverflow,
+	UnknownConstantTag(u8),
+	UnknownUpvalueKind(u8),
+	InvalidOpcode(u8),
+	InvalidOperand,
+	MissingSetListWord,
+	MissingCapture,
+	JumpIntoSequence,
+	TooDeeplyNested,
+	BadProtoLength,
+	UnbalancedProtos,
+	MissingAuxWord,
+	CompileError(String),
+	Io(std::io::ErrorKind)
+}
+
+impl fmt::Display for DecodeErrorKind {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::UnexpectedEof { needed, remaining } => write!(f, "unexpected end of chunk (needed {} bytes, {} left)", needed, remaining),
+			Self::BadSignature => write!(f, "not a lua chunk"),
+			Self::BadTail => write!(f, "corrupted chunk (LUAC_TAIL mismatch)"),
+			Self::BadCheckValue(what) => write!(f, "{} mismatch in header", what),
+			Self::UnsupportedVersion(v) => write!(f, "unsupported version {:#04x}", v),
+			Self::UnsupportedFormat(v) => write!(f, "unsupported format {}", v),
+			Self::UnsupportedEndianness(v) => write!(f, "unsupported endianness flag {}", v),
+			Self::UnsupportedSize { what, size } => write!(f, "unsupported size of {} ({})", what, size),
+			Self::InvalidIntegralFlag(v) => write!(f, "invalid integral flag {}", v),
+			Self::IntegerOverflow => write!(f, "integer does not fit in 32 bits"),
+			Self::UnknownConstantTag(t) => write!(f, "unknown constant tag {}", t),
+			Self::UnknownUpvalueKind(k) => write!(f, "unknown upvalue kind {}", k),
+			Self::InvalidOpcode(op) => write!(f, "invalid opcode {}", op),
+			Self::InvalidOperand => write!(f, "operand out of range"),
+			Self::MissingSetListWord => write!(f, "SETLIST with C=0 is missing its extra word"),
+			Self::MissingCapture => write!(f, "CLOSURE is missing the MOVE or GETUPVAL of an upvalue"),
+			Self::JumpIntoSequence => write!(f, "jump lands inside a CLOSURE or SETLIST sequence"),
+			Self::TooDeeplyNested => write!(f, "protos are nested too deeply"),
+			Self::BadProtoLength => write!(f, "proto length doesn't match its contents"),
+			Self::UnbalancedProtos => write!(f, "child protos don't match the KGC_CHILD constants"),
+			Self::MissingAuxWord => write!(f, "instruction is missing its AUX word"),
+			Self::CompileError(msg) => write!(f, "chunk holds a compile error: {}", msg),
+			Self::Io(kind) => write!(f, "read failed: {}", kind)
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeError {
+	pub offset: usize, // byte offset where the failing read started
+	pub field: Field,
+	pub path: Vec<usize>, // nested proto indices, empty for the main proto
+	pub kind: DecodeErrorKind
+}
+
+impl fmt::Display for DecodeError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} while reading {} of proto main", self.kind, self.field)?;
+		for idx in &self.path {
+			write!(f, "/{}", idx)?;
+		}
+		write!(f, " at offset {:#x}", self.offset)
+	}
+}
+
+impl std::error::Error for DecodeError {}
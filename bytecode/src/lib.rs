@@ -1,6 +1,26 @@
 pub type Bytecode = [u8];
 
+mod error;
+mod string;
 pub mod lua51;
+pub mod lua52;
+pub mod lua53;
+pub mod lua54;
+pub mod luajit;
+pub mod luau;
+
+pub use error::{DecodeError, DecodeErrorKind, Field};
+
+// same limit the reference vm puts on nested C calls while undumping
+pub(crate) const MAX_NESTING: usize = 200;
+pub use string::LuaString;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Endianness {
+	Little,
+	Big
+}
 
 pub trait Hex: std::fmt::Debug {
 	fn hex(&self, pt: Option<usize>) -> String;
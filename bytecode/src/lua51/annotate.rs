@@ -0,0 +1,63 @@
+// hex dump of a chunk where every byte range is labelled with the field it was read as, up to
+// wherever reading stopped. meant for looking at chunks that don't load
+
+use std::fmt::Write;
+
+use crate::{Bytecode, Field};
+use super::{Reader, deserialize_from};
+use super::disassemble::OPNAMES;
+
+const WIDTH: usize = 16;
+
+fn hex_lines(out: &mut String, indent: &str, bytecode: &Bytecode, from: usize, to: usize, label: &str) {
+	for (i, line) in bytecode[from..to].chunks(WIDTH).enumerate() {
+		let hex = line.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(" ");
+		let label = if i == 0 { label } else { "" };
+		writeln!(out, "{}{:08x}  {:<w$}  {}", indent, from + i * WIDTH, hex, label, w = WIDTH * 3 - 1).unwrap();
+	}
+}
+
+fn path_name(path: &[usize]) -> String {
+	path.iter().fold("main".to_string(), |name, idx| format!("{}/{}", name, idx))
+}
+
+pub fn annotate(bytecode: &Bytecode) -> String {
+	let mut reader = Reader::new(bytecode);
+	reader.record_fields();
+	let result = deserialize_from(&mut reader);
+	let end = reader.position();
+	let big = bytecode.get(6) == Some(&0);
+
+	let mut out = String::new();
+	let marks = reader.marks();
+	let mut indent = String::new();
+	for (i, (from, field, path)) in marks.iter().enumerate() {
+		let to = marks.get(i + 1).map_or(end, |next| next.0).min(end);
+		indent = "  ".repeat(path.len());
+		if *field == Field::Source {
+			writeln!(out, "{}proto {}", indent, path_name(path)).unwrap();
+		}
+		if to <= *from {
+			continue;
+		}
+
+		let label = match field {
+			// the opcode sits in the low bits of the word
+			Field::Instruction(_) => {
+				let low = if big { bytecode[to - 1] } else { bytecode[*from] };
+				format!("{}  {}", field, OPNAMES.get((low & 0x3f) as usize).unwrap_or(&"?"))
+			}
+			field => field.to_string()
+		};
+		hex_lines(&mut out, &indent, bytecode, *from, to, &label);
+	}
+
+	match result {
+		Err(err) => {
+			writeln!(out, "{}{:08x}  error: {}", indent, err.offset, err).unwrap();
+			hex_lines(&mut out, &indent, bytecode, end, bytecode.len(), "not read");
+		}
+		Ok(_) => hex_lines(&mut out, "", bytecode, end, bytecode.len(), "trailing data")
+	}
+	out
+}
@@ -0,0 +1,747 @@
+// a text format for protos, which also reads what disassemble prints
+//
+//	.source "@patch.lua"
+//	.const print "print"
+//	.upval counter
+//	.line 3
+//	loop:	GETGLOBAL 0 print	; constants by name, `-1 - index` or as "string", #1.5, nil, true, false
+//		GETUPVAL 1 counter	; upvalues by name or index
+//		CLOSURE 2 callback	; children by name or index
+//		JMP loop			; labels or raw sBx offsets
+//	.local i loop done	; pcs are labels or 1 based pcs like luac prints them
+//	done:	RETURN 0 1
+//	.function callback 4 6
+//		.params 1
+//		RETURN 0 1
+//	.end
+//
+// other directives are .params n, .vararg [flag], .stack n. without .stack the stack size is an
+// estimate. a listing comes back the way it prints, sources of child functions as None when
+// they are the parent's.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::LuaString;
+use super::{Constants, LocalVar, Proto};
+use super::code;
+use super::instruction::{is_k, rk_as_k, MAXINDEXRK};
+use super::disassemble::{OPNAMES, Arg, Mode, opmode};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssembleError {
+	pub line: usize, // 1 based
+	pub message: String
+}
+
+impl fmt::Display for AssembleError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "line {}: {}", self.line, self.message)
+	}
+}
+
+impl std::error::Error for AssembleError {}
+
+type Result<T> = std::result::Result<T, AssembleError>;
+
+fn error<T>(line: usize, message: impl Into<String>) -> Result<T> {
+	Err(AssembleError { line, message: message.into() })
+}
+
+enum Operand {
+	Int(i64),
+	Name(String),
+	Const(Constants)
+}
+
+enum Section {
+	Code,
+	Constants,
+	Locals,
+	Upvalues
+}
+
+struct Pending {
+	line_no: usize,
+	op: u32,
+	operands: Vec<Operand>,
+	count: Option<u32>, // SETLIST's extra word, luac prints it as the comment
+	line: u32
+}
+
+struct PendingLocal {
+	line_no: usize,
+	name: LuaString,
+	start: Operand,
+	end: Operand
+}
+
+struct Function {
+	line_no: usize,
+	name: Option<String>,
+	started: bool,
+	section: Section,
+	source: Option<LuaString>,
+	line_defined: u32,
+	last_line_defined: u32,
+	nparams: u8,
+	vararg: u8,
+	stack: Option<u8>,
+	nupvals: Option<u8>,
+	line: u32,
+	has_lines: bool,
+	code: Vec<Pending>,
+	labels: HashMap<String, usize>, // index into code
+	constants: Vec<Constants>,
+	const_names: HashMap<String, u32>,
+	locals: Vec<PendingLocal>,
+	upvals: Vec<LuaString>,
+	children: Vec<Function>,
+	listing_children: Option<usize>
+}
+
+// splits off a ; comment that isn't inside a string
+fn split_comment(line: &str) -> (&str, Option<&str>) {
+	let mut quoted = false;
+	let mut escaped = false;
+	for (i, c) in line.char_indices() {
+		match c {
+			_ if escaped => escaped = false,
+			'\\' if quoted => escaped = true,
+			'"' => quoted = !quoted,
+			';' if !quoted => return (&line[..i], Some(&line[i + 1..])),
+			_ => {}
+		}
+	}
+	(line, None)
+}
+
+fn tokenize(line: &str, line_no: usize) -> Result<Vec<&str>> {
+	let mut tokens = vec![];
+	let mut rest = line.trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+	while !rest.is_empty() {
+		let end = if rest.starts_with('"') {
+			let mut escaped = false;
+			let close = rest.char_indices().skip(1).find(|&(_, c)| {
+				let close = c == '"' && !escaped;
+				escaped = c == '\\' && !escaped;
+				close
+			});
+			match close {
+				Some((i, _)) => i + 1,
+				None => return error(line_no, "unterminated string")
+			}
+		} else {
+			rest.find(|c: char| c.is_whitespace() || c == ',').unwrap_or(rest.len())
+		};
+		tokens.push(&rest[..end]);
+		rest = rest[end..].trim_start_matches(|c: char| c.is_whitespace() || c == ',');
+	}
+	Ok(tokens)
+}
+
+// the escapes luac prints, plus lua's \ddd with fewer digits
+fn string(token: &str, line_no: usize) -> Result<LuaString> {
+	let inner = &token.as_bytes()[1..token.len() - 1];
+	let mut out = vec![];
+	let mut i = 0;
+	while i < inner.len() {
+		if inner[i] != b'\\' {
+			out.push(inner[i]);
+			i += 1;
+			continue;
+		}
+		i += 1;
+		let Some(&c) = inner.get(i) else {
+			return error(line_no, "unfinished escape");
+		};
+		i += 1;
+		out.push(match c {
+			b'a' => 0x07,
+			b'b' => 0x08,
+			b'f' => 0x0c,
+			b'n' => b'\n',
+			b'r' => b'\r',
+			b't' => b'\t',
+			b'v' => 0x0b,
+			b'0'..=b'9' => {
+				let mut n = (c - b'0') as u32;
+				for _ in 0..2 {
+					match inner.get(i) {
+						Some(d @ b'0'..=b'9') => {
+							n = n * 10 + (d - b'0') as u32;
+							i += 1;
+						},
+						_ => break
+					}
+				}
+				if n > 255 {
+					return error(line_no, format!("escape \\{} is out of range", n));
+				}
+				n as u8
+			},
+			c => c
+		});
+	}
+	Ok(LuaString(out))
+}
+
+fn number(token: &str, line_no: usize) -> Result<f64> {
+	match token.parse() {
+		Ok(n) => Ok(n),
+		Err(_) => error(line_no, format!("`{}` is not a number", token))
+	}
+}
+
+fn integer<T: std::str::FromStr>(token: Option<&&str>, what: &str, line_no: usize) -> Result<T> {
+	match token.and_then(|token| token.parse().ok()) {
+		Some(n) => Ok(n),
+		None => error(line_no, format!("expected {}", what))
+	}
+}
+
+// a constant value, numbers are bare in the constant listing and .const
+fn literal(token: &str, bare_numbers: bool, line_no: usize) -> Result<Option<Constants>> {
+	Ok(Some(match token {
+		"nil" => Constants::Nil,
+		"true" => Constants::Boolean(true),
+		"false" => Constants::Boolean(false),
+		_ if token.starts_with('"') => Constants::String(string(token, line_no)?),
+		_ if token.starts_with('#') => Constants::Number(number(&token[1..], line_no)?),
+		_ if bare_numbers => Constants::Number(number(token, line_no)?),
+		_ => return Ok(None)
+	}))
+}
+
+fn operand(token: &str, line_no: usize) -> Result<Operand> {
+	if let Some(kst) = literal(token, false, line_no)? {
+		return Ok(Operand::Const(kst));
+	}
+	if let Ok(n) = token.parse() {
+		return Ok(Operand::Int(n));
+	}
+	if !is_name(token) {
+		return error(line_no, format!("bad operand `{}`", token));
+	}
+	Ok(Operand::Name(token.to_string()))
+}
+
+fn is_name(token: &str) -> bool {
+	token.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+		&& token.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+fn name(token: Option<&&str>, line_no: usize) -> Result<LuaString> {
+	match token {
+		Some(token) if token.starts_with('"') => string(token, line_no),
+		Some(token) => Ok((*token).into()),
+		None => error(line_no, "expected a name")
+	}
+}
+
+// the number in front of "params", "slots", ... on luac's second header line
+fn count(part: Option<&str>, line_no: usize) -> Result<(usize, bool)> {
+	let part = part.unwrap_or_default().trim();
+	let digits = part.find(|c: char| !c.is_ascii_digit()).unwrap_or(part.len());
+	match part[..digits].parse() {
+		Ok(n) => Ok((n, part[digits..].starts_with('+'))),
+		Err(_) => error(line_no, "bad function header")
+	}
+}
+
+impl Function {
+	fn new(line_no: usize, name: Option<String>, vararg: u8) -> Self {
+		Self {
+			line_no,
+			name,
+			started: false,
+			section: Section::Code,
+			source: None,
+			line_defined: 0,
+			last_line_defined: 0,
+			nparams: 0,
+			vararg,
+			stack: None,
+			nupvals: None,
+			line: 0,
+			has_lines: false,
+			code: vec![],
+			labels: HashMap::new(),
+			constants: vec![],
+			const_names: HashMap::new(),
+			locals: vec![],
+			upvals: vec![],
+			children: vec![],
+			listing_children: None
+		}
+	}
+
+	// main <test.lua:0,0> (13 instructions, 52 bytes at 0x...)
+	// 0+ params, 4 slots, 0 upvalues, 2 locals, 3 constants, 1 function
+	fn header(&mut self, line: &str, counts: &str, line_no: usize) -> Result<()> {
+		let inner = line.split_once('<').and_then(|(_, rest)| rest.split_once('>')).map(|(inner, _)| inner);
+		let Some((source, lines)) = inner.and_then(|inner| inner.rsplit_once(':')) else {
+			return error(line_no, "bad function header");
+		};
+		let lines: Vec<&str> = lines.split(',').collect();
+		self.line_defined = integer(lines.first(), "line defined", line_no)?;
+		self.last_line_defined = integer(lines.get(1), "last line defined", line_no)?;
+		self.source = Some(match source {
+			"?" => "=?".into(),
+			"(string)" | "(bstring)" => source.into(),
+			_ => format!("@{}", source).into()
+		});
+
+		let mut parts = counts.split(',');
+		let (nparams, vararg) = count(parts.next(), line_no + 1)?;
+		self.nparams = nparams as u8;
+		self.vararg = if vararg { 2 } else { 0 };
+		self.stack = Some(count(parts.next(), line_no + 1)?.0 as u8);
+		self.nupvals = Some(count(parts.next(), line_no + 1)?.0 as u8);
+		self.listing_children = Some(count(parts.nth(2), line_no + 1)?.0);
+		Ok(())
+	}
+
+	fn directive(&mut self, tokens: &[&str], line_no: usize) -> Result<()> {
+		match tokens[0] {
+			".source" => match tokens.get(1) {
+				Some(token) if token.starts_with('"') => self.source = Some(string(token, line_no)?),
+				_ => return error(line_no, "expected a string")
+			},
+			".line" => self.line = integer(tokens.get(1), "a line", line_no)?,
+			".params" => self.nparams = integer(tokens.get(1), "a parameter count", line_no)?,
+			".vararg" => self.vararg = match tokens.get(1) {
+				Some(_) => integer(tokens.get(1), "a vararg flag", line_no)?,
+				None => 2
+			},
+			".stack" => self.stack = Some(integer(tokens.get(1), "a stack size", line_no)?),
+			".upval" => self.upvals.push(name(tokens.get(1), line_no)?),
+			".local" => {
+				let (Some(start), Some(end)) = (tokens.get(2), tokens.get(3)) else {
+					return error(line_no, "expected .local name start end");
+				};
+				let local = PendingLocal { line_no, name: name(tokens.get(1), line_no)?, start: operand(start, line_no)?, end: operand(end, line_no)? };
+				self.locals.push(local);
+			},
+			".const" => {
+				let (Some(name), Some(value)) = (tokens.get(1), tokens.get(2)) else {
+					return error(line_no, "expected .const name value");
+				};
+				let Some(kst) = literal(value, true, line_no)? else {
+					return error(line_no, format!("bad constant `{}`", value));
+				};
+				self.const_names.insert(name.to_string(), self.constants.len() as u32);
+				self.constants.push(kst);
+			},
+			directive => return error(line_no, format!("unknown directive {}", directive))
+		}
+		Ok(())
+	}
+
+	// `1 [3] LOADK 0 -1` from a listing, or `label: LOADK 0 "x"`
+	fn instruction(&mut self, tokens: &[&str], comment: Option<&str>, line_no: usize) -> Result<()> {
+		let mut tokens = tokens;
+		if tokens[0].chars().all(|c| c.is_ascii_digit()) {
+			tokens = &tokens[1..]; // pc
+		}
+		let mut line = self.line;
+		if let Some(token) = tokens.first().filter(|token| token.starts_with('[')) {
+			line = match token.trim_matches(|c| c == '[' || c == ']') {
+				"-" => 0,
+				n => integer(Some(&n), "a line", line_no)?
+			};
+			tokens = &tokens[1..];
+		}
+		if let Some(label) = tokens.first().and_then(|token| token.strip_suffix(':')) {
+			if !is_name(label) {
+				return error(line_no, format!("bad label `{}`", label));
+			}
+			if self.labels.insert(label.to_string(), self.code.len()).is_some() {
+				return error(line_no, format!("label `{}` is defined twice", label));
+			}
+			tokens = &tokens[1..];
+		}
+		let Some(opname) = tokens.first() else {
+			return Ok(());
+		};
+
+		let Some(op) = OPNAMES.iter().position(|name| name.eq_ignore_ascii_case(opname)) else {
+			return error(line_no, format!("unknown opcode {}", opname));
+		};
+		let operands = tokens[1..].iter().map(|token| operand(token, line_no)).collect::<Result<_>>()?;
+		let count = comment.and_then(|comment| comment.trim().parse().ok());
+		self.has_lines |= line > 0;
+		self.code.push(Pending { line_no, op: op as u32, operands, count, line });
+		Ok(())
+	}
+
+	// names in the local and upvalue listings may hold spaces, like (for index)
+	fn listed(&mut self, code: &str, line_no: usize) -> Result<()> {
+		let tokens: Vec<&str> = match self.section {
+			Section::Constants => tokenize(code, line_no)?,
+			_ => code.split('\t').map(str::trim).filter(|token| !token.is_empty()).collect()
+		};
+		let index: usize = integer(tokens.first(), "an index", line_no)?;
+		match self.section {
+			Section::Code => unreachable!(),
+			Section::Constants => {
+				if index != self.constants.len() + 1 {
+					return error(line_no, format!("expected constant {}", self.constants.len() + 1));
+				}
+				let kst = match tokens.get(1) {
+					Some(token) => literal(token, true, line_no)?,
+					None => None
+				};
+				match kst {
+					Some(kst) => self.constants.push(kst),
+					None => return error(line_no, "expected a constant")
+				}
+			},
+			Section::Locals => {
+				let local = PendingLocal {
+					line_no,
+					name: name(tokens.get(1), line_no)?,
+					start: Operand::Int(integer(tokens.get(2), "a start pc", line_no)?),
+					end: Operand::Int(integer(tokens.get(3), "an end pc", line_no)?)
+				};
+				self.locals.push(local);
+			},
+			Section::Upvalues => self.upvals.push(name(tokens.get(1), line_no)?)
+		}
+		Ok(())
+	}
+
+	fn intern(&mut self, kst: Constants) -> u32 {
+		let same = |k: &Constants| match (k, &kst) {
+			(Constants::Number(a), Constants::Number(b)) => a.to_bits() == b.to_bits(),
+			(a, b) => a == b
+		};
+		match self.constants.iter().position(same) {
+			Some(i) => i as u32,
+			None => {
+				self.constants.push(kst);
+				self.constants.len() as u32 - 1
+			}
+		}
+	}
+
+	// a constant index, from `-1 - index`, a name or a literal
+	fn constant(&mut self, operand: &Operand, line_no: usize) -> Result<u32> {
+		match operand {
+			Operand::Int(n) if *n < 0 => Ok((-1 - n) as u32),
+			Operand::Name(name) => match self.const_names.get(name) {
+				Some(k) => Ok(*k),
+				None => error(line_no, format!("unknown constant `{}`", name))
+			},
+			Operand::Const(kst) => Ok(self.intern(kst.clone())),
+			Operand::Int(_) => error(line_no, "constants are written as -1 - index, a name or a literal")
+		}
+	}
+
+	fn field(&mut self, operand: &Operand, mode: &Arg, line_no: usize) -> Result<u32> {
+		// luac prints any B or C with the constant bit set as -1 - index
+		let k = match (mode, operand) {
+			(_, Operand::Int(n)) if *n >= 0 => match *n {
+				n if n <= 0x1ff => return Ok(n as u32),
+				_ => return error(line_no, "operand out of range")
+			},
+			(Arg::K, operand) | (_, operand @ Operand::Int(_)) => self.constant(operand, line_no)?,
+			_ => return error(line_no, "expected a register or number")
+		};
+		match k {
+			k if k <= MAXINDEXRK => Ok(rk_as_k(k)),
+			_ => error(line_no, "constant index too large for an RK operand")
+		}
+	}
+
+	// the pc a label or a 1 based pc points at
+	fn pc(&self, operand: &Operand, word_at: &[usize], line_no: usize) -> Result<u32> {
+		match operand {
+			Operand::Int(n) if *n >= 1 => Ok(*n as u32 - 1),
+			Operand::Name(label) => match self.labels.get(label) {
+				Some(i) => Ok(word_at[*i] as u32),
+				None => error(line_no, format!("unknown label `{}`", label))
+			},
+			_ => error(line_no, "expected a label or pc")
+		}
+	}
+
+	fn words(&mut self, pending: &Pending, at: usize, word_at: &[usize]) -> Result<Vec<u32>> {
+		let line_no = pending.line_no;
+		let op = pending.op;
+		let (b_mode, c_mode, mode) = opmode(op);
+		let expected = match mode {
+			Mode::Abc => 1 + (b_mode != Arg::N) as usize + (c_mode != Arg::N) as usize,
+			Mode::AsBx if op == 22 => 1,
+			_ => 2
+		};
+		if pending.operands.len() != expected {
+			return error(line_no, format!("{} takes {} operands", OPNAMES[op as usize], expected));
+		}
+
+		let mut operands = pending.operands.iter();
+		let a = match (mode, op) {
+			(Mode::AsBx, 22) => 0,
+			_ => match operands.next() {
+				Some(Operand::Int(n)) if (0..=0xff).contains(n) => *n as u32,
+				_ => return error(line_no, "A must be a number from 0 to 255")
+			}
+		};
+
+		let mut extra = None;
+		let word = match mode {
+			Mode::Abc => {
+				let b = match b_mode {
+					Arg::N => 0,
+					_ => match (op, operands.next().unwrap()) {
+						(4 | 8, Operand::Name(name)) => match self.upvals.iter().position(|upval| upval == name.as_str()) {
+							Some(i) => i as u32,
+							None => return error(line_no, format!("unknown upvalue `{}`", name))
+						},
+						(_, operand) => self.field(operand, &b_mode, line_no)?
+					}
+				};
+				let c = match c_mode {
+					Arg::N => 0,
+					_ => match (op, operands.next().unwrap()) {
+						(34, Operand::Int(n)) if *n > 0x1ff => {
+							extra = Some(*n as u32);
+							0
+						},
+						(_, operand) => self.field(operand, &c_mode, line_no)?
+					}
+				};
+				if op == 34 && c == 0 && extra.is_none() {
+					match pending.count {
+						Some(count) => extra = Some(count),
+						None => return error(line_no, "SETLIST with C=0 needs its count as the comment")
+					}
+				}
+				op | a << 6 | c << 14 | b << 23
+			},
+			Mode::Abx => {
+				let operand = operands.next().unwrap();
+				let bx = match (b_mode, operand) {
+					(Arg::K, operand) => self.constant(operand, line_no)?,
+					(_, Operand::Int(n)) if *n >= 0 => *n as u32,
+					(_, Operand::Name(name)) => match self.children.iter().position(|child| child.name.as_ref() == Some(name)) {
+						Some(i) => i as u32,
+						None => return error(line_no, format!("unknown function `{}`", name))
+					},
+					_ => return error(line_no, "expected a function")
+				};
+				if bx > 0x3ffff {
+					return error(line_no, "Bx out of range");
+				}
+				op | a << 6 | bx << 14
+			},
+			Mode::AsBx => {
+				let sbx = match operands.next().unwrap() {
+					Operand::Int(n) => *n,
+					Operand::Name(label) => match self.labels.get(label) {
+						Some(i) => word_at[*i] as i64 - at as i64 - 1,
+						None => return error(line_no, format!("unknown label `{}`", label))
+					},
+					Operand::Const(_) => return error(line_no, "expected a label or offset")
+				};
+				if !(-0x1ffff..=0x20000).contains(&sbx) {
+					return error(line_no, "jump out of range");
+				}
+				op | a << 6 | ((sbx + 0x1ffff) as u32) << 14
+			}
+		};
+
+		Ok(match extra {
+			Some(extra) => vec![word, extra],
+			None => vec![word]
+		})
+	}
+
+	// highest register the code touches, luac's stack size is at least 2
+	fn estimate_stack(words: &[u32]) -> u8 {
+		let mut top = 0;
+		let mut i = 0;
+		while i < words.len() {
+			let word = words[i];
+			let op = word & 0x3f;
+			let a = (word >> 6) & 0xff;
+			let c = (word >> 14) & 0x1ff;
+			let b = (word >> 23) & 0x1ff;
+			let (b_mode, c_mode, mode) = opmode(op);
+			let reg = match op {
+				3 => b, // LOADNIL
+				28 | 29 => a + b.saturating_sub(1).max(c.saturating_sub(2)), // CALL, TAILCALL
+				37 => a + b.saturating_sub(2), // VARARG
+				33 => a + 2 + c, // TFORLOOP
+				11 => a + 1, // SELF
+				31 | 32 => a + 3, // FORLOOP, FORPREP
+				34 => a + b, // SETLIST
+				22..=25 => 0, // JMP, EQ, LT, LE
+				_ => a
+			};
+			top = top.max(reg);
+			if let Mode::Abc = mode {
+				for (mode, x) in [(b_mode, b), (c_mode, c)] {
+					if mode == Arg::R || (mode == Arg::K && !is_k(x)) {
+						top = top.max(x);
+					}
+				}
+			}
+			i += if op == 34 && c == 0 { 2 } else { 1 };
+		}
+		(top + 1).clamp(2, 0xff) as u8
+	}
+
+	fn finish(mut self, parent_source: &LuaString) -> Result<Proto> {
+		let code = std::mem::take(&mut self.code);
+
+		// where every line of code lands, SETLIST may take 2 words
+		let mut word_at = vec![];
+		let mut size = 0;
+		for pending in &code {
+			word_at.push(size);
+			let big = matches!(pending.operands.get(2), Some(Operand::Int(n)) if *n == 0 || *n > 0x1ff);
+			size += if pending.op == 34 && big { 2 } else { 1 };
+		}
+		word_at.push(size);
+
+		let mut words = vec![];
+		let mut lines = vec![];
+		let mut origin = vec![];
+		for (i, pending) in code.iter().enumerate() {
+			for word in self.words(pending, word_at[i], &word_at)? {
+				words.push(word);
+				lines.push(pending.line);
+				origin.push(pending.line_no);
+			}
+		}
+
+		let mut locals = vec![];
+		for local in &self.locals {
+			let start = self.pc(&local.start, &word_at, local.line_no)?;
+			let end = self.pc(&local.end, &word_at, local.line_no)?;
+			locals.push(LocalVar { name: local.name.clone(), start_pc: start, end_pc: end });
+		}
+
+		let source = self.source.clone().unwrap_or_else(|| parent_source.clone());
+		let mut prototypes = vec![];
+		for child in std::mem::take(&mut self.children) {
+			prototypes.push(child.finish(&source)?);
+		}
+
+		// CLOSURE takes as many of the following words as its child has upvalues
+		let (instructions, layout) = match code::decode(&words, |i| prototypes.get(i as usize).map(|p| p.nupvals)) {
+			Ok(decoded) => decoded,
+			Err((pc, kind)) => return error(origin[pc], kind.to_string())
+		};
+		let (lines, locals) = code::debug_info(&layout, if self.has_lines { lines } else { vec![] }, locals);
+
+		if self.listing_children.is_some_and(|n| n != prototypes.len()) {
+			return error(self.line_no, "function count doesn't match the header");
+		}
+		Ok(Proto {
+			source: if &source == parent_source { None } else { Some(source) },
+			line_defined: self.line_defined,
+			last_line_defined: self.last_line_defined,
+			nupvals: self.nupvals.unwrap_or(self.upvals.len() as u8),
+			nparams: self.nparams,
+			is_vararg_flag: self.vararg,
+			max_stack_size: self.stack.unwrap_or_else(|| Self::estimate_stack(&words)),
+			instructions,
+			constants: self.constants,
+			prototypes,
+			source_lines: Some(lines),
+			locals: Some(locals),
+			upvals: Some(self.upvals)
+		})
+	}
+}
+
+struct Parser<'a> {
+	lines: Vec<&'a str>,
+	pos: usize
+}
+
+impl Parser<'_> {
+	// reads one function; a listing's function ends at the next header, a .function at its .end
+	fn function(&mut self, mut f: Function, dotted: bool) -> Result<Function> {
+		loop {
+			let Some(raw) = self.lines.get(self.pos) else {
+				if dotted {
+					return error(f.line_no, ".function without .end");
+				}
+				break;
+			};
+			let line_no = self.pos + 1;
+			let (code, comment) = split_comment(raw);
+			let code = code.trim();
+			if code.is_empty() {
+				self.pos += 1;
+				continue;
+			}
+
+			if code.starts_with("main <") || code.starts_with("function <") {
+				if f.started {
+					break;
+				}
+				let counts = self.lines.get(self.pos + 1).map_or("", |line| split_comment(line).0);
+				f.header(code, counts, line_no)?;
+				f.started = true;
+				self.pos += 2;
+				continue;
+			}
+
+			self.pos += 1;
+			f.started = true;
+			if code.starts_with("constants (") {
+				f.section = Section::Constants;
+				continue;
+			} else if code.starts_with("locals (") {
+				f.section = Section::Locals;
+				continue;
+			} else if code.starts_with("upvalues (") {
+				f.section = Section::Upvalues;
+				continue;
+			}
+
+			let tokens = tokenize(code, line_no)?;
+			match tokens[0] {
+				".function" => {
+					let mut child = Function::new(line_no, tokens.get(1).map(|name| name.to_string()), 0);
+					if tokens.len() > 2 {
+						child.line_defined = integer(tokens.get(2), "line defined", line_no)?;
+						child.last_line_defined = integer(tokens.get(3), "last line defined", line_no)?;
+					}
+					let child = self.function(child, true)?;
+					f.children.push(child);
+				},
+				".end" if dotted => return Ok(f),
+				".end" => return error(line_no, ".end without .function"),
+				directive if directive.starts_with('.') => f.directive(&tokens, line_no)?,
+				_ => match f.section {
+					Section::Code => f.instruction(&tokens, comment, line_no)?,
+					_ => f.listed(code, line_no)?
+				}
+			}
+		}
+
+		// in a listing the children follow their parent
+		for _ in 0..f.listing_children.unwrap_or(0) {
+			if self.pos >= self.lines.len() {
+				return error(f.line_no, "missing functions listed in the header");
+			}
+			let child = self.function(Function::new(self.pos + 1, None, 0), false)?;
+			f.children.push(child);
+		}
+		Ok(f)
+	}
+}
+
+pub fn assemble(text: &str) -> std::result::Result<Proto, AssembleError> {
+	let mut parser = Parser { lines: text.lines().collect(), pos: 0 };
+	let main = parser.function(Function::new(1, None, 2), false)?;
+	if parser.pos < parser.lines.len() {
+		return error(parser.pos + 1, "more than one main function");
+	}
+	main.finish(&"=?".into())
+}
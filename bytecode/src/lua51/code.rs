@@ -0,0 +1,187 @@
+// instructions as the vm sees them, one word each
+//
+// a CLOSURE is followed by one MOVE/GETUPVAL word per upvalue of its proto and a SETLIST with
+// C=0 by its count, which the vm skips over. protos keep those as a single instruction, so jump
+// offsets, line info and local pcs all count instructions and get converted here. pseudo words
+// share the line of their instruction, as they do in everything luac writes
+
+use crate::DecodeErrorKind;
+use super::{LocalVar, Proto};
+use super::instruction::{Capture, Instr, Instruction, Reg};
+
+pub(super) struct Code {
+	pub words: Vec<u32>,
+	pub starts: Vec<usize> // first word of every instruction, then the word count
+}
+
+impl Code {
+	fn len(&self) -> usize {
+		self.starts.len() - 1
+	}
+
+	fn total(&self) -> usize {
+		self.starts[self.len()]
+	}
+
+	// pcs outside the code are kept at the same distance from it
+	pub fn pc(&self, index: i64) -> i64 {
+		match index {
+			i if i < 0 => i,
+			i if i as usize > self.len() => self.total() as i64 + i - self.len() as i64,
+			i => self.starts[i as usize] as i64
+		}
+	}
+
+	// the instruction starting at pc, None when pc is in the middle of one
+	pub fn index(&self, pc: i64) -> Option<i64> {
+		match pc {
+			pc if pc < 0 => Some(pc),
+			pc if pc as usize > self.total() => Some(self.len() as i64 + pc - self.total() as i64),
+			pc => self.starts.binary_search(&(pc as usize)).ok().map(|i| i as i64)
+		}
+	}
+
+	// the instruction pc falls in
+	pub fn containing(&self, pc: i64) -> i64 {
+		self.index(pc).unwrap_or_else(|| self.starts.partition_point(|&start| start as i64 <= pc) as i64 - 1)
+	}
+
+	pub fn lines(&self, lines: &[u32]) -> Vec<u32> {
+		if lines.len() != self.len() {
+			return lines.to_vec();
+		}
+		lines.iter().enumerate().flat_map(|(i, line)| std::iter::repeat_n(*line, self.starts[i + 1] - self.starts[i])).collect()
+	}
+
+	pub fn locals(&self, locals: &[LocalVar]) -> Vec<LocalVar> {
+		locals.iter().map(|local| LocalVar {
+			name: local.name.clone(),
+			start_pc: self.pc(local.start_pc as i64) as u32,
+			end_pc: self.pc(local.end_pc as i64) as u32
+		}).collect()
+	}
+}
+
+fn offset(instr: &Instr) -> Option<i32> {
+	match instr {
+		Instr::Jump(_, sbx) | Instr::ForLoop(_, sbx) | Instr::ForPrep(_, sbx) => Some(*sbx),
+		_ => None
+	}
+}
+
+fn capture(word: u32) -> Option<Capture> {
+	let b = (word >> 23) & 0x1ff;
+	match word & 0x3f {
+		0 if b <= 0xff => Some(Capture::Local(Reg(b as u8))),
+		4 => Some(Capture::Upvalue(b as u16)),
+		_ => None
+	}
+}
+
+pub(super) fn encode(instructions: &[Instruction]) -> Code {
+	let mut starts = Vec::with_capacity(instructions.len() + 1);
+	let mut size = 0;
+	for instruction in instructions {
+		starts.push(size);
+		size += match &instruction.1 {
+			Instr::NOP => 0,
+			Instr::Closure(_, _, captures) => 1 + captures.len(),
+			Instr::SetList(_, _, c) if *c == 0 || *c > 0x1ff => 2,
+			_ => 1
+		};
+	}
+	starts.push(size);
+
+	let mut code = Code { words: Vec::with_capacity(size), starts };
+	for (i, instruction) in instructions.iter().enumerate() {
+		// NOPs are placeholders that take up no space, jumps to them go to whatever comes next
+		if let Instr::NOP = instruction.1 {
+			continue;
+		}
+		let mut word = instruction.serialize();
+		if let Some(sbx) = offset(&instruction.1) {
+			let target = code.pc(i as i64 + 1 + sbx as i64);
+			let sbx = target - (code.starts[i] as i64 + 1);
+			word = (word & 0x3fff) | (((sbx + 0x1ffff) as u32) << 14);
+		}
+		code.words.push(word);
+
+		match &instruction.1 {
+			Instr::Closure(_, _, captures) => code.words.extend(captures.iter().map(|capture| match capture {
+				Capture::Local(reg) => (reg.0 as u32) << 23,
+				Capture::Upvalue(idx) => 4 | ((*idx as u32 & 0x1ff) << 23)
+			})),
+			Instr::SetList(_, _, c) if *c == 0 || *c > 0x1ff => code.words.push(*c),
+			_ => {}
+		}
+	}
+	code
+}
+
+// nups gives the upvalue count of a child proto, errors come with the pc of the bad word
+pub(super) fn decode(words: &[u32], nups: impl Fn(u32) -> Option<u8>) -> Result<(Vec<Instruction>, Code), (usize, DecodeErrorKind)> {
+	let mut instructions = vec![];
+	let mut starts = vec![];
+	let mut pc = 0;
+	while pc < words.len() {
+		let word = words[pc];
+		let mut instruction = Instruction::try_from_instr(word, words.get(pc + 1)).map_err(|kind| (pc, kind))?;
+		starts.push(pc);
+
+		let mut size = 1;
+		match &mut instruction.1 {
+			Instr::SetList(..) if (word >> 14) & 0x1ff == 0 => size = 2,
+			Instr::Closure(_, b, captures) => {
+				for _ in 0..nups(*b).unwrap_or(0) {
+					let capture = words.get(pc + size).and_then(|word| capture(*word));
+					captures.push(capture.ok_or((pc + size, DecodeErrorKind::MissingCapture))?);
+					size += 1;
+				}
+			},
+			_ => {}
+		}
+		instructions.push(instruction);
+		pc += size;
+	}
+	starts.push(words.len());
+
+	let code = Code { words: words.to_vec(), starts };
+	for (i, instruction) in instructions.iter_mut().enumerate() {
+		if let Instr::Jump(_, sbx) | Instr::ForLoop(_, sbx) | Instr::ForPrep(_, sbx) = &mut instruction.1 {
+			let pc = code.starts[i] as i64;
+			let target = code.index(pc + 1 + *sbx as i64).ok_or((pc as usize, DecodeErrorKind::JumpIntoSequence))?;
+			*sbx = (target - (i as i64 + 1)) as i32;
+			instruction.2 = instruction.1.get_opmode();
+		}
+	}
+	Ok((instructions, code))
+}
+
+// line info and locals read alongside the words, moved over to instruction indices
+pub(super) fn debug_info(code: &Code, lines: Vec<u32>, locals: Vec<LocalVar>) -> (Vec<u32>, Vec<LocalVar>) {
+	let lines = if lines.len() == code.total() {
+		code.starts[..code.len()].iter().map(|&start| lines[start]).collect()
+	} else {
+		lines
+	};
+	let locals = locals.into_iter().map(|LocalVar { name, start_pc, end_pc }| LocalVar {
+		name,
+		start_pc: code.containing(start_pc as i64) as u32,
+		end_pc: code.containing(end_pc as i64) as u32
+	}).collect();
+	(lines, locals)
+}
+
+// the words of a proto with its line info and locals in word pcs
+pub(super) struct Words {
+	pub code: Code,
+	pub lines: Option<Vec<u32>>,
+	pub locals: Option<Vec<LocalVar>>
+}
+
+pub(super) fn words(proto: &Proto) -> Words {
+	let code = encode(&proto.instructions);
+	let lines = proto.source_lines.as_deref().map(|lines| code.lines(lines));
+	let locals = proto.locals.as_deref().map(|locals| code.locals(locals));
+	Words { code, lines, locals }
+}
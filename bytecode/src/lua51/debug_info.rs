@@ -0,0 +1,73 @@
+// line info and locals of a proto. pcs here are instruction indices, the same as jump offsets
+// count, so they line up with `instructions` rather than the words luac writes
+
+use crate::LuaString;
+use super::{LocalVar, Proto};
+use super::instruction::{Instruction, Reg};
+
+impl Proto {
+	// None when the line info is stripped or doesn't cover pc
+	pub fn line(&self, pc: usize) -> Option<u32> {
+		self.source_lines.as_ref()?.get(pc).copied()
+	}
+
+	pub fn set_line(&mut self, pc: usize, line: u32) {
+		if let Some(slot) = self.source_lines.as_mut().and_then(|lines| lines.get_mut(pc)) {
+			*slot = line;
+		}
+	}
+
+	// locals in scope at pc, the nth of them lives in register n (see luaF_getlocalname)
+	pub fn live_locals(&self, pc: usize) -> impl Iterator<Item = &LocalVar> {
+		self.locals.iter().flatten().filter(move |local| local.start_pc as usize <= pc && pc < local.end_pc as usize)
+	}
+
+	pub fn local_name(&self, reg: Reg, pc: usize) -> Option<&LuaString> {
+		self.live_locals(pc).nth(reg.0 as usize).map(|local| &local.name)
+	}
+
+	// empty line info is how a stripped chunk comes in, so only a full set gets edited along
+	fn lines_cover_code(&self) -> bool {
+		self.source_lines.as_ref().is_some_and(|lines| !lines.is_empty() && lines.len() == self.instructions.len())
+	}
+
+	// inserts before the instruction at index. the new one takes the line and locals of the one
+	// in front of it, jumps over the spot are left to the caller
+	pub fn insert_instruction(&mut self, index: usize, instruction: Instruction) {
+		let covered = self.lines_cover_code();
+		self.instructions.insert(index, instruction);
+		if let (true, Some(lines)) = (covered, &mut self.source_lines) {
+			let line = index.checked_sub(1).or((index < lines.len()).then_some(index)).map_or(self.line_defined, |pc| lines[pc]);
+			lines.insert(index, line);
+		}
+
+		for local in self.locals.iter_mut().flatten() {
+			// locals live from the start, like parameters, stay live from the start
+			if local.start_pc as usize > index || (local.start_pc as usize == index && index > 0) {
+				local.start_pc += 1;
+			}
+			if local.end_pc as usize >= index {
+				local.end_pc += 1;
+			}
+		}
+	}
+
+	// locals that were only live at index are left with an empty range
+	pub fn remove_instruction(&mut self, index: usize) -> Instruction {
+		let covered = self.lines_cover_code();
+		let instruction = self.instructions.remove(index);
+		if let (true, Some(lines)) = (covered, &mut self.source_lines) {
+			lines.remove(index);
+		}
+
+		for local in self.locals.iter_mut().flatten() {
+			if local.start_pc as usize > index {
+				local.start_pc -= 1;
+			}
+			if local.end_pc as usize > index {
+				local.end_pc -= 1;
+			}
+		}
+		instruction
+	}
+}
@@ -1,83 +1,173 @@
-use crate::{Bytecode};
-use super::{Reader, Constants, Local, Proto, instruction::Instruction, Header};
-
-fn header(reader: &mut Reader) -> Header {
-	assert_eq!(reader.bytes(4), b"\x1BLua");
-	assert_eq!(*reader.byte(), b'\x51');
-	assert_eq!(*reader.byte(), 0);
-	assert_eq!(*reader.byte(), 1); // no support for big endian yet
-	let int = *reader.byte();
-	assert_eq!(int, 4);
-	let size_t = *reader.byte();
-	assert_eq!(size_t, 4);
-	let instr = *reader.byte();
-	assert_eq!(instr, 4);
-	let lua_number = *reader.byte();
-	assert_eq!(lua_number, 8);
-	assert_eq!(*reader.byte(), 0);
-
-	(int, size_t, instr, lua_number)
+use crate::{Bytecode, DecodeError, DecodeErrorKind, Endianness, Field, MAX_NESTING};
+use super::{Reader, Constants, LocalVar, Proto, Header, code};
+
+pub(crate) fn expect_byte(reader: &mut Reader, expected: u8, kind: fn(u8) -> DecodeErrorKind) -> Result<u8, DecodeError> {
+	let at = reader.position();
+	let v = reader.byte()?;
+	if v != expected {
+		return Err(reader.error_at(at, kind(v)));
+	}
+	Ok(v)
+}
+
+fn size_byte(reader: &mut Reader, what: &'static str, allowed: &[u8]) -> Result<u8, DecodeError> {
+	let at = reader.position();
+	let size = reader.byte()?;
+	if !allowed.contains(&size) {
+		return Err(reader.error_at(at, DecodeErrorKind::UnsupportedSize { what, size }));
+	}
+	Ok(size)
+}
+
+fn header(reader: &mut Reader) -> Result<Header, DecodeError> {
+	reader.set_field(Field::Header);
+
+	let start = reader.position();
+	if reader.bytes(4)? != b"\x1BLua" {
+		return Err(reader.error_at(start, DecodeErrorKind::BadSignature));
+	}
+
+	expect_byte(reader, 0x51, DecodeErrorKind::UnsupportedVersion)?;
+	expect_byte(reader, 0, DecodeErrorKind::UnsupportedFormat)?;
+
+	layout(reader)
+}
+
+// everything from the endianness flag to the integral flag, shared with 5.2
+pub(crate) fn layout(reader: &mut Reader) -> Result<Header, DecodeError> {
+	let at = reader.position();
+	let endianness = match reader.byte()? {
+		0 => Endianness::Big,
+		1 => Endianness::Little,
+		v => return Err(reader.error_at(at, DecodeErrorKind::UnsupportedEndianness(v)))
+	};
+	reader.set_endianness(endianness);
+
+	let int_size = size_byte(reader, "int", &[2, 4, 8])?;
+	let size_t_size = size_byte(reader, "size_t", &[4, 8])?;
+	let instruction_size = size_byte(reader, "Instruction", &[4, 8])?;
+	let number_at = reader.position();
+	let number_size = reader.byte()?;
+
+	let at = reader.position();
+	let integral = match reader.byte()? {
+		0 => false,
+		1 => true,
+		v => return Err(reader.error_at(at, DecodeErrorKind::InvalidIntegralFlag(v)))
+	};
+
+	// float builds only come in single or double precision
+	let allowed: &[u8] = if integral { &[1, 2, 4, 8] } else { &[4, 8] };
+	if !allowed.contains(&number_size) {
+		return Err(reader.error_at(number_at, DecodeErrorKind::UnsupportedSize { what: "lua_Number", size: number_size }));
+	}
+
+	Ok(Header {
+		endianness,
+		int_size,
+		size_t_size,
+		instruction_size,
+		number_size,
+		integral
+	})
 }
 
-fn load_vec<V>(reader: &mut Reader, header: &Header, read: fn(&mut Reader, header: &Header) -> V) -> Vec<V> {
-	let n = reader.int(header.0 as usize);
+pub(crate) fn load_vec<V>(
+	reader: &mut Reader, header: &Header, count: Field, item: fn(usize) -> Field,
+	read: fn(&mut Reader, header: &Header) -> Result<V, DecodeError>
+) -> Result<Vec<V>, DecodeError> {
+	reader.set_field(count);
+	let n = reader.int(header.int_size as usize)?;
 	let mut list: Vec<V> = vec![];
-	for _ in 0..n {
-		list.push(read(reader, header));
+	for i in 0..n as usize {
+		reader.set_field(item(i));
+		list.push(read(reader, header)?);
 	};
-	
-	list
+
+	Ok(list)
 }
 
-fn chunk(reader: &mut Reader, header: &Header) -> Proto {
-	let source = reader.string(header.1);
-	let line_defined = reader.int(header.0 as usize);
-	let last_line_defined = reader.int(header.0 as usize);
-	let nupvals = *reader.byte();
-	let nparams = *reader.byte();
-	let is_vararg_flag = *reader.byte();
-	let max_stack_size = *reader.byte();
-
-	// instructions 
-	let instrs = load_vec(reader, header, |reader, header| 
-		reader.int(header.2 as usize));
-	let mut instructions = vec![];
-	for (i, instr) in instrs.iter().enumerate() {
-		instructions.push(Instruction::from_instr(*instr, instrs.get(i + 1)));
-	}
+pub(crate) fn constant(reader: &mut Reader, header: &Header, i: usize) -> Result<Constants, DecodeError> {
+	reader.set_field(Field::ConstantTag(i));
+	let at = reader.position();
+	let t = reader.byte()?;
 
-	// constants
-	let constants = load_vec(reader, header, |reader, header| {
-		let t = reader.byte();
-		match t {
-			0 => Constants::Nil,
-			1 => Constants::Boolean(*reader.byte() != 0u8),
-			3 => Constants::Number(reader.number(header.0 as usize)),
-			4 => Constants::String(reader.string(header.1)),
-			_ => unreachable!()
+	reader.set_field(Field::Constant(i));
+	Ok(match t {
+		0 => Constants::Nil,
+		1 => Constants::Boolean(reader.byte()? != 0u8),
+		3 => Constants::Number(reader.number(header.number_size, header.integral)?),
+		4 => Constants::String(reader.string(header.size_t_size)?),
+		_ => {
+			reader.set_field(Field::ConstantTag(i));
+			return Err(reader.error_at(at, DecodeErrorKind::UnknownConstantTag(t)))
 		}
-	});
+	})
+}
+
+fn chunk(reader: &mut Reader, header: &Header) -> Result<Proto, DecodeError> {
+	reader.set_field(Field::Source);
+	let source = reader.optional_string(header.size_t_size)?;
+	reader.set_field(Field::LineDefined);
+	let line_defined = reader.int(header.int_size as usize)?;
+	reader.set_field(Field::LastLineDefined);
+	let last_line_defined = reader.int(header.int_size as usize)?;
+	reader.set_field(Field::ProtoInfo);
+	let nupvals = reader.byte()?;
+	let nparams = reader.byte()?;
+	let is_vararg_flag = reader.byte()?;
+	let max_stack_size = reader.byte()?;
+
+	// instructions
+	let instrs_start = reader.position() + header.int_size as usize;
+	let instrs = load_vec(reader, header, Field::InstructionCount, Field::Instruction, |reader, header|
+		reader.int(header.instruction_size as usize))?;
+
+	// constants
+	reader.set_field(Field::ConstantCount);
+	let n = reader.int(header.int_size as usize)?;
+	let mut constants = vec![];
+	for i in 0..n as usize {
+		constants.push(constant(reader, header, i)?);
+	}
 
 	// prototypes
-	let prototypes = load_vec(reader, header, chunk);
+	reader.set_field(Field::ProtoCount);
+	if reader.path().len() >= MAX_NESTING {
+		return Err(reader.error(DecodeErrorKind::TooDeeplyNested));
+	}
+	let n = reader.int(header.int_size as usize)?;
+	let mut prototypes = vec![];
+	for i in 0..n as usize {
+		reader.enter_proto(i);
+		prototypes.push(chunk(reader, header)?);
+		reader.leave_proto();
+	}
 
 	// source lines
-	let source_lines = Some(load_vec(reader, header, |reader, header| 
-		reader.int(header.0 as usize)));
-	
+	let source_lines = load_vec(reader, header, Field::SourceLineCount, Field::SourceLine, |reader, header|
+		reader.int(header.int_size as usize))?;
+
 	// local list
-	let locals = Some(load_vec(reader, header, |reader, header| {
-		let name = reader.string(header.1);
-		let start = reader.int(header.0 as usize);
-		let end = reader.int(header.0 as usize);
-		Local(name, start, end)
-	}));
+	let locals = load_vec(reader, header, Field::LocalCount, Field::Local, |reader, header| {
+		let name = reader.string(header.size_t_size)?;
+		let start = reader.int(header.int_size as usize)?;
+		let end = reader.int(header.int_size as usize)?;
+		Ok(LocalVar { name, start_pc: start, end_pc: end })
+	})?;
 
 	// upvalues list
-	let upvals = Some(load_vec(reader, header, |reader, header| 
-		reader.string(header.1)));
+	let upvals = Some(load_vec(reader, header, Field::UpvalueCount, Field::Upvalue, |reader, header|
+		reader.string(header.size_t_size))?);
+
+	// grouping CLOSURE with its captures needs the upvalue counts of the children
+	let (instructions, code) = code::decode(&instrs, |i| prototypes.get(i as usize).map(|p| p.nupvals)).map_err(|(pc, kind)| {
+		reader.set_field(Field::Instruction(pc));
+		reader.error_at(instrs_start + pc * header.instruction_size as usize, kind)
+	})?;
+	let (source_lines, locals) = code::debug_info(&code, source_lines, locals);
 
-	Proto {
+	Ok(Proto {
 		source,
 		line_defined,
 		last_line_defined,
@@ -88,17 +178,27 @@ fn chunk(reader: &mut Reader, header: &Header) -> Proto {
 		instructions,
 		constants,
 		prototypes,
-		source_lines,
-		locals,
+		source_lines: Some(source_lines),
+		locals: Some(locals),
 		upvals
-	}
+	})
 }
 
-pub fn deserialize_bytecode(bytecode: &Bytecode) -> (Header, Proto){
-	let mut reader = Reader::new(bytecode);
+pub fn try_deserialize_bytecode(bytecode: &Bytecode) -> Result<(Header, Proto), DecodeError> {
+	let (header_data, proto, _) = deserialize_from(&mut Reader::new(bytecode))?;
+	Ok((header_data, proto))
+}
 
-	let header_data = header(&mut reader);
-	let proto = chunk(&mut reader, &header_data);
+// reads one chunk from wherever the reader is, also returning how many bytes it took up
+pub fn deserialize_from(reader: &mut Reader) -> Result<(Header, Proto, usize), DecodeError> {
+	let start = reader.position();
 
-	(header_data, proto)
-}
\ No newline at end of file
+	let header_data = header(reader)?;
+	let proto = chunk(reader, &header_data)?;
+
+	Ok((header_data, proto, reader.position() - start))
+}
+
+pub fn deserialize_bytecode(bytecode: &Bytecode) -> (Header, Proto) {
+	try_deserialize_bytecode(bytecode).unwrap_or_else(|err| panic!("{}", err))
+}
@@ -0,0 +1,266 @@
+// listings in the format of luac -l (and -l -l with full set)
+//
+// pointers can't be reproduced, so every function prints as its 1 based index in
+// preorder instead (main is 0x00000001). normalise 0x[0-9a-f]+ when diffing against luac.
+// vararg flags that `+` can't tell, like the compat arg bits, go on a .vararg line after
+// the header that luac doesn't print.
+
+use std::fmt::Write;
+
+use crate::LuaString;
+use super::{Constants, Header, Proto};
+use super::code::{self, Words};
+use super::instruction::{is_k, index_k};
+
+pub(super) const OPNAMES: [&str; 38] = [
+	"MOVE", "LOADK", "LOADBOOL", "LOADNIL", "GETUPVAL", "GETGLOBAL", "GETTABLE", "SETGLOBAL",
+	"SETUPVAL", "SETTABLE", "NEWTABLE", "SELF", "ADD", "SUB", "MUL", "DIV", "MOD", "POW", "UNM",
+	"NOT", "LEN", "CONCAT", "JMP", "EQ", "LT", "LE", "TEST", "TESTSET", "CALL", "TAILCALL",
+	"RETURN", "FORLOOP", "FORPREP", "TFORLOOP", "SETLIST", "CLOSE", "CLOSURE", "VARARG"
+];
+
+// operand modes from lopcodes.c
+#[derive(Clone, Copy, PartialEq)]
+pub(super) enum Arg {
+	N, // unused
+	U, // used
+	R, // register or jump offset
+	K  // constant or register/constant
+}
+
+#[derive(Clone, Copy)]
+pub(super) enum Mode {
+	Abc,
+	Abx,
+	AsBx
+}
+
+pub(super) fn opmode(op: u32) -> (Arg, Arg, Mode) {
+	use Arg::*;
+	match op {
+		0 | 3 | 18 | 19 | 20 => (R, N, Mode::Abc), // MOVE, LOADNIL, UNM, NOT, LEN
+		1 | 5 | 7 => (K, N, Mode::Abx), // LOADK, GETGLOBAL, SETGLOBAL
+		2 | 10 | 28 | 29 | 34 => (U, U, Mode::Abc), // LOADBOOL, NEWTABLE, CALL, TAILCALL, SETLIST
+		4 | 8 | 30 | 37 => (U, N, Mode::Abc), // GETUPVAL, SETUPVAL, RETURN, VARARG
+		6 | 11 => (R, K, Mode::Abc), // GETTABLE, SELF
+		9 | 12..=17 | 23..=25 => (K, K, Mode::Abc), // SETTABLE, arithmetic, EQ, LT, LE
+		21 => (R, R, Mode::Abc), // CONCAT
+		22 | 31 | 32 => (R, N, Mode::AsBx), // JMP, FORLOOP, FORPREP
+		26 | 27 => (R, U, Mode::Abc), // TEST, TESTSET
+		33 => (N, U, Mode::Abc), // TFORLOOP
+		36 => (U, N, Mode::Abx), // CLOSURE
+		_ => (N, N, Mode::Abc) // CLOSE
+	}
+}
+
+// printf's %.14g, which is LUA_NUMBER_FMT
+fn number(n: f64) -> String {
+	if n.is_nan() {
+		return if n.is_sign_negative() { "-nan" } else { "nan" }.to_string();
+	}
+	if n.is_infinite() {
+		return if n < 0.0 { "-inf" } else { "inf" }.to_string();
+	}
+	if n == 0.0 {
+		return if n.is_sign_negative() { "-0" } else { "0" }.to_string();
+	}
+
+	// the exponent after rounding to 14 significant digits decides the style
+	let sci = format!("{:.13e}", n);
+	let (mantissa, exp) = sci.split_once('e').unwrap();
+	let exp: i32 = exp.parse().unwrap();
+	let trim = |s: &str| {
+		if s.contains('.') { s.trim_end_matches('0').trim_end_matches('.').to_string() } else { s.to_string() }
+	};
+	if (-4..14).contains(&exp) {
+		trim(&format!("{:.*}", (13 - exp) as usize, n))
+	} else {
+		format!("{}e{}{:02}", trim(mantissa), if exp < 0 { '-' } else { '+' }, exp.abs())
+	}
+}
+
+// what printf's %s makes of a lua string
+fn c_str(s: &[u8]) -> String {
+	let end = s.iter().position(|&b| b == 0).unwrap_or(s.len());
+	String::from_utf8_lossy(&s[..end]).into_owned()
+}
+
+fn plural(n: usize) -> &'static str {
+	if n == 1 { "" } else { "s" }
+}
+
+fn count(proto: &Proto) -> usize {
+	1 + proto.prototypes.iter().map(count).sum::<usize>()
+}
+
+struct Printer {
+	out: String,
+	full: bool,
+	instruction_size: usize
+}
+
+impl Printer {
+	fn constant(&mut self, proto: &Proto, i: u32) {
+		match proto.constants.get(i as usize) {
+			Some(Constants::Nil) => self.out.push_str("nil"),
+			Some(Constants::Boolean(b)) => self.out.push_str(if *b { "true" } else { "false" }),
+			Some(Constants::Number(n)) => self.out.push_str(&number(*n)),
+			Some(Constants::String(s)) => write!(self.out, "\"{}\"", s.escaped()).unwrap(),
+			None => self.out.push('?')
+		}
+	}
+
+	fn header(&mut self, proto: &Proto, words: &Words, source: &LuaString, id: usize) {
+		let source = match source.first() {
+			Some(b'@' | b'=') => c_str(&source[1..]),
+			Some(0x1b) => "(bstring)".to_string(),
+			_ => "(string)".to_string()
+		};
+		let size = words.code.words.len();
+		writeln!(self.out, "\n{} <{}:{},{}> ({} instruction{}, {} bytes at 0x{:08x})",
+			if proto.line_defined == 0 { "main" } else { "function" }, source,
+			proto.line_defined, proto.last_line_defined,
+			size, plural(size), size * self.instruction_size, id).unwrap();
+
+		let locals = words.locals.as_ref().map_or(0, |locals| locals.len());
+		writeln!(self.out, "{}{} param{}, {} slot{}, {} upvalue{}, {} local{}, {} constant{}, {} function{}",
+			proto.nparams, if proto.is_vararg_flag != 0 { "+" } else { "" }, plural(proto.nparams as usize),
+			proto.max_stack_size, plural(proto.max_stack_size as usize),
+			proto.nupvals, plural(proto.nupvals as usize),
+			locals, plural(locals),
+			proto.constants.len(), plural(proto.constants.len()),
+			proto.prototypes.len(), plural(proto.prototypes.len())).unwrap();
+		if !matches!(proto.is_vararg_flag, 0 | 2) {
+			writeln!(self.out, ".vararg {}", proto.is_vararg_flag).unwrap();
+		}
+	}
+
+	fn code(&mut self, proto: &Proto, words: &Words, id: usize) {
+		let code = &words.code.words;
+		let mut pc = 0;
+		while pc < code.len() {
+			let i = code[pc];
+			let op = i & 0x3f;
+			let a = (i >> 6) & 0xff;
+			let c = (i >> 14) & 0x1ff;
+			let b = (i >> 23) & 0x1ff;
+			let bx = i >> 14;
+			let sbx = bx as i32 - 0x1ffff;
+			let rk = |x: u32| if is_k(x) { -1 - index_k(x) as i32 } else { x as i32 };
+
+			write!(self.out, "\t{}\t", pc + 1).unwrap();
+			match words.lines.as_ref().and_then(|lines| lines.get(pc)) {
+				Some(line) if *line > 0 => write!(self.out, "[{}]\t", line).unwrap(),
+				_ => self.out.push_str("[-]\t")
+			}
+			write!(self.out, "{:<9}\t", OPNAMES.get(op as usize).unwrap_or(&"NOP")).unwrap();
+
+			let (b_mode, c_mode, mode) = opmode(op);
+			match mode {
+				Mode::Abc => {
+					write!(self.out, "{}", a).unwrap();
+					if b_mode != Arg::N {
+						write!(self.out, " {}", rk(b)).unwrap();
+					}
+					if c_mode != Arg::N {
+						write!(self.out, " {}", rk(c)).unwrap();
+					}
+				},
+				Mode::Abx if b_mode == Arg::K => write!(self.out, "{} {}", a, -1 - bx as i64).unwrap(),
+				Mode::Abx => write!(self.out, "{} {}", a, bx).unwrap(),
+				Mode::AsBx if op == 22 => write!(self.out, "{}", sbx).unwrap(),
+				Mode::AsBx => write!(self.out, "{} {}", a, sbx).unwrap()
+			}
+
+			match op {
+				1 => { // LOADK
+					self.out.push_str("\t; ");
+					self.constant(proto, bx);
+				},
+				4 | 8 => { // GETUPVAL, SETUPVAL
+					let name = match &proto.upvals {
+						Some(upvals) if !upvals.is_empty() => upvals.get(b as usize).map_or("?".to_string(), |s| c_str(s)),
+						_ => "-".to_string()
+					};
+					write!(self.out, "\t; {}", name).unwrap();
+				},
+				5 | 7 => { // GETGLOBAL, SETGLOBAL
+					self.out.push_str("\t; ");
+					match proto.constants.get(bx as usize) {
+						Some(Constants::String(s)) => self.out.push_str(&c_str(s)),
+						_ => self.constant(proto, bx)
+					}
+				},
+				6 | 11 if is_k(c) => { // GETTABLE, SELF
+					self.out.push_str("\t; ");
+					self.constant(proto, index_k(c));
+				},
+				// MOD is missing here in luac 5.1 as well
+				9 | 12..=15 | 17 | 23..=25 if is_k(b) || is_k(c) => {
+					self.out.push_str("\t; ");
+					if is_k(b) { self.constant(proto, index_k(b)) } else { self.out.push('-') }
+					self.out.push(' ');
+					if is_k(c) { self.constant(proto, index_k(c)) } else { self.out.push('-') }
+				},
+				22 | 31 | 32 => write!(self.out, "\t; to {}", sbx + pc as i32 + 2).unwrap(), // JMP, FORLOOP, FORPREP
+				36 => { // CLOSURE
+					let child = id + 1 + proto.prototypes.iter().take(bx as usize).map(count).sum::<usize>();
+					write!(self.out, "\t; 0x{:08x}", child).unwrap();
+				},
+				34 if c == 0 => { // SETLIST, the count is in the next word
+					pc += 1;
+					write!(self.out, "\t; {}", code.get(pc).copied().unwrap_or(0) as i32).unwrap();
+				},
+				34 => write!(self.out, "\t; {}", c).unwrap(),
+				_ => {}
+			}
+			self.out.push('\n');
+			pc += 1;
+		}
+	}
+
+	fn lists(&mut self, proto: &Proto, words: &Words, id: usize) {
+		writeln!(self.out, "constants ({}) for 0x{:08x}:", proto.constants.len(), id).unwrap();
+		for i in 0..proto.constants.len() {
+			write!(self.out, "\t{}\t", i + 1).unwrap();
+			self.constant(proto, i as u32);
+			self.out.push('\n');
+		}
+
+		let locals = words.locals.as_deref().unwrap_or_default();
+		writeln!(self.out, "locals ({}) for 0x{:08x}:", locals.len(), id).unwrap();
+		for (i, local) in locals.iter().enumerate() {
+			writeln!(self.out, "\t{}\t{}\t{}\t{}", i, c_str(&local.name), local.start_pc + 1, local.end_pc + 1).unwrap();
+		}
+
+		let upvals = proto.upvals.as_deref().unwrap_or_default();
+		writeln!(self.out, "upvalues ({}) for 0x{:08x}:", upvals.len(), id).unwrap();
+		for (i, upval) in upvals.iter().enumerate() {
+			writeln!(self.out, "\t{}\t{}", i, c_str(upval)).unwrap();
+		}
+	}
+
+	// children without a source of their own use their parent's
+	fn function(&mut self, proto: &Proto, source: &LuaString, id: usize) {
+		let source = proto.source.as_ref().unwrap_or(source);
+		let words = code::words(proto);
+		self.header(proto, &words, source, id);
+		self.code(proto, &words, id);
+		if self.full {
+			self.lists(proto, &words, id);
+		}
+
+		let mut child = id + 1;
+		for p in &proto.prototypes {
+			self.function(p, source, child);
+			child += count(p);
+		}
+	}
+}
+
+// luac -l for full = false, luac -l -l for full = true
+pub fn disassemble(header: &Header, proto: &Proto, full: bool) -> String {
+	let mut printer = Printer { out: String::new(), full, instruction_size: header.instruction_size as usize };
+	printer.function(proto, &"=?".into(), 1);
+	printer.out
+}
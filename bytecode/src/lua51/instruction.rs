@@ -1,10 +1,13 @@
 use num_derive::FromPrimitive;
 use num_traits::FromPrimitive;
 
+use crate::DecodeErrorKind;
+
 use std::sync::atomic::{AtomicUsize, Ordering};
 static COUNTER: AtomicUsize = AtomicUsize::new(1);
 
-#[derive(FromPrimitive, Debug, Clone, Copy)]
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Opcode {
 	Move = 0,
 	LoadK,
@@ -52,6 +55,14 @@ impl Opcode {
 		Self::from_u8((instr & 0x3f) as u8).unwrap()
 	}
 
+	// NOP is ours, so it never shows up in a real chunk
+	fn try_from_instr(instr: u32) -> Result<Self, DecodeErrorKind> {
+		match Self::from_u8((instr & 0x3f) as u8) {
+			Some(Self::NOP) | None => Err(DecodeErrorKind::InvalidOpcode((instr & 0x3f) as u8)),
+			Some(op) => Ok(op)
+		}
+	}
+
 	fn to_instr(&self) -> u32 {
 		*self as u32
 	}
@@ -64,35 +75,21 @@ impl Abc {
 	fn bool_c(&self) -> bool { self.2 != 0 }
 
 	fn reg_a(&self) -> Reg { Reg(self.0) }
-	fn reg_b(&self) -> Reg {
+	fn reg_b(&self) -> Result<Reg, DecodeErrorKind> {
 		if self.1 <= 0xff {
-			return Reg(self.1 as u8)
+			return Ok(Reg(self.1 as u8))
 		}
-		panic!()
+		Err(DecodeErrorKind::InvalidOperand)
 	}
-	fn reg_c(&self) -> Reg {
+	fn reg_c(&self) -> Result<Reg, DecodeErrorKind> {
 		if self.2 <= 0xff {
-			return Reg(self.2 as u8)
+			return Ok(Reg(self.2 as u8))
 		}
-		panic!()
+		Err(DecodeErrorKind::InvalidOperand)
 	}
 
-	fn rk_b(&self) -> RegKst {
-		if self.1 > 0xff {
-			//println!("possible subtraction needed #1");
-			RegKst::K(Kst((self.1) as u32)) // constants should be subtracted 0x100 for value 
-		} else {
-			RegKst::R(Reg(self.1 as u8))
-		}
-	}
-	fn rk_c(&self) -> RegKst {
-		if self.2 > 0xff {
-			//println!("possible subtraction needed #1");
-			RegKst::K(Kst((self.2) as u32)) // constants should be subtracted 0x100 for value 
-		} else {
-			RegKst::R(Reg(self.2 as u8))
-		}
-	}
+	fn rk_b(&self) -> RegKst { RegKst::from_field(self.1 as u32) }
+	fn rk_c(&self) -> RegKst { RegKst::from_field(self.2 as u32) }
 }
 #[allow(non_snake_case)]
 fn ABC(instr: u32) -> Abc {
@@ -135,25 +132,76 @@ fn AsBx(instr: u32) -> Asbx {
 // }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Reg(pub u8);
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Kst(pub u32);
 
+// B and C fields with this bit set hold a constant index, see ISK in lopcodes.h
+pub const BITRK: u32 = 0x100;
+// the highest constant index an RK operand can hold
+pub const MAXINDEXRK: u32 = BITRK - 1;
+
+pub fn is_k(field: u32) -> bool {
+	field & BITRK != 0
+}
+
+pub fn index_k(field: u32) -> u32 {
+	field & !BITRK
+}
+
+pub fn rk_as_k(index: u32) -> u32 {
+	index | BITRK
+}
+
+// a register or an index into the constants, without the bias it gets in an instruction
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum RegKst {
 	R(Reg), K(Kst)
 }
 impl RegKst {
-	pub fn get(&self) -> u32 {
+	pub fn from_field(field: u32) -> Self {
+		if is_k(field) {
+			Self::K(Kst(index_k(field)))
+		} else {
+			Self::R(Reg(field as u8))
+		}
+	}
+
+	// None when the index is too large to be encoded as RK, it has to be loaded into a register
+	pub fn constant(index: u32) -> Option<Self> {
+		(index <= MAXINDEXRK).then_some(Self::K(Kst(index)))
+	}
+
+	pub fn fits(&self) -> bool {
+		match self {
+			Self::R(_) => true,
+			Self::K(kst) => kst.0 <= MAXINDEXRK
+		}
+	}
+
+	// the 9 bit B or C field. panics on indices that don't fit rather than encode another constant
+	pub fn field(&self) -> u32 {
 		match self {
 			Self::R(r) => r.0 as u32,
 			Self::K(kst) => {
-				// println!("possible subtraction needed #2");
-				kst.0
+				assert!(self.fits(), "constant {} is too large for an RK operand", kst.0);
+				rk_as_k(kst.0)
 			}
 		}
 	}
+
+	// the register or constant index
+	pub fn get(&self) -> u32 {
+		match self {
+			Self::R(r) => r.0 as u32,
+			Self::K(kst) => kst.0
+		}
+	}
+
 	pub fn set(&mut self, v: u32) {
 		match self {
 			Self::R(r) => r.0 = v as u8,
@@ -164,22 +212,34 @@ impl RegKst {
 
 pub type Upvalue = u16;
 
+// what a closure's upvalue is taken from, the MOVE or GETUPVAL following CLOSURE
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Capture {
+	Local(Reg),
+	Upvalue(Upvalue)
+}
+
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BinOp {
 	Add, Sub, Mul, Div, Mod, Pow
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum UnOp {
 	Unm, Not, Len
 }
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum BinCondOp {
 	Eq, Lt, Le
 }
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Instr {
 	Move(Reg, Reg),
 	LoadK(Reg, Kst),
@@ -208,7 +268,7 @@ pub enum Instr {
 	TForLoop(Reg, u16),
 	SetList(Reg, u16, u32),
 	Close(Reg),
-	Closure(Reg, u32), // Vec<u32>), //??
+	Closure(Reg, u32, Vec<Capture>),
 	VarArg(Reg, i32),
 	NOP
 }
@@ -224,7 +284,7 @@ pub enum Opmode {
 
 impl Instr {
 	pub fn from_opmode(op: Opcode, opmode: Opmode) -> Self {
-		if let Opmode::NOP = opmode {
+		if let (Opcode::NOP, _) | (_, Opmode::NOP) = (op, opmode) {
 			return Self::NOP;
 		}
 
@@ -233,40 +293,44 @@ impl Instr {
 	}
 
 	pub fn from_instr(instr: u32, next_instr: Option<&u32>) -> Self {
-		let op = Opcode::from_instr(instr);
+		Self::try_from_instr(instr, next_instr).expect("invalid instruction")
+	}
+
+	pub fn try_from_instr(instr: u32, next_instr: Option<&u32>) -> Result<Self, DecodeErrorKind> {
+		let op = Opcode::try_from_instr(instr)?;
 		let abc = ABC(instr);
 		let abx = ABx(instr);
 		let asbx = AsBx(instr);
 
-		match op {
-			Opcode::Move => Self::Move(abc.reg_a(), abc.reg_b()), // iABC
+		Ok(match op {
+			Opcode::Move => Self::Move(abc.reg_a(), abc.reg_b()?), // iABC
 			Opcode::LoadK => Self::LoadK(abc.reg_a(), abx.kst()), // iABx
 			Opcode::LoadBool => Self::LoadBool(abc.reg_a(), abc.bool_b(), abc.bool_c()), // iABC
-			Opcode::LoadNil => Self::LoadNil(abc.reg_a(), abc.reg_b()), // iABC
+			Opcode::LoadNil => Self::LoadNil(abc.reg_a(), abc.reg_b()?), // iABC
 			Opcode::GetUpval => Self::GetUpval(abc.reg_a(), abc.1), // iABC
 			Opcode::GetGlobal => Self::GetGlobal(abc.reg_a(), abx.kst()), // iABx
-			Opcode::GetTable => Self::GetTable(abc.reg_a(), abc.reg_b(), abc.rk_c()), // iABC
+			Opcode::GetTable => Self::GetTable(abc.reg_a(), abc.reg_b()?, abc.rk_c()), // iABC
 			Opcode::SetGlobal => Self::SetGlobal(abc.reg_a(), abx.kst()), // iABx
 			Opcode::SetUpval => Self::SetUpval(abc.reg_a(), abc.1), // iABC
 			Opcode::SetTable => Self::SetTable(abc.reg_a(), abc.rk_b(), abc.rk_c()), // iABC
-			Opcode::NewTable => Self::NewTable(abc.reg_a(), abc.reg_b(), abc.reg_c()), // iABC
-			Opcode::Self_ => Self::Self_(abc.reg_a(), abc.reg_b(), abc.rk_c()), // iABC
+			Opcode::NewTable => Self::NewTable(abc.reg_a(), abc.reg_b()?, abc.reg_c()?), // iABC
+			Opcode::Self_ => Self::Self_(abc.reg_a(), abc.reg_b()?, abc.rk_c()), // iABC
 			Opcode::Add => Self::BinOp(abc.reg_a(), abc.rk_b(), BinOp::Add, abc.rk_c()), // iABC
 			Opcode::Sub => Self::BinOp(abc.reg_a(), abc.rk_b(), BinOp::Sub, abc.rk_c()), // iABC
 			Opcode::Mul => Self::BinOp(abc.reg_a(), abc.rk_b(), BinOp::Mul, abc.rk_c()), // iABC
 			Opcode::Div => Self::BinOp(abc.reg_a(), abc.rk_b(), BinOp::Div, abc.rk_c()), // iABC
 			Opcode::Mod => Self::BinOp(abc.reg_a(), abc.rk_b(), BinOp::Mod, abc.rk_c()), // iABC
 			Opcode::Pow => Self::BinOp(abc.reg_a(), abc.rk_b(), BinOp::Pow, abc.rk_c()), // iABC
-			Opcode::Unm => Self::UnOp(abc.reg_a(), UnOp::Unm, abc.reg_b()), // iABC
-			Opcode::Not => Self::UnOp(abc.reg_a(), UnOp::Not, abc.reg_b()), // iABC
-			Opcode::Len => Self::UnOp(abc.reg_a(), UnOp::Len, abc.reg_b()), // iABC
-			Opcode::Concat => Self::Concat(abc.reg_a(), abc.reg_b(), abc.reg_c()), // iABC
+			Opcode::Unm => Self::UnOp(abc.reg_a(), UnOp::Unm, abc.reg_b()?), // iABC
+			Opcode::Not => Self::UnOp(abc.reg_a(), UnOp::Not, abc.reg_b()?), // iABC
+			Opcode::Len => Self::UnOp(abc.reg_a(), UnOp::Len, abc.reg_b()?), // iABC
+			Opcode::Concat => Self::Concat(abc.reg_a(), abc.reg_b()?, abc.reg_c()?), // iABC
 			Opcode::Jump => Self::Jump(abc.reg_a(), asbx.1 as i32), // iAsBx
 			Opcode::Eq => Self::BinCondOp(abc.bool_a(), abc.rk_b(), BinCondOp::Eq, abc.rk_c()), // iABC
 			Opcode::Lt => Self::BinCondOp(abc.bool_a(), abc.rk_b(), BinCondOp::Lt, abc.rk_c()), // iABC
 			Opcode::Le => Self::BinCondOp(abc.bool_a(), abc.rk_b(), BinCondOp::Le, abc.rk_c()), // iABC
 			Opcode::Test => Self::Test(abc.reg_a(), abc.bool_c()), // iABC
-			Opcode::TestSet => Self::TestSet(abc.reg_a(), abc.reg_b(), abc.bool_c()), // iABC
+			Opcode::TestSet => Self::TestSet(abc.reg_a(), abc.reg_b()?, abc.bool_c()), // iABC
 			Opcode::Call => Self::Call(abc.reg_a(), abc.1, abc.2), // iABC
 			Opcode::TailCall => Self::TailCall(abc.reg_a(), abc.1, abc.2), // iABC
 			Opcode::Return => Self::Return(abc.reg_a(), abc.1), // iABC
@@ -276,17 +340,17 @@ impl Instr {
 			Opcode::SetList => { // iABC
 				let set = if abc.2 == 0 {
 					// count += 1;
-					*next_instr.unwrap()
+					*next_instr.ok_or(DecodeErrorKind::MissingSetListWord)?
 				} else {
 					abc.2.into()
 				};
 				Self::SetList(abc.reg_a(), abc.1, set)
 			},
 			Opcode::Close => Self::Close(abc.reg_a()), // iABC
-			Opcode::Closure => Self::Closure(abc.reg_a(), abx.1),  // iABx
+			Opcode::Closure => Self::Closure(abc.reg_a(), abx.1, vec![]),  // iABx, captures come from the words after it
 			Opcode::VarArg => Self::VarArg(abc.reg_a(), abc.1.into()), // iABC
 			Opcode::NOP => Self::NOP
-		}
+		})
 	}
 	pub fn get_opmode(&self) -> Opmode {
 		match self {
@@ -298,15 +362,15 @@ impl Instr {
 			Self::LoadBool(a, b, c) => Opmode::iABC(a.0 as u32, *b as u32, *c as u32),
 			Self::GetUpval(a, b) => Opmode::iABC(a.0 as u32, *b as u32, 0),
 			Self::GetTable(a, b, c)
-			| Self::Self_(a, b, c) => Opmode::iABC(a.0 as u32, b.0 as u32, c.get()),
+			| Self::Self_(a, b, c) => Opmode::iABC(a.0 as u32, b.0 as u32, c.field()),
 			Self::SetUpval(a, b) => Opmode::iABC(a.0 as u32, *b as u32, 0),
-			Self::SetTable(a, b, c) => Opmode::iABC(a.0 as u32, b.get(), c.get()),
+			Self::SetTable(a, b, c) => Opmode::iABC(a.0 as u32, b.field(), c.field()),
 			Self::NewTable(a, b, c) => Opmode::iABC(a.0 as u32, b.0 as u32, c.0 as u32),
-			Self::BinOp(a, b, _, c) => Opmode::iABC(a.0 as u32, b.get(), c.get()),
+			Self::BinOp(a, b, _, c) => Opmode::iABC(a.0 as u32, b.field(), c.field()),
 			Self::UnOp(a, _, b) => Opmode::iABC(a.0 as u32, b.0 as u32, 0),
 			Self::Concat(a, b, c) => Opmode::iABC(a.0 as u32, b.0 as u32, c.0 as u32),
 			Self::Jump(a, b) => Opmode::iAsBx(a.0 as u32, *b as u32),
-			Self::BinCondOp(a, b, _, c) => Opmode::iABC(*a as u32, b.get(), c.get()),
+			Self::BinCondOp(a, b, _, c) => Opmode::iABC(*a as u32, b.field(), c.field()),
 			Self::Test(a, c) => Opmode::iABC(a.0 as u32, 0, *c as u32),
 			Self::TestSet(a, b, c) => Opmode::iABC(a.0 as u32, b.0 as u32, *c as u32),
 			Self::Call(a, b, c)
@@ -315,13 +379,65 @@ impl Instr {
 			Self::TForLoop(a, b) => Opmode::iABC(a.0 as u32, 0, *b as u32),
 			Self::ForLoop(a, b)
 			| Self::ForPrep(a, b) => Opmode::iAsBx(a.0 as u32, *b as u32),
-			Self::SetList(a, b, c) => Opmode::iABC(a.0 as u32, *b as u32, *c),
+			// counts that don't fit in C are stored in the next word
+			Self::SetList(a, b, c) => Opmode::iABC(a.0 as u32, *b as u32, if *c == 0 || *c > 0x1ff { 0 } else { *c }),
 			Self::Close(a) => Opmode::iABC(a.0 as u32, 0, 0),
-			Self::Closure(a, b) => Opmode::iABx(a.0 as u32, *b),
+			Self::Closure(a, b, _) => Opmode::iABx(a.0 as u32, *b),
 			Self::VarArg(a, b) => Opmode::iABC(a.0 as u32, *b as u32, 0),
 			Self::NOP => Opmode::NOP
 		}
 	}
+
+	// the only opcode these operands can go with
+	pub fn opcode(&self) -> Opcode {
+		match self {
+			Self::Move(..) => Opcode::Move,
+			Self::LoadK(..) => Opcode::LoadK,
+			Self::LoadBool(..) => Opcode::LoadBool,
+			Self::LoadNil(..) => Opcode::LoadNil,
+			Self::GetUpval(..) => Opcode::GetUpval,
+			Self::GetGlobal(..) => Opcode::GetGlobal,
+			Self::GetTable(..) => Opcode::GetTable,
+			Self::SetGlobal(..) => Opcode::SetGlobal,
+			Self::SetUpval(..) => Opcode::SetUpval,
+			Self::SetTable(..) => Opcode::SetTable,
+			Self::NewTable(..) => Opcode::NewTable,
+			Self::Self_(..) => Opcode::Self_,
+			Self::BinOp(_, _, op, _) => match op {
+				BinOp::Add => Opcode::Add,
+				BinOp::Sub => Opcode::Sub,
+				BinOp::Mul => Opcode::Mul,
+				BinOp::Div => Opcode::Div,
+				BinOp::Mod => Opcode::Mod,
+				BinOp::Pow => Opcode::Pow
+			},
+			Self::UnOp(_, op, _) => match op {
+				UnOp::Unm => Opcode::Unm,
+				UnOp::Not => Opcode::Not,
+				UnOp::Len => Opcode::Len
+			},
+			Self::Concat(..) => Opcode::Concat,
+			Self::Jump(..) => Opcode::Jump,
+			Self::BinCondOp(_, _, op, _) => match op {
+				BinCondOp::Eq => Opcode::Eq,
+				BinCondOp::Lt => Opcode::Lt,
+				BinCondOp::Le => Opcode::Le
+			},
+			Self::Test(..) => Opcode::Test,
+			Self::TestSet(..) => Opcode::TestSet,
+			Self::Call(..) => Opcode::Call,
+			Self::TailCall(..) => Opcode::TailCall,
+			Self::Return(..) => Opcode::Return,
+			Self::ForLoop(..) => Opcode::ForLoop,
+			Self::ForPrep(..) => Opcode::ForPrep,
+			Self::TForLoop(..) => Opcode::TForLoop,
+			Self::SetList(..) => Opcode::SetList,
+			Self::Close(..) => Opcode::Close,
+			Self::Closure(..) => Opcode::Closure,
+			Self::VarArg(..) => Opcode::VarArg,
+			Self::NOP => Opcode::NOP
+		}
+	}
 }
 
 // unique id
@@ -331,6 +447,25 @@ fn get_id() -> usize {
 
 #[derive(Debug, Clone)]
 pub struct Instruction(pub Opcode, pub Instr, pub Opmode, pub usize);
+
+// only the opcode and operands are stored, the opmode is worked out again and the id is a new one
+#[cfg(feature = "serde")]
+impl serde::Serialize for Instruction {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serde::Serialize::serialize(&(&self.0, &self.1), serializer)
+	}
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Instruction {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let (op, instr) = <(Opcode, Instr)>::deserialize(deserializer)?;
+		if op != instr.opcode() {
+			return Err(serde::de::Error::custom(format!("opcode {:?} doesn't go with {:?}", op, instr)));
+		}
+		Ok(Self::new(op, instr))
+	}
+}
 impl Instruction {
 	pub fn new(op: Opcode, instr: Instr) -> Self {
 		let mode = instr.get_opmode();
@@ -344,6 +479,13 @@ impl Instruction {
 		Self(op, inst, mode, get_id())
 	}
 
+	pub fn try_from_instr(instr: u32, next_instr: Option<&u32>) -> Result<Self, DecodeErrorKind> {
+		let op = Opcode::try_from_instr(instr)?;
+		let inst = Instr::try_from_instr(instr, next_instr)?;
+		let mode = inst.get_opmode();
+		Ok(Self(op, inst, mode, get_id()))
+	}
+
 	pub fn serialize(&self) -> u32 {
 		let opmode = self.2;
 
@@ -1,29 +1,52 @@
 mod reader;
 mod deserialize;
 pub mod instruction;
+mod code;
 mod writer;
 mod serialize;
+mod retarget;
+mod disassemble;
+mod annotate;
+mod assemble;
+mod verify;
+mod debug_info;
 pub mod luac;
 
+use crate::{Endianness, LuaString};
+
 pub use reader::Reader;
 pub use writer::Writer;
-pub use deserialize::deserialize_bytecode;
-pub use serialize::serialize_bytecode;
+pub use deserialize::{deserialize_bytecode, try_deserialize_bytecode, deserialize_from};
+pub use serialize::{serialize_bytecode, serialize_bytecode_with, serialize_to, serialize_to_with, DebugInfo, SerializeOptions};
+pub use retarget::{retarget, retarget_bytecode, Loss, LossKind};
+pub use disassemble::disassemble;
+pub use annotate::annotate;
+pub use assemble::{assemble, AssembleError};
+pub use verify::{verify, VerifyError, VerifyErrorKind};
+pub(crate) use deserialize::{expect_byte, layout, load_vec, constant};
+pub(crate) use serialize::dump_layout;
 
 #[derive(Debug, PartialEq, PartialOrd, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Constants {
 	Nil,
 	Boolean(bool),
 	Number(f64),
-	String(String)
+	String(LuaString)
 }
 
-#[derive(Debug, Clone)]
-pub struct Local(String, u32, u32);
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LocalVar {
+	pub name: LuaString,
+	pub start_pc: u32, // first instruction the local is live at
+	pub end_pc: u32 // first instruction it's dead again
+}
 
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Proto {
-	pub source: String,
+	pub source: Option<LuaString>, // None when stripped or the same as the parent's
 	pub line_defined: u32,
 	pub last_line_defined: u32,
 	pub nupvals: u8,
@@ -34,14 +57,14 @@ pub struct Proto {
 	pub constants: Vec<Constants>,
 	pub prototypes: Vec<Self>,
 	pub source_lines: Option<Vec<u32>>,
-	pub locals: Option<Vec<Local>>,
-	pub upvals: Option<Vec<String>>
+	pub locals: Option<Vec<LocalVar>>,
+	pub upvals: Option<Vec<LuaString>>
 }
 
 impl  Proto {
 	pub fn default() -> Self {
 		Self {
-			source: "@default.lua".to_string(),
+			source: Some("@default.lua".into()),
 			line_defined: 0,
 			last_line_defined: 0,
 			nupvals: 0,
@@ -58,4 +81,27 @@ impl  Proto {
 	}
 }
 
-pub type Header = (u8, u8, u8, u8); // int, size_t, instr, lua_number
\ No newline at end of file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Header {
+	pub endianness: Endianness,
+	pub int_size: u8,
+	pub size_t_size: u8,
+	pub instruction_size: u8,
+	pub number_size: u8,
+	pub integral: bool // lua_Number is an integer type
+}
+
+impl Default for Header {
+	// what a stock 32-bit x86 luac emits
+	fn default() -> Self {
+		Self {
+			endianness: Endianness::Little,
+			int_size: 4,
+			size_t_size: 4,
+			instruction_size: 4,
+			number_size: 8,
+			integral: false
+		}
+	}
+}
\ No newline at end of file
@@ -1,55 +1,243 @@
-pub struct Reader<'a>(&'a [u8], usize);
+use std::io::{BufRead, BufReader, Read};
+
+use crate::{DecodeError, DecodeErrorKind, Endianness, Field, LuaString};
+
+enum Source<'a> {
+	Slice(&'a [u8]),
+	// only ever consumes what was asked for, so the next chunk can be read from the same reader
+	Stream(Box<dyn BufRead + 'a>, Vec<u8>)
+}
+
+pub struct Reader<'a> {
+	source: Source<'a>,
+	position: usize,
+	endianness: Endianness,
+
+	// context for errors
+	field: Field,
+	path: Vec<usize>,
+
+	// where each field started, when asked for
+	marks: Option<Vec<(usize, Field, Vec<usize>)>>
+}
 
 impl<'a> Reader<'a> {
 	pub fn new(buffer: &'a [u8]) -> Self {
-		Self (buffer, 0)
+		Self::with_source(Source::Slice(buffer))
+	}
+
+	// buffered reader over a stream, the position counts every byte consumed from it
+	pub fn from_read<R: Read + 'a>(stream: R) -> Self {
+		Self::with_source(Source::Stream(Box::new(BufReader::new(stream)), vec![]))
+	}
+
+	fn with_source(source: Source<'a>) -> Self {
+		Self {
+			source,
+			position: 0,
+			endianness: Endianness::Little,
+			field: Field::Header,
+			path: vec![],
+			marks: None
+		}
 	}
 
+	// the whole buffer for slices, nothing for streams
 	pub fn as_bytes(&self) -> &[u8] {
-		&self.0
+		match &self.source {
+			Source::Slice(buffer) => buffer,
+			Source::Stream(..) => &[]
+		}
 	}
 
-	#[inline]
-	pub fn byte(&mut self) -> &u8 {
-		let v = &self.0[self.1];
-		self.1 += 1;
-		v
+	pub fn position(&self) -> usize {
+		self.position
+	}
+
+	// true once there is nothing left to read, for looping over back-to-back chunks
+	pub fn at_end(&mut self) -> Result<bool, DecodeError> {
+		match &mut self.source {
+			Source::Slice(buffer) => Ok(self.position == buffer.len()),
+			Source::Stream(stream, _) => match stream.fill_buf() {
+				Ok(buf) => Ok(buf.is_empty()),
+				Err(err) => Err(self.error(DecodeErrorKind::Io(err.kind())))
+			}
+		}
+	}
+
+	pub fn set_endianness(&mut self, endianness: Endianness) {
+		self.endianness = endianness;
+	}
+
+	pub fn set_field(&mut self, field: Field) {
+		self.field = field;
+		if let Some(marks) = &mut self.marks {
+			marks.push((self.position, field, self.path.clone()));
+		}
+	}
+
+	// from here on every set_field is remembered along with the offset and proto path
+	pub(crate) fn record_fields(&mut self) {
+		self.marks = Some(vec![]);
+	}
+
+	pub(crate) fn marks(&self) -> &[(usize, Field, Vec<usize>)] {
+		self.marks.as_deref().unwrap_or_default()
+	}
+
+	pub fn path(&self) -> &[usize] {
+		&self.path
+	}
+
+	pub fn enter_proto(&mut self, idx: usize) {
+		self.path.push(idx);
+	}
+
+	pub fn leave_proto(&mut self) {
+		self.path.pop();
+	}
+
+	pub fn error(&self, kind: DecodeErrorKind) -> DecodeError {
+		self.error_at(self.position, kind)
+	}
+
+	pub fn error_at(&self, offset: usize, kind: DecodeErrorKind) -> DecodeError {
+		DecodeError {
+			offset,
+			field: self.field,
+			path: self.path.clone(),
+			kind
+		}
 	}
 
 	#[inline]
-	pub fn bytes(&mut self, n: usize) -> &[u8] {
-		let v = &self.0[self.1..self.1 + n];
-		self.1 += n;
-		v
+	pub fn byte(&mut self) -> Result<u8, DecodeError> {
+		Ok(self.bytes(1)?[0])
 	}
 
 	#[inline]
-	pub fn int(&mut self, n: usize) -> u32 {
-		let bytes = self.bytes(n);
-		let mut sum: u32 = 0;
-		for i in (0..n).rev() {
-			if i == n {
-				continue
+	pub fn bytes(&mut self, n: usize) -> Result<&[u8], DecodeError> {
+		let position = self.position;
+		let remaining = match &mut self.source {
+			Source::Slice(buffer) => {
+				let remaining = buffer.len() - position;
+				if n <= remaining {
+					self.position += n;
+					return Ok(&buffer[position..position + n]);
+				}
+				remaining
+			}
+			Source::Stream(stream, scratch) => {
+				// grows with the data actually read rather than trusting n up front
+				scratch.clear();
+				if let Err(err) = stream.take(n as u64).read_to_end(scratch) {
+					return Err(self.error(DecodeErrorKind::Io(err.kind())));
+				}
+				let remaining = scratch.len();
+				if n == remaining {
+					self.position += n;
+					let Source::Stream(_, scratch) = &self.source else { unreachable!() };
+					return Ok(scratch);
+				}
+				self.position += remaining;
+				remaining
 			}
+		};
+		Err(self.error_at(position, DecodeErrorKind::UnexpectedEof { needed: n, remaining }))
+	}
 
-			sum = (sum << 8) + (bytes[i] as u32);
+	// everything up to the end of the buffer or stream
+	pub fn rest(&mut self) -> Result<Vec<u8>, DecodeError> {
+		let position = self.position;
+		match &mut self.source {
+			Source::Slice(buffer) => {
+				self.position = buffer.len();
+				Ok(buffer[position..].to_vec())
+			}
+			Source::Stream(stream, _) => {
+				let mut rest = vec![];
+				match stream.read_to_end(&mut rest) {
+					Ok(n) => {
+						self.position += n;
+						Ok(rest)
+					}
+					Err(err) => Err(self.error(DecodeErrorKind::Io(err.kind())))
+				}
+			}
+		}
+	}
+
+	// unsigned integer of n bytes in the chunk's byte order
+	#[inline]
+	pub fn uint(&mut self, n: usize) -> Result<u64, DecodeError> {
+		let start = self.position;
+		let endianness = self.endianness;
+		let bytes = self.bytes(n)?;
+		let mut sum: u64 = 0;
+		let mut push = |byte: u8| {
+			if sum > u64::MAX >> 8 {
+				return false;
+			}
+			sum = (sum << 8) | byte as u64;
+			true
+		};
+		let fits = match endianness {
+			Endianness::Little => bytes.iter().rev().all(|b| push(*b)),
+			Endianness::Big => bytes.iter().all(|b| push(*b))
+		};
+		if !fits {
+			return Err(self.error_at(start, DecodeErrorKind::IntegerOverflow));
 		}
-		sum
+		Ok(sum)
+	}
+
+	#[inline]
+	pub fn int(&mut self, n: usize) -> Result<u32, DecodeError> {
+		let start = self.position;
+		let v = self.uint(n)?;
+		u32::try_from(v).map_err(|_| self.error_at(start, DecodeErrorKind::IntegerOverflow))
 	}
 
+	// signed integer of n bytes
 	#[inline]
-	pub fn number(&mut self, int: usize) -> f64 {
-		let a = self.int(int) as u64;
-		let a2 = self.int(int) as u64;
-		let b = (a2 << 32) | a;
-		f64::from_bits(b)
+	pub fn integer(&mut self, n: usize) -> Result<i64, DecodeError> {
+		let bits = self.uint(n)?;
+		let shift = 64 - n.clamp(1, 8) as u32 * 8;
+		Ok((bits << shift) as i64 >> shift)
 	}
 
 	#[inline]
-	pub fn string(&mut self, size_t: u8) -> String {
-		let str_size = self.int(size_t as usize);
-		let mut str = self.bytes(str_size as usize).to_vec();
+	pub fn number(&mut self, size: u8, integral: bool) -> Result<f64, DecodeError> {
+		let start = self.position;
+		let bits = self.uint(size as usize)?;
+
+		match (integral, size) {
+			(true, 1..=8) => {
+				// sign extend from the top bit of the value
+				let shift = 64 - size as u32 * 8;
+				Ok(((bits << shift) as i64 >> shift) as f64)
+			}
+			(false, 4) => Ok(f32::from_bits(bits as u32) as f64),
+			(false, 8) => Ok(f64::from_bits(bits)),
+			_ => Err(self.error_at(start, DecodeErrorKind::UnsupportedSize { what: "lua_Number", size }))
+		}
+	}
+
+	// strings dumped from a NULL pointer have a size of 0
+	#[inline]
+	pub fn optional_string(&mut self, size_t: u8) -> Result<Option<LuaString>, DecodeError> {
+		let str_size = self.uint(size_t as usize)?;
+		if str_size == 0 {
+			return Ok(None);
+		}
+
+		let mut str = self.bytes(str_size as usize)?.to_vec();
 		str.pop(); // remove nul character
-		String::from_utf8(str).expect("invalid string")
+		Ok(Some(LuaString(str)))
+	}
+
+	#[inline]
+	pub fn string(&mut self, size_t: u8) -> Result<LuaString, DecodeError> {
+		Ok(self.optional_string(size_t)?.unwrap_or_default())
 	}
 }
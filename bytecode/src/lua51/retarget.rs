@@ -0,0 +1,141 @@
+// re-emitting a chunk for a different ABI (size_t, endianness, lua_Number, ...)
+
+use crate::{Bytecode, DecodeError};
+use super::{Constants, Header, Proto, code, try_deserialize_bytecode, serialize_bytecode};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LossKind {
+	// the target's lua_Number stores a different value than the source did
+	NumberRounded { index: usize, from: f64, to: f64 },
+	// a string whose length doesn't fit the target's size_t
+	StringTooLong { what: &'static str, len: usize },
+	// an int field (line info, counts, pcs) that doesn't fit the target's int
+	IntOutOfRange { what: &'static str, value: u64 }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Loss {
+	pub path: Vec<usize>, // nested proto indices, empty for the main proto
+	pub kind: LossKind
+}
+
+struct Retarget<'a> {
+	target: &'a Header,
+	path: Vec<usize>,
+	losses: Vec<Loss>
+}
+
+impl Retarget<'_> {
+	fn report(&mut self, kind: LossKind) {
+		self.losses.push(Loss { path: self.path.clone(), kind });
+	}
+
+	// the value a target vm would read back for n
+	fn number(&self, n: f64) -> f64 {
+		let size = self.target.number_size as u32;
+		match (self.target.integral, size) {
+			(true, _) => {
+				let shift = 64 - size * 8;
+				((n as i64) << shift >> shift) as f64
+			}
+			(false, 4) => n as f32 as f64,
+			_ => n
+		}
+	}
+
+	fn int(&mut self, what: &'static str, value: u64) {
+		let max = match self.target.int_size {
+			2 => i16::MAX as u64,
+			4 => i32::MAX as u64,
+			_ => i64::MAX as u64
+		};
+		if value > max {
+			self.report(LossKind::IntOutOfRange { what, value });
+		}
+	}
+
+	fn string(&mut self, what: &'static str, str: &[u8]) {
+		// the stored size includes the trailing nul
+		let max = match self.target.size_t_size {
+			4 => u32::MAX as u64,
+			_ => u64::MAX
+		};
+		if str.len() as u64 >= max {
+			self.report(LossKind::StringTooLong { what, len: str.len() });
+		}
+	}
+
+	fn proto(&mut self, proto: &mut Proto) {
+		if let Some(source) = &proto.source {
+			self.string("source", source);
+		}
+		self.int("line defined", proto.line_defined.into());
+		self.int("last line defined", proto.last_line_defined.into());
+		let words = code::words(proto);
+		self.int("instruction count", words.code.words.len() as u64);
+		self.int("constant count", proto.constants.len() as u64);
+		self.int("proto count", proto.prototypes.len() as u64);
+
+		for (index, kst) in proto.constants.iter_mut().enumerate() {
+			match kst {
+				Constants::Number(n) => {
+					let to = self.number(*n);
+					if to.to_bits() != n.to_bits() && !(to.is_nan() && n.is_nan()) {
+						self.report(LossKind::NumberRounded { index, from: *n, to });
+						*n = to;
+					}
+				}
+				Constants::String(s) => self.string("string constant", s),
+				_ => {}
+			}
+		}
+
+		for (i, child) in proto.prototypes.iter_mut().enumerate() {
+			self.path.push(i);
+			self.proto(child);
+			self.path.pop();
+		}
+
+		if let Some(lines) = &words.lines {
+			self.int("source line count", lines.len() as u64);
+			for line in lines {
+				self.int("source line", (*line).into());
+			}
+		}
+		if let Some(locals) = &words.locals {
+			self.int("local count", locals.len() as u64);
+			for local in locals {
+				self.string("local name", &local.name);
+				self.int("local start pc", local.start_pc.into());
+				self.int("local end pc", local.end_pc.into());
+			}
+		}
+		if let Some(upvals) = &proto.upvals {
+			self.int("upvalue name count", upvals.len() as u64);
+			for upval in upvals {
+				self.string("upvalue name", upval);
+			}
+		}
+	}
+}
+
+// converts a proto so it can be serialized with the target header; the returned proto holds
+// exactly what the target vm will see, and every difference from the source is reported
+pub fn retarget(proto: &Proto, target: &Header) -> (Proto, Vec<Loss>) {
+	let mut converted = proto.clone();
+	let mut state = Retarget {
+		target,
+		path: vec![],
+		losses: vec![]
+	};
+	state.proto(&mut converted);
+
+	(converted, state.losses)
+}
+
+pub fn retarget_bytecode(bytecode: &Bytecode, target: &Header) -> Result<(Vec<u8>, Vec<Loss>), DecodeError> {
+	let (_, proto) = try_deserialize_bytecode(bytecode)?;
+	let (converted, losses) = retarget(&proto, target);
+
+	Ok((serialize_bytecode(target, &converted), losses))
+}
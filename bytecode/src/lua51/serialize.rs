@@ -1,41 +1,75 @@
-use super::{Writer, Header, Proto, Constants, instruction::Opcode};
+use crate::{Endianness, LuaString};
+use super::{Writer, Header, Proto, Constants, code};
+
+// the line of an instruction, from the path to its proto and its index
+type Lines<'a> = dyn Fn(&[usize], usize) -> u32 + 'a;
+
+// what happens to line info, local names, upvalue names and source names on the way out
+#[derive(Clone, Copy, Default)]
+pub enum DebugInfo<'a> {
+	#[default]
+	Keep,
+	// drops all of it, like luac -s
+	Strip,
+	// source goes on the main proto and every nested one inherits it. None keeps what's there
+	Replace { source: Option<&'a LuaString>, lines: Option<&'a Lines<'a>> }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct SerializeOptions<'a> {
+	pub debug_info: DebugInfo<'a>
+}
 
 fn dump_header(writer: &mut Writer, header: &Header) {
 	writer.bytes(b"\x1BLua".to_vec());
 	writer.byte(b'\x51');
 	writer.byte(0);
-	writer.byte(1);
-	writer.byte(header.0);
-	writer.byte(header.1);
-	writer.byte(header.2);
-	writer.byte(header.3);
-	writer.byte(0);
+	dump_layout(writer, header);
+}
+
+pub(crate) fn dump_layout(writer: &mut Writer, header: &Header) {
+	writer.byte(match header.endianness {
+		Endianness::Big => 0,
+		Endianness::Little => 1
+	});
+	writer.byte(header.int_size);
+	writer.byte(header.size_t_size);
+	writer.byte(header.instruction_size);
+	writer.byte(header.number_size);
+	writer.byte(header.integral as u8);
 }
 
 fn dump_vector<I>(writer: &mut Writer, list: I, n: u32, header: &Header, dump: fn(&mut Writer, header: &Header, I::Item)) where I: Iterator {
-	writer.int(n, header.0);
+	writer.int(n, header.int_size);
 	list.enumerate().for_each(|(_i, v)| dump(writer, header, v));
 }
 
-fn dump_chunk(writer: &mut Writer, header: &Header, proto: &Proto) {
-	writer.string(&proto.source, header.1);
-	writer.int(proto.line_defined, header.0);
-	writer.int(proto.last_line_defined, header.0);
+fn dump_chunk(writer: &mut Writer, header: &Header, proto: &Proto, options: &SerializeOptions, path: &mut Vec<usize>) {
+	let source = match options.debug_info {
+		DebugInfo::Strip => None,
+		DebugInfo::Replace { source: Some(source), .. } => path.is_empty().then_some(source),
+		_ => proto.source.as_ref()
+	};
+	writer.optional_string(source.map(|source| &source[..]), header.size_t_size);
+	writer.int(proto.line_defined, header.int_size);
+	writer.int(proto.last_line_defined, header.int_size);
 	writer.byte(proto.nupvals);
 	writer.byte(proto.nparams);
 	writer.byte(proto.is_vararg_flag);
 	writer.byte(proto.max_stack_size);
 
 	// instructions
-	dump_vector(writer, 
-		proto.instructions.iter(), proto.instructions.len() as u32, header, 
-		|writer, header, instr| {
-			if let Opcode::NOP = instr.0 {
-				println!("almost serialzied a NOP! skipping");
-			} else {
-				writer.int(instr.serialize(), header.2);
-			}
-	});
+	let mut words = code::words(proto);
+	match options.debug_info {
+		DebugInfo::Keep => {},
+		DebugInfo::Strip => (words.lines, words.locals) = (None, None),
+		DebugInfo::Replace { lines, .. } => if let Some(lines) = lines {
+			let lines: Vec<_> = (0..proto.instructions.len()).map(|i| lines(path, i)).collect();
+			words.lines = Some(words.code.lines(&lines));
+		}
+	}
+	dump_vector(writer, words.code.words.iter(), words.code.words.len() as u32, header, |writer, header, word|
+		writer.int(*word, header.instruction_size));
 
 	// constants
 	dump_vector(writer, 
@@ -44,50 +78,75 @@ fn dump_chunk(writer: &mut Writer, header: &Header, proto: &Proto) {
 			match kst {
 				Constants::Nil => writer.byte(0),
 				&Constants::Boolean(b) => {writer.byte(1); writer.byte(b as u8)},
-				Constants::String(s) => {writer.byte(4); writer.string(s, header.1)},
-				&Constants::Number(n) => {writer.byte(3); writer.number(n, header.0)}
+				Constants::String(s) => {writer.byte(4); writer.string(s, header.size_t_size)},
+				&Constants::Number(n) => {writer.byte(3); writer.number(n, header.number_size, header.integral)}
 			}
 	});
 
 	// protos
-	dump_vector(writer, proto.prototypes.iter(), proto.prototypes.len() as u32, header, |writer, header, proto| 
-		dump_chunk(writer, header, proto));
+	writer.int(proto.prototypes.len() as u32, header.int_size);
+	for (i, child) in proto.prototypes.iter().enumerate() {
+		path.push(i);
+		dump_chunk(writer, header, child, options, path);
+		path.pop();
+	}
 
 	// source lines
-	if let Some(source_lines) = &proto.source_lines {
+	if let Some(source_lines) = &words.lines {
 		dump_vector(writer, source_lines.iter(), source_lines.len() as u32, header, |writer, header, l| {
-			writer.int(*l, header.0)
+			writer.int(*l, header.int_size)
 		});
 	} else {
-		writer.int(0, header.0);
+		writer.int(0, header.int_size);
 	}
 
 	// locals
-	if let Some(locals) = &proto.locals {
+	if let Some(locals) = &words.locals {
 		dump_vector(writer, locals.iter(), locals.len() as u32, header, |writer, header, local| {
-			writer.string(&local.0, header.1);
-			writer.int(local.1, header.0);
-			writer.int(local.2, header.0);
+			writer.string(&local.name, header.size_t_size);
+			writer.int(local.start_pc, header.int_size);
+			writer.int(local.end_pc, header.int_size);
 		});
 	} else {
-		writer.int(0, header.0);
+		writer.int(0, header.int_size);
 	}
 
 	// upvalues
-	if let Some(upvals) = &proto.upvals {
+	let upvals = match options.debug_info {
+		DebugInfo::Strip => None,
+		_ => proto.upvals.as_ref()
+	};
+	if let Some(upvals) = upvals {
 		dump_vector(writer, upvals.iter(), upvals.len() as u32, header, |writer, header, upval| {
-			writer.string(upval, header.1);
+			writer.string(upval, header.size_t_size);
 		});
 	} else {
-		writer.int(0, header.0);
+		writer.int(0, header.int_size);
 	}
 }
 
 pub fn serialize_bytecode(header: &Header, proto: &Proto) -> Vec<u8> {
+	serialize_bytecode_with(header, proto, &SerializeOptions::default())
+}
+
+pub fn serialize_bytecode_with(header: &Header, proto: &Proto, options: &SerializeOptions) -> Vec<u8> {
 	let mut writer = Writer::new();
+	serialize_to_with(&mut writer, header, proto, options);
+	writer.as_bytes().into()
+}
 
-	dump_header(&mut writer, header);
-	dump_chunk(&mut writer, header, proto);
+// appends one chunk to the writer, returning how many bytes it took up. errors from a
+// streaming writer only show up once it is flushed
+pub fn serialize_to(writer: &mut Writer, header: &Header, proto: &Proto) -> usize {
+	serialize_to_with(writer, header, proto, &SerializeOptions::default())
+}
 
-	writer.as_bytes().into()
-}
\ No newline at end of file
+pub fn serialize_to_with(writer: &mut Writer, header: &Header, proto: &Proto, options: &SerializeOptions) -> usize {
+	let start = writer.position();
+	writer.set_endianness(header.endianness);
+
+	dump_header(writer, header);
+	dump_chunk(writer, header, proto, options, &mut vec![]);
+
+	writer.position() - start
+}
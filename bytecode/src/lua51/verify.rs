@@ -0,0 +1,343 @@
+// static checks on code before it reaches the vm, after luaG_checkcode in 5.1's ldebug.c
+//
+// the reference only refuses jumps onto a SETLIST count, this also refuses jumps onto the
+// MOVE/GETUPVAL words following a CLOSURE since the vm never executes those either
+
+use std::fmt;
+
+use super::{Constants, Proto};
+use super::code::{self, Code};
+use super::instruction::{is_k, index_k, Instr};
+use super::disassemble::{opmode, Arg, Mode};
+
+const MAXSTACK: u8 = 250;
+
+const VARARG_HASARG: u8 = 1;
+const VARARG_ISVARARG: u8 = 2;
+const VARARG_NEEDSARG: u8 = 4;
+
+const MOVE: u32 = 0;
+const LOADBOOL: u32 = 2;
+const GETUPVAL: u32 = 4;
+const GETGLOBAL: u32 = 5;
+const SETGLOBAL: u32 = 7;
+const SETUPVAL: u32 = 8;
+const SELF: u32 = 11;
+const CONCAT: u32 = 21;
+const JMP: u32 = 22;
+const EQ: u32 = 23;
+const TESTSET: u32 = 27;
+const CALL: u32 = 28;
+const TAILCALL: u32 = 29;
+const RETURN: u32 = 30;
+const FORLOOP: u32 = 31;
+const FORPREP: u32 = 32;
+const TFORLOOP: u32 = 33;
+const SETLIST: u32 = 34;
+const CLOSURE: u32 = 36;
+const VARARG: u32 = 37;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyErrorKind {
+	StackTooLarge(u8),
+	ParamsExceedStack,
+	BadVarargFlags(u8),
+	TooManyUpvalueNames,
+	LineInfoMismatch,
+	MissingReturn,
+	InvalidOpcode(u8),
+	RegisterOutOfRange(i64),
+	ConstantOutOfRange(u32),
+	RkOutOfRange(u32),
+	UnusedOperand,
+	JumpOutOfRange(i64),
+	JumpIntoPseudo(usize), // target word
+	MissingJump,
+	OpenResultNotConsumed,
+	GlobalNotString(u32),
+	UpvalueOutOfRange(u32),
+	ConcatTooFewOperands,
+	NoLoopResults,
+	MissingSetListCount,
+	ClosureOutOfRange(u32),
+	BadCapture,
+	NotVararg
+}
+
+impl fmt::Display for VerifyErrorKind {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			Self::StackTooLarge(size) => write!(f, "stack size {} is above {}", size, MAXSTACK),
+			Self::ParamsExceedStack => write!(f, "parameters don't fit in the stack"),
+			Self::BadVarargFlags(flags) => write!(f, "inconsistent vararg flags {}", flags),
+			Self::TooManyUpvalueNames => write!(f, "more upvalue names than upvalues"),
+			Self::LineInfoMismatch => write!(f, "line info doesn't cover the code"),
+			Self::MissingReturn => write!(f, "code doesn't end with RETURN"),
+			Self::InvalidOpcode(op) => write!(f, "invalid opcode {}", op),
+			Self::RegisterOutOfRange(reg) => write!(f, "register {} is outside the stack", reg),
+			Self::ConstantOutOfRange(idx) => write!(f, "constant {} out of range", idx),
+			Self::RkOutOfRange(idx) => write!(f, "constant {} is too large for an RK operand", idx),
+			Self::UnusedOperand => write!(f, "unused operand is not 0"),
+			Self::JumpOutOfRange(target) => write!(f, "jump to {} is outside the code", target),
+			Self::JumpIntoPseudo(target) => write!(f, "jump to {} lands on a SETLIST count or CLOSURE capture", target),
+			Self::MissingJump => write!(f, "test is not followed by JMP"),
+			Self::OpenResultNotConsumed => write!(f, "open results are not consumed by the next instruction"),
+			Self::GlobalNotString(idx) => write!(f, "global name constant {} is not a string", idx),
+			Self::UpvalueOutOfRange(idx) => write!(f, "upvalue {} out of range", idx),
+			Self::ConcatTooFewOperands => write!(f, "CONCAT of fewer than two values"),
+			Self::NoLoopResults => write!(f, "TFORLOOP without results"),
+			Self::MissingSetListCount => write!(f, "SETLIST with C=0 is missing its count"),
+			Self::ClosureOutOfRange(idx) => write!(f, "closure of missing proto {}", idx),
+			Self::BadCapture => write!(f, "CLOSURE is not followed by a MOVE or GETUPVAL per upvalue"),
+			Self::NotVararg => write!(f, "VARARG in a function that isn't vararg")
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyError {
+	pub path: Vec<usize>, // nested proto indices, empty for the main proto
+	pub pc: Option<usize>, // index into the instructions, None for problems with the proto as a whole
+	pub kind: VerifyErrorKind
+}
+
+impl fmt::Display for VerifyError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{} in proto main", self.kind)?;
+		for idx in &self.path {
+			write!(f, "/{}", idx)?;
+		}
+		if let Some(pc) = self.pc {
+			write!(f, " at pc {}", pc)?;
+		}
+		Ok(())
+	}
+}
+
+impl std::error::Error for VerifyError {}
+
+// works on the words, so pcs in here are word pcs until they make it into an error
+struct Verifier<'a> {
+	proto: &'a Proto,
+	path: &'a [usize],
+	code: Code
+}
+
+impl Verifier<'_> {
+	fn error(&self, pc: Option<usize>, kind: VerifyErrorKind) -> VerifyError {
+		let pc = pc.map(|pc| self.code.containing(pc as i64) as usize);
+		VerifyError { path: self.path.to_vec(), pc, kind }
+	}
+
+	fn reg(&self, pc: usize, reg: i64) -> Result<(), VerifyError> {
+		if reg >= self.proto.max_stack_size as i64 {
+			return Err(self.error(Some(pc), VerifyErrorKind::RegisterOutOfRange(reg)));
+		}
+		Ok(())
+	}
+
+	fn arg(&self, pc: usize, r: u32, mode: Arg) -> Result<(), VerifyError> {
+		match mode {
+			Arg::N if r != 0 => Err(self.error(Some(pc), VerifyErrorKind::UnusedOperand)),
+			Arg::R => self.reg(pc, r as i64),
+			Arg::K if is_k(r) => self.constant(pc, index_k(r)),
+			Arg::K => self.reg(pc, r as i64),
+			_ => Ok(())
+		}
+	}
+
+	fn constant(&self, pc: usize, idx: u32) -> Result<(), VerifyError> {
+		if idx as usize >= self.proto.constants.len() {
+			return Err(self.error(Some(pc), VerifyErrorKind::ConstantOutOfRange(idx)));
+		}
+		Ok(())
+	}
+
+	fn target(&self, pc: usize, target: i64) -> Result<(), VerifyError> {
+		if target < 0 || target >= self.code.words.len() as i64 {
+			return Err(self.error(Some(pc), VerifyErrorKind::JumpOutOfRange(target)));
+		}
+		// words that don't start an instruction are the ones the vm skips over
+		if self.code.index(target).is_none() {
+			return Err(self.error(Some(pc), VerifyErrorKind::JumpIntoPseudo(target as usize)));
+		}
+		Ok(())
+	}
+
+	// an instruction leaving an open number of results has to be followed by one taking them
+	fn open(&self, pc: usize) -> Result<(), VerifyError> {
+		match self.code.words.get(pc + 1) {
+			Some(&next) if matches!(next & 0x3f, CALL | TAILCALL | RETURN | SETLIST) && (next >> 23) & 0x1ff == 0 => Ok(()),
+			_ => Err(self.error(Some(pc), VerifyErrorKind::OpenResultNotConsumed))
+		}
+	}
+
+	fn precheck(&self) -> Result<(), VerifyError> {
+		let proto = self.proto;
+		let flags = proto.is_vararg_flag;
+		if proto.max_stack_size > MAXSTACK {
+			return Err(self.error(None, VerifyErrorKind::StackTooLarge(proto.max_stack_size)));
+		}
+		if proto.nparams as u32 + (flags & VARARG_HASARG) as u32 > proto.max_stack_size as u32 {
+			return Err(self.error(None, VerifyErrorKind::ParamsExceedStack));
+		}
+		if flags & !7 != 0 || (flags & VARARG_NEEDSARG != 0 && flags & VARARG_HASARG == 0) {
+			return Err(self.error(None, VerifyErrorKind::BadVarargFlags(flags)));
+		}
+		if proto.upvals.as_ref().is_some_and(|upvals| upvals.len() > proto.nupvals as usize) {
+			return Err(self.error(None, VerifyErrorKind::TooManyUpvalueNames));
+		}
+		if proto.source_lines.as_ref().is_some_and(|lines| !lines.is_empty() && lines.len() != proto.instructions.len()) {
+			return Err(self.error(None, VerifyErrorKind::LineInfoMismatch));
+		}
+		if self.code.words.last().map(|i| i & 0x3f) != Some(RETURN) {
+			return Err(self.error(None, VerifyErrorKind::MissingReturn));
+		}
+		Ok(())
+	}
+
+	fn instruction(&self, pc: usize) -> Result<usize, VerifyError> {
+		let proto = self.proto;
+		let i = self.code.words[pc];
+		let op = i & 0x3f;
+		let a = (i >> 6) & 0xff;
+		let c = (i >> 14) & 0x1ff;
+		let mut b = (i >> 23) & 0x1ff;
+		let bx = i >> 14;
+		let sbx = bx as i64 - 0x1ffff;
+		let (a, b_i, c_i) = (a as i64, b as i64, c as i64);
+
+		if op > VARARG {
+			return Err(self.error(Some(pc), VerifyErrorKind::InvalidOpcode(op as u8)));
+		}
+		self.reg(pc, a)?;
+		let (b_mode, c_mode, mode) = opmode(op);
+		match mode {
+			Mode::Abc => {
+				self.arg(pc, b, b_mode)?;
+				self.arg(pc, c, c_mode)?;
+			},
+			Mode::Abx => {
+				b = bx;
+				if b_mode == Arg::K {
+					self.constant(pc, bx)?;
+				}
+			},
+			Mode::AsBx => self.target(pc, pc as i64 + 1 + sbx)?
+		}
+
+		// EQ, LT, LE, TEST and TESTSET skip the JMP after them
+		if (EQ..=TESTSET).contains(&op) {
+			if pc + 2 >= self.code.words.len() {
+				return Err(self.error(Some(pc), VerifyErrorKind::JumpOutOfRange(pc as i64 + 2)));
+			}
+			if self.code.words[pc + 1] & 0x3f != JMP {
+				return Err(self.error(Some(pc), VerifyErrorKind::MissingJump));
+			}
+		}
+
+		match op {
+			LOADBOOL if c == 1 => self.target(pc, pc as i64 + 2)?,
+			GETUPVAL | SETUPVAL if b >= proto.nupvals as u32 => {
+				return Err(self.error(Some(pc), VerifyErrorKind::UpvalueOutOfRange(b)));
+			},
+			GETGLOBAL | SETGLOBAL if !matches!(proto.constants[b as usize], Constants::String(_)) => {
+				return Err(self.error(Some(pc), VerifyErrorKind::GlobalNotString(b)));
+			},
+			SELF => self.reg(pc, a + 1)?,
+			CONCAT if b >= c => return Err(self.error(Some(pc), VerifyErrorKind::ConcatTooFewOperands)),
+			TFORLOOP => {
+				if c == 0 {
+					return Err(self.error(Some(pc), VerifyErrorKind::NoLoopResults));
+				}
+				self.reg(pc, a + 2 + c_i)?;
+			},
+			FORLOOP | FORPREP => self.reg(pc, a + 3)?,
+			CALL | TAILCALL => {
+				if b != 0 {
+					self.reg(pc, a + b_i - 1)?;
+				}
+				if c == 0 {
+					self.open(pc)?;
+				} else if c != 1 {
+					self.reg(pc, a + c_i - 2)?;
+				}
+			},
+			RETURN if b > 1 => self.reg(pc, a + b_i - 2)?,
+			SETLIST => {
+				if b > 0 {
+					self.reg(pc, a + b_i)?;
+				}
+				if c == 0 {
+					// the count is the next word, which can't be the final RETURN
+					if pc + 1 >= self.code.words.len() - 1 {
+						return Err(self.error(Some(pc), VerifyErrorKind::MissingSetListCount));
+					}
+					return Ok(pc + 2);
+				}
+			},
+			CLOSURE => {
+				let Some(child) = proto.prototypes.get(bx as usize) else {
+					return Err(self.error(Some(pc), VerifyErrorKind::ClosureOutOfRange(bx)));
+				};
+				let nup = child.nupvals as usize;
+				if pc + nup >= self.code.words.len() {
+					return Err(self.error(Some(pc), VerifyErrorKind::BadCapture));
+				}
+				if !self.code.words[pc + 1..=pc + nup].iter().all(|i| matches!(i & 0x3f, MOVE | GETUPVAL)) {
+					return Err(self.error(Some(pc), VerifyErrorKind::BadCapture));
+				}
+			},
+			VARARG => {
+				if proto.is_vararg_flag & VARARG_ISVARARG == 0 || proto.is_vararg_flag & VARARG_NEEDSARG != 0 {
+					return Err(self.error(Some(pc), VerifyErrorKind::NotVararg));
+				}
+				if b == 0 {
+					self.open(pc)?;
+				}
+				self.reg(pc, a + b_i - 2)?;
+			},
+			_ => {}
+		}
+		Ok(pc + 1)
+	}
+}
+
+fn check(proto: &Proto, path: &mut Vec<usize>) -> Result<(), VerifyError> {
+	for (i, instruction) in proto.instructions.iter().enumerate() {
+		let error = |kind| Err(VerifyError { path: path.to_vec(), pc: Some(i), kind });
+		match &instruction.1 {
+			Instr::Closure(_, b, captures) if proto.prototypes.get(*b as usize).is_some_and(|child| child.nupvals as usize != captures.len()) => {
+				return error(VerifyErrorKind::BadCapture);
+			},
+			// these can't be encoded at all, so they're caught before the words are made
+			Instr::GetTable(_, _, c) | Instr::Self_(_, _, c) if !c.fits() => {
+				return error(VerifyErrorKind::RkOutOfRange(c.get()));
+			},
+			Instr::SetTable(_, b, c) | Instr::BinOp(_, b, _, c) | Instr::BinCondOp(_, b, _, c) => {
+				if let Some(rk) = [b, c].into_iter().find(|rk| !rk.fits()) {
+					return error(VerifyErrorKind::RkOutOfRange(rk.get()));
+				}
+			},
+			_ => {}
+		}
+	}
+	let verifier = Verifier { proto, path, code: code::encode(&proto.instructions) };
+	verifier.precheck()?;
+	let mut pc = 0;
+	while pc < verifier.code.words.len() {
+		pc = verifier.instruction(pc)?;
+	}
+
+	for (i, child) in proto.prototypes.iter().enumerate() {
+		path.push(i);
+		check(child, path)?;
+		path.pop();
+	}
+	Ok(())
+}
+
+// checks a proto and all of its children, returning the first problem found
+pub fn verify(proto: &Proto) -> Result<(), VerifyError> {
+	check(proto, &mut vec![])
+}
@@ -1,48 +1,138 @@
-pub struct Writer(Vec<u8>);
-impl Writer {
+use std::io::{self, Write};
+
+use crate::Endianness;
+
+// how much a streaming writer holds before passing it on
+const BUFFER_SIZE: usize = 8192;
+
+pub struct Writer<'a> {
+	buffer: Vec<u8>,
+	endianness: Endianness,
+
+	// bytes already passed on to the sink, and the first error it returned
+	sink: Option<Box<dyn Write + 'a>>,
+	flushed: usize,
+	error: Option<io::Error>
+}
+
+impl<'a> Writer<'a> {
 	pub fn new() -> Self {
-		Self(vec![])
+		Self {
+			buffer: vec![],
+			endianness: Endianness::Little,
+			sink: None,
+			flushed: 0,
+			error: None
+		}
+	}
+
+	// buffered writer over a stream, call flush once done to see whether it all got there
+	pub fn from_write<W: Write + 'a>(sink: W) -> Self {
+		Self { sink: Some(Box::new(sink)), ..Self::new() }
+	}
+
+	// everything written so far, or only what hasn't been flushed yet for streams
+	pub fn as_bytes(&self) -> &[u8] {
+		self.buffer.as_slice()
+	}
+
+	// bytes written since the writer was made, flushed or not
+	pub fn position(&self) -> usize {
+		self.flushed + self.buffer.len()
 	}
 
-	pub fn as_bytes<'a>(&'a self) -> &'a [u8] {
-		&self.0.as_slice()
+	pub fn set_endianness(&mut self, endianness: Endianness) {
+		self.endianness = endianness;
+	}
+
+	// passes the buffer on to the sink, returning the total number of bytes written to it
+	pub fn flush(&mut self) -> io::Result<usize> {
+		self.drain();
+		if let Some(err) = self.error.take() {
+			return Err(err);
+		}
+		if let Some(sink) = &mut self.sink {
+			sink.flush()?;
+		}
+		Ok(self.position())
+	}
+
+	fn drain(&mut self) {
+		let Some(sink) = &mut self.sink else { return };
+		// once the sink failed nothing else is sent, flush reports the error
+		if self.error.is_none() {
+			if let Err(err) = sink.write_all(&self.buffer) {
+				self.error = Some(err);
+			}
+		}
+		self.flushed += self.buffer.len();
+		self.buffer.clear();
 	}
 
 	#[inline]
 	pub fn byte(&mut self, b: u8) {
-		self.0.push(b);
+		self.buffer.push(b);
+		if self.sink.is_some() && self.buffer.len() >= BUFFER_SIZE {
+			self.drain();
+		}
 	}
 
 	#[inline]
 	pub fn bytes(&mut self, bs: Vec<u8>) {
-		for b in bs {
-			self.0.push(b)
+		self.buffer.extend_from_slice(&bs);
+		if self.sink.is_some() && self.buffer.len() >= BUFFER_SIZE {
+			self.drain();
 		}
 	}
 
+	// unsigned integer of s bytes in the writer's byte order, truncating anything above
 	#[inline]
-	pub fn int(&mut self, n: u32, s: u8) {
+	pub fn uint(&mut self, n: u64, s: u8) {
 		let mut num = n;
+		let mut le = Vec::with_capacity(s as usize);
+
+		for _i in 0..s {
+			le.push((num & 0xff) as u8);
+			num >>= 8;
+		}
 
-		for _i in (0..s).rev() {
-			self.byte((num & 0xff) as u8);
-			num = num >> 8; 
+		if let Endianness::Big = self.endianness {
+			le.reverse();
 		}
+		self.bytes(le);
+	}
+
+	#[inline]
+	pub fn int(&mut self, n: u32, s: u8) {
+		self.uint(n as u64, s);
 	}
 
 	#[inline]
-	pub fn string(&mut self, str: &str, s: u8) {
-		self.int(str.len() as u32 + 1, s);
-		self.bytes(str.as_bytes().to_vec());
+	pub fn integer(&mut self, n: i64, s: u8) {
+		self.uint(n as u64, s);
+	}
+
+	#[inline]
+	pub fn string(&mut self, str: &[u8], s: u8) {
+		self.uint(str.len() as u64 + 1, s);
+		self.bytes(str.to_vec());
 		self.byte(0);
 	}
 
 	#[inline]
-	pub fn number(&mut self, n: f64, s: u8) {
-		let num = n.to_bits();
-		let a0 = (num >> 32) as u32;
-		let a1 = num as u32;
-		self.int(a1, s);
-		self.int(a0, s);
+	pub fn optional_string(&mut self, str: Option<&[u8]>, s: u8) {
+		match str {
+			Some(str) => self.string(str, s),
+			None => self.uint(0, s)
+		}
+	}
+
+	#[inline]
+	pub fn number(&mut self, n: f64, size: u8, integral: bool) {
+		match (integral, size) {
+			(true, _) => self.uint(n as i64 as u64, size),
+			(false, 4) => self.uint((n as f32).to_bits() as u64, size),
+			_ => self.uint(n.to_bits(), size)
+		}
 	}
-}
\ No newline at end of file
+}
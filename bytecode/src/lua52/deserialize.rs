@@ -0,0 +1,133 @@
+use crate::{Bytecode, DecodeError, DecodeErrorKind, Field, MAX_NESTING};
+use crate::lua51::{expect_byte, layout, load_vec, constant};
+use super::{Reader, LocalVar, Proto, UpvalueDesc, instruction::Instruction, Header};
+
+// catches chunks mangled by text-mode transfers
+pub(crate) const LUAC_TAIL: &[u8] = b"\x19\x93\r\n\x1a\n";
+
+fn header(reader: &mut Reader) -> Result<Header, DecodeError> {
+	reader.set_field(Field::Header);
+
+	let start = reader.position();
+	if reader.bytes(4)? != b"\x1BLua" {
+		return Err(reader.error_at(start, DecodeErrorKind::BadSignature));
+	}
+
+	expect_byte(reader, 0x52, DecodeErrorKind::UnsupportedVersion)?;
+	expect_byte(reader, 0, DecodeErrorKind::UnsupportedFormat)?;
+	let header = layout(reader)?;
+
+	let at = reader.position();
+	if reader.bytes(LUAC_TAIL.len())? != LUAC_TAIL {
+		return Err(reader.error_at(at, DecodeErrorKind::BadTail));
+	}
+
+	Ok(header)
+}
+
+fn chunk(reader: &mut Reader, header: &Header) -> Result<Proto, DecodeError> {
+	reader.set_field(Field::LineDefined);
+	let line_defined = reader.int(header.int_size as usize)?;
+	reader.set_field(Field::LastLineDefined);
+	let last_line_defined = reader.int(header.int_size as usize)?;
+	reader.set_field(Field::ProtoInfo);
+	let nparams = reader.byte()?;
+	let is_vararg_flag = reader.byte()?;
+	let max_stack_size = reader.byte()?;
+
+	// instructions
+	let instrs_start = reader.position() + header.int_size as usize;
+	let instrs = load_vec(reader, header, Field::InstructionCount, Field::Instruction, |reader, header|
+		reader.int(header.instruction_size as usize))?;
+	let mut instructions = vec![];
+	for (i, instr) in instrs.iter().enumerate() {
+		let instruction = Instruction::try_from_instr(*instr).map_err(|kind| {
+			reader.set_field(Field::Instruction(i));
+			reader.error_at(instrs_start + i * header.instruction_size as usize, kind)
+		})?;
+		instructions.push(instruction);
+	}
+
+	// constants
+	reader.set_field(Field::ConstantCount);
+	let n = reader.int(header.int_size as usize)?;
+	let mut constants = vec![];
+	for i in 0..n as usize {
+		constants.push(constant(reader, header, i)?);
+	}
+
+	// prototypes
+	reader.set_field(Field::ProtoCount);
+	if reader.path().len() >= MAX_NESTING {
+		return Err(reader.error(DecodeErrorKind::TooDeeplyNested));
+	}
+	let n = reader.int(header.int_size as usize)?;
+	let mut prototypes = vec![];
+	for i in 0..n as usize {
+		reader.enter_proto(i);
+		prototypes.push(chunk(reader, header)?);
+		reader.leave_proto();
+	}
+
+	// upvalues
+	let upvalues = load_vec(reader, header, Field::UpvalueDescCount, Field::UpvalueDesc, |reader, _| {
+		let in_stack = reader.byte()? != 0;
+		let index = reader.byte()?;
+		Ok(UpvalueDesc { in_stack, index })
+	})?;
+
+	// debug info, starting with the source which is NULL when stripped
+	reader.set_field(Field::Source);
+	let source = reader.optional_string(header.size_t_size)?;
+
+	// source lines
+	let source_lines = Some(load_vec(reader, header, Field::SourceLineCount, Field::SourceLine, |reader, header|
+		reader.int(header.int_size as usize))?);
+
+	// local list
+	let locals = Some(load_vec(reader, header, Field::LocalCount, Field::Local, |reader, header| {
+		let name = reader.string(header.size_t_size)?;
+		let start = reader.int(header.int_size as usize)?;
+		let end = reader.int(header.int_size as usize)?;
+		Ok(LocalVar { name, start_pc: start, end_pc: end })
+	})?);
+
+	// upvalue names
+	let upvals = Some(load_vec(reader, header, Field::UpvalueCount, Field::Upvalue, |reader, header|
+		reader.string(header.size_t_size))?);
+
+	Ok(Proto {
+		source,
+		line_defined,
+		last_line_defined,
+		nparams,
+		is_vararg_flag,
+		max_stack_size,
+		instructions,
+		constants,
+		prototypes,
+		upvalues,
+		source_lines,
+		locals,
+		upvals
+	})
+}
+
+pub fn try_deserialize_bytecode(bytecode: &Bytecode) -> Result<(Header, Proto), DecodeError> {
+	let (header_data, proto, _) = deserialize_from(&mut Reader::new(bytecode))?;
+	Ok((header_data, proto))
+}
+
+// reads one chunk from wherever the reader is, also returning how many bytes it took up
+pub fn deserialize_from(reader: &mut Reader) -> Result<(Header, Proto, usize), DecodeError> {
+	let start = reader.position();
+
+	let header_data = header(reader)?;
+	let proto = chunk(reader, &header_data)?;
+
+	Ok((header_data, proto, reader.position() - start))
+}
+
+pub fn deserialize_bytecode(bytecode: &Bytecode) -> (Header, Proto) {
+	try_deserialize_bytecode(bytecode).unwrap_or_else(|err| panic!("{}", err))
+}
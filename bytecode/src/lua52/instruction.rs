@@ -0,0 +1,329 @@
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+
+use crate::DecodeErrorKind;
+pub use crate::lua51::instruction::{Reg, Kst, RegKst, Upvalue, BinOp, UnOp, BinCondOp};
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+static COUNTER: AtomicUsize = AtomicUsize::new(1);
+
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+	Move = 0,
+	LoadK,
+	LoadKx,
+	LoadBool,
+	LoadNil,
+	GetUpval,
+	GetTabUp,
+	GetTable,
+	SetTabUp,
+	SetUpval,
+	SetTable,
+	NewTable,
+	Self_,
+	Add,
+	Sub,
+	Mul,
+	Div,
+	Mod,
+	Pow,
+	Unm,
+	Not,
+	Len,
+	Concat,
+	Jump,
+	Eq,
+	Lt,
+	Le,
+	Test,
+	TestSet,
+	Call,
+	TailCall,
+	Return,
+	ForLoop,
+	ForPrep,
+	TForCall,
+	TForLoop,
+	SetList,
+	Closure,
+	VarArg,
+	ExtraArg
+}
+
+impl Opcode {
+	fn try_from_instr(instr: u32) -> Result<Self, DecodeErrorKind> {
+		Self::from_u8((instr & 0x3f) as u8).ok_or(DecodeErrorKind::InvalidOpcode((instr & 0x3f) as u8))
+	}
+
+	fn to_instr(self) -> u32 {
+		self as u32
+	}
+}
+
+// same field layout as 5.1, plus Ax taking up everything above the opcode
+struct Abc(u8, u16, u16);
+impl Abc {
+	fn bool_a(&self) -> bool { self.0 != 0 }
+	fn bool_b(&self) -> bool { self.1 != 0 }
+	fn bool_c(&self) -> bool { self.2 != 0 }
+
+	fn reg_a(&self) -> Reg { Reg(self.0) }
+	fn reg_b(&self) -> Result<Reg, DecodeErrorKind> {
+		if self.1 <= 0xff {
+			return Ok(Reg(self.1 as u8))
+		}
+		Err(DecodeErrorKind::InvalidOperand)
+	}
+	fn reg_c(&self) -> Result<Reg, DecodeErrorKind> {
+		if self.2 <= 0xff {
+			return Ok(Reg(self.2 as u8))
+		}
+		Err(DecodeErrorKind::InvalidOperand)
+	}
+
+	fn rk_b(&self) -> RegKst { RegKst::from_field(self.1 as u32) }
+	fn rk_c(&self) -> RegKst { RegKst::from_field(self.2 as u32) }
+}
+
+#[allow(non_snake_case)]
+fn ABC(instr: u32) -> Abc {
+	Abc (
+		((instr >> 6) & 0xff) as u8,
+		((instr >> (6 + 8 + 9)) & 0x1ff) as u16,
+		((instr >> (6 + 8)) & 0x1ff) as u16,
+	)
+}
+
+#[allow(non_snake_case)]
+fn Bx(instr: u32) -> u32 {
+	(instr >> (6 + 8)) & 0x3ffff
+}
+
+#[allow(non_snake_case)]
+fn sBx(instr: u32) -> i32 {
+	Bx(instr) as i32 - 0x1ffff
+}
+
+#[allow(non_snake_case)]
+fn Ax(instr: u32) -> u32 {
+	instr >> 6
+}
+
+#[derive(Debug, Clone)]
+pub enum Instr {
+	Move(Reg, Reg),
+	LoadK(Reg, Kst),
+	LoadKx(Reg), // constant index is in the following ExtraArg
+	LoadBool(Reg, bool, bool),
+	LoadNil(Reg, u16), // R(A) .. R(A + B)
+	GetUpval(Reg, Upvalue),
+	GetTabUp(Reg, Upvalue, RegKst),
+	GetTable(Reg, Reg, RegKst),
+	SetTabUp(Upvalue, RegKst, RegKst),
+	SetUpval(Reg, Upvalue),
+	SetTable(Reg, RegKst, RegKst),
+	NewTable(Reg, u16, u16),
+	Self_(Reg, Reg, RegKst),
+	BinOp(Reg, RegKst, BinOp, RegKst),
+	UnOp(Reg, UnOp, Reg),
+	Concat(Reg, Reg, Reg),
+	Jump(Reg, i32), // A > 0 closes upvalues >= R(A - 1)
+	BinCondOp(bool, RegKst, BinCondOp, RegKst),
+	Test(Reg, bool),
+	TestSet(Reg, Reg, bool),
+	Call(Reg, u16, u16),
+	TailCall(Reg, u16, u16),
+	Return(Reg, u16),
+	ForLoop(Reg, i32),
+	ForPrep(Reg, i32),
+	TForCall(Reg, u16),
+	TForLoop(Reg, i32),
+	SetList(Reg, u16, u16), // C = 0 takes the block from the following ExtraArg
+	Closure(Reg, u32),
+	VarArg(Reg, u16),
+	ExtraArg(u32)
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy)]
+pub enum Opmode {
+	iABC(u32, u32, u32),
+	iABx(u32, u32),
+	iAsBx(u32, i32),
+	iAx(u32)
+}
+
+impl Instr {
+	pub fn from_instr(instr: u32) -> Self {
+		Self::try_from_instr(instr).expect("invalid instruction")
+	}
+
+	pub fn try_from_instr(instr: u32) -> Result<Self, DecodeErrorKind> {
+		let op = Opcode::try_from_instr(instr)?;
+		let abc = ABC(instr);
+
+		Ok(match op {
+			Opcode::Move => Self::Move(abc.reg_a(), abc.reg_b()?), // iABC
+			Opcode::LoadK => Self::LoadK(abc.reg_a(), Kst(Bx(instr))), // iABx
+			Opcode::LoadKx => Self::LoadKx(abc.reg_a()), // iABx
+			Opcode::LoadBool => Self::LoadBool(abc.reg_a(), abc.bool_b(), abc.bool_c()), // iABC
+			Opcode::LoadNil => Self::LoadNil(abc.reg_a(), abc.1), // iABC
+			Opcode::GetUpval => Self::GetUpval(abc.reg_a(), abc.1), // iABC
+			Opcode::GetTabUp => Self::GetTabUp(abc.reg_a(), abc.1, abc.rk_c()), // iABC
+			Opcode::GetTable => Self::GetTable(abc.reg_a(), abc.reg_b()?, abc.rk_c()), // iABC
+			Opcode::SetTabUp => Self::SetTabUp(abc.0 as Upvalue, abc.rk_b(), abc.rk_c()), // iABC
+			Opcode::SetUpval => Self::SetUpval(abc.reg_a(), abc.1), // iABC
+			Opcode::SetTable => Self::SetTable(abc.reg_a(), abc.rk_b(), abc.rk_c()), // iABC
+			Opcode::NewTable => Self::NewTable(abc.reg_a(), abc.1, abc.2), // iABC
+			Opcode::Self_ => Self::Self_(abc.reg_a(), abc.reg_b()?, abc.rk_c()), // iABC
+			Opcode::Add => Self::BinOp(abc.reg_a(), abc.rk_b(), BinOp::Add, abc.rk_c()), // iABC
+			Opcode::Sub => Self::BinOp(abc.reg_a(), abc.rk_b(), BinOp::Sub, abc.rk_c()), // iABC
+			Opcode::Mul => Self::BinOp(abc.reg_a(), abc.rk_b(), BinOp::Mul, abc.rk_c()), // iABC
+			Opcode::Div => Self::BinOp(abc.reg_a(), abc.rk_b(), BinOp::Div, abc.rk_c()), // iABC
+			Opcode::Mod => Self::BinOp(abc.reg_a(), abc.rk_b(), BinOp::Mod, abc.rk_c()), // iABC
+			Opcode::Pow => Self::BinOp(abc.reg_a(), abc.rk_b(), BinOp::Pow, abc.rk_c()), // iABC
+			Opcode::Unm => Self::UnOp(abc.reg_a(), UnOp::Unm, abc.reg_b()?), // iABC
+			Opcode::Not => Self::UnOp(abc.reg_a(), UnOp::Not, abc.reg_b()?), // iABC
+			Opcode::Len => Self::UnOp(abc.reg_a(), UnOp::Len, abc.reg_b()?), // iABC
+			Opcode::Concat => Self::Concat(abc.reg_a(), abc.reg_b()?, abc.reg_c()?), // iABC
+			Opcode::Jump => Self::Jump(abc.reg_a(), sBx(instr)), // iAsBx
+			Opcode::Eq => Self::BinCondOp(abc.bool_a(), abc.rk_b(), BinCondOp::Eq, abc.rk_c()), // iABC
+			Opcode::Lt => Self::BinCondOp(abc.bool_a(), abc.rk_b(), BinCondOp::Lt, abc.rk_c()), // iABC
+			Opcode::Le => Self::BinCondOp(abc.bool_a(), abc.rk_b(), BinCondOp::Le, abc.rk_c()), // iABC
+			Opcode::Test => Self::Test(abc.reg_a(), abc.bool_c()), // iABC
+			Opcode::TestSet => Self::TestSet(abc.reg_a(), abc.reg_b()?, abc.bool_c()), // iABC
+			Opcode::Call => Self::Call(abc.reg_a(), abc.1, abc.2), // iABC
+			Opcode::TailCall => Self::TailCall(abc.reg_a(), abc.1, abc.2), // iABC
+			Opcode::Return => Self::Return(abc.reg_a(), abc.1), // iABC
+			Opcode::ForLoop => Self::ForLoop(abc.reg_a(), sBx(instr)), // iAsBx
+			Opcode::ForPrep => Self::ForPrep(abc.reg_a(), sBx(instr)), // iAsBx
+			Opcode::TForCall => Self::TForCall(abc.reg_a(), abc.2), // iABC
+			Opcode::TForLoop => Self::TForLoop(abc.reg_a(), sBx(instr)), // iAsBx
+			Opcode::SetList => Self::SetList(abc.reg_a(), abc.1, abc.2), // iABC
+			Opcode::Closure => Self::Closure(abc.reg_a(), Bx(instr)), // iABx
+			Opcode::VarArg => Self::VarArg(abc.reg_a(), abc.1), // iABC
+			Opcode::ExtraArg => Self::ExtraArg(Ax(instr)) // iAx
+		})
+	}
+
+	pub fn get_opcode(&self) -> Opcode {
+		match self {
+			Self::Move(..) => Opcode::Move,
+			Self::LoadK(..) => Opcode::LoadK,
+			Self::LoadKx(..) => Opcode::LoadKx,
+			Self::LoadBool(..) => Opcode::LoadBool,
+			Self::LoadNil(..) => Opcode::LoadNil,
+			Self::GetUpval(..) => Opcode::GetUpval,
+			Self::GetTabUp(..) => Opcode::GetTabUp,
+			Self::GetTable(..) => Opcode::GetTable,
+			Self::SetTabUp(..) => Opcode::SetTabUp,
+			Self::SetUpval(..) => Opcode::SetUpval,
+			Self::SetTable(..) => Opcode::SetTable,
+			Self::NewTable(..) => Opcode::NewTable,
+			Self::Self_(..) => Opcode::Self_,
+			Self::BinOp(_, _, op, _) => match op {
+				BinOp::Add => Opcode::Add,
+				BinOp::Sub => Opcode::Sub,
+				BinOp::Mul => Opcode::Mul,
+				BinOp::Div => Opcode::Div,
+				BinOp::Mod => Opcode::Mod,
+				BinOp::Pow => Opcode::Pow
+			},
+			Self::UnOp(_, op, _) => match op {
+				UnOp::Unm => Opcode::Unm,
+				UnOp::Not => Opcode::Not,
+				UnOp::Len => Opcode::Len
+			},
+			Self::Concat(..) => Opcode::Concat,
+			Self::Jump(..) => Opcode::Jump,
+			Self::BinCondOp(_, _, op, _) => match op {
+				BinCondOp::Eq => Opcode::Eq,
+				BinCondOp::Lt => Opcode::Lt,
+				BinCondOp::Le => Opcode::Le
+			},
+			Self::Test(..) => Opcode::Test,
+			Self::TestSet(..) => Opcode::TestSet,
+			Self::Call(..) => Opcode::Call,
+			Self::TailCall(..) => Opcode::TailCall,
+			Self::Return(..) => Opcode::Return,
+			Self::ForLoop(..) => Opcode::ForLoop,
+			Self::ForPrep(..) => Opcode::ForPrep,
+			Self::TForCall(..) => Opcode::TForCall,
+			Self::TForLoop(..) => Opcode::TForLoop,
+			Self::SetList(..) => Opcode::SetList,
+			Self::Closure(..) => Opcode::Closure,
+			Self::VarArg(..) => Opcode::VarArg,
+			Self::ExtraArg(..) => Opcode::ExtraArg
+		}
+	}
+
+	pub fn get_opmode(&self) -> Opmode {
+		match self {
+			Self::Move(a, b)
+			| Self::UnOp(a, _, b) => Opmode::iABC(a.0 as u32, b.0 as u32, 0),
+			Self::LoadK(a, kst) => Opmode::iABx(a.0 as u32, kst.0),
+			Self::LoadKx(a) => Opmode::iABx(a.0 as u32, 0),
+			Self::LoadBool(a, b, c) => Opmode::iABC(a.0 as u32, *b as u32, *c as u32),
+			Self::LoadNil(a, b)
+			| Self::GetUpval(a, b)
+			| Self::SetUpval(a, b)
+			| Self::Return(a, b)
+			| Self::VarArg(a, b) => Opmode::iABC(a.0 as u32, *b as u32, 0),
+			Self::GetTabUp(a, b, c) => Opmode::iABC(a.0 as u32, *b as u32, c.field()),
+			Self::GetTable(a, b, c)
+			| Self::Self_(a, b, c) => Opmode::iABC(a.0 as u32, b.0 as u32, c.field()),
+			Self::SetTabUp(a, b, c) => Opmode::iABC(*a as u32, b.field(), c.field()),
+			Self::SetTable(a, b, c)
+			| Self::BinOp(a, b, _, c) => Opmode::iABC(a.0 as u32, b.field(), c.field()),
+			Self::NewTable(a, b, c)
+			| Self::Call(a, b, c)
+			| Self::TailCall(a, b, c)
+			| Self::SetList(a, b, c) => Opmode::iABC(a.0 as u32, *b as u32, *c as u32),
+			Self::Concat(a, b, c) => Opmode::iABC(a.0 as u32, b.0 as u32, c.0 as u32),
+			Self::Jump(a, sbx)
+			| Self::ForLoop(a, sbx)
+			| Self::ForPrep(a, sbx)
+			| Self::TForLoop(a, sbx) => Opmode::iAsBx(a.0 as u32, *sbx),
+			Self::BinCondOp(a, b, _, c) => Opmode::iABC(*a as u32, b.field(), c.field()),
+			Self::Test(a, c) => Opmode::iABC(a.0 as u32, 0, *c as u32),
+			Self::TestSet(a, b, c) => Opmode::iABC(a.0 as u32, b.0 as u32, *c as u32),
+			Self::TForCall(a, c) => Opmode::iABC(a.0 as u32, 0, *c as u32),
+			Self::Closure(a, bx) => Opmode::iABx(a.0 as u32, *bx),
+			Self::ExtraArg(ax) => Opmode::iAx(*ax)
+		}
+	}
+}
+
+// unique id
+fn get_id() -> usize {
+	COUNTER.fetch_add(1, Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone)]
+pub struct Instruction(pub Opcode, pub Instr, pub Opmode, pub usize);
+impl Instruction {
+	pub fn new(instr: Instr) -> Self {
+		let op = instr.get_opcode();
+		let mode = instr.get_opmode();
+		Self(op, instr, mode, get_id())
+	}
+
+	pub fn from_instr(instr: u32) -> Self {
+		Self::new(Instr::from_instr(instr))
+	}
+
+	pub fn try_from_instr(instr: u32) -> Result<Self, DecodeErrorKind> {
+		Ok(Self::new(Instr::try_from_instr(instr)?))
+	}
+
+	pub fn serialize(&self) -> u32 {
+		let serialized = self.0.to_instr();
+		match self.2 {
+			Opmode::iABC(a, b, c) => serialized | ((a & 0xff) << 6) | ((b & 0x1ff) << (6 + 8 + 9)) | ((c & 0x1ff) << (6 + 8)),
+			Opmode::iABx(a, bx) => serialized | ((a & 0xff) << 6) | ((bx & 0x3ffff) << (6 + 8)),
+			Opmode::iAsBx(a, sbx) => serialized | ((a & 0xff) << 6) | ((((sbx + 0x1ffff) as u32) & 0x3ffff) << (6 + 8)),
+			Opmode::iAx(ax) => serialized | (ax << 6)
+		}
+	}
+}
@@ -0,0 +1,55 @@
+mod deserialize;
+pub mod instruction;
+mod serialize;
+
+use crate::LuaString;
+
+pub use crate::lua51::{Reader, Writer, Header, Constants, LocalVar};
+pub use deserialize::{deserialize_bytecode, try_deserialize_bytecode, deserialize_from};
+pub use serialize::{serialize_bytecode, serialize_to};
+pub(crate) use deserialize::LUAC_TAIL;
+
+// where a closure finds an upvalue when it is created
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpvalueDesc {
+	pub in_stack: bool, // register of the enclosing function, otherwise one of its upvalues
+	pub index: u8
+}
+
+#[derive(Debug, Clone)]
+pub struct Proto {
+	pub source: Option<LuaString>, // None when stripped
+	pub line_defined: u32,
+	pub last_line_defined: u32,
+	pub nparams: u8,
+	pub is_vararg_flag: u8,
+	pub max_stack_size: u8,
+	pub instructions: Vec<instruction::Instruction>,
+	pub constants: Vec<Constants>,
+	pub prototypes: Vec<Self>,
+	pub upvalues: Vec<UpvalueDesc>,
+	pub source_lines: Option<Vec<u32>>,
+	pub locals: Option<Vec<LocalVar>>,
+	pub upvals: Option<Vec<LuaString>>
+}
+
+impl Default for Proto {
+	fn default() -> Self {
+		Self {
+			source: Some("@default.lua".into()),
+			line_defined: 0,
+			last_line_defined: 0,
+			nparams: 0,
+			is_vararg_flag: 1,
+			max_stack_size: 2,
+			instructions: vec![],
+			constants: vec![],
+			prototypes: vec![],
+			// the main chunk's only upvalue is _ENV
+			upvalues: vec![UpvalueDesc { in_stack: true, index: 0 }],
+			source_lines: Some(vec![]),
+			locals: Some(vec![]),
+			upvals: Some(vec![])
+		}
+	}
+}
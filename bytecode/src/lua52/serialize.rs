@@ -0,0 +1,99 @@
+use crate::lua51::dump_layout;
+use super::{Writer, Header, Proto, Constants, LUAC_TAIL};
+
+fn dump_header(writer: &mut Writer, header: &Header) {
+	writer.bytes(b"\x1BLua".to_vec());
+	writer.byte(b'\x52');
+	writer.byte(0);
+	dump_layout(writer, header);
+	writer.bytes(LUAC_TAIL.to_vec());
+}
+
+fn dump_vector<I>(writer: &mut Writer, list: I, n: u32, header: &Header, dump: fn(&mut Writer, header: &Header, I::Item)) where I: Iterator {
+	writer.int(n, header.int_size);
+	list.for_each(|v| dump(writer, header, v));
+}
+
+fn dump_chunk(writer: &mut Writer, header: &Header, proto: &Proto) {
+	writer.int(proto.line_defined, header.int_size);
+	writer.int(proto.last_line_defined, header.int_size);
+	writer.byte(proto.nparams);
+	writer.byte(proto.is_vararg_flag);
+	writer.byte(proto.max_stack_size);
+
+	// instructions
+	dump_vector(writer,
+		proto.instructions.iter(), proto.instructions.len() as u32, header,
+		|writer, header, instr| writer.int(instr.serialize(), header.instruction_size));
+
+	// constants
+	dump_vector(writer,
+		proto.constants.iter(), proto.constants.len() as u32, header,
+		|writer, header, kst| {
+			match kst {
+				Constants::Nil => writer.byte(0),
+				&Constants::Boolean(b) => {writer.byte(1); writer.byte(b as u8)},
+				Constants::String(s) => {writer.byte(4); writer.string(s, header.size_t_size)},
+				&Constants::Number(n) => {writer.byte(3); writer.number(n, header.number_size, header.integral)}
+			}
+	});
+
+	// protos
+	dump_vector(writer, proto.prototypes.iter(), proto.prototypes.len() as u32, header, dump_chunk);
+
+	// upvalues
+	dump_vector(writer, proto.upvalues.iter(), proto.upvalues.len() as u32, header, |writer, _, upval| {
+		writer.byte(upval.in_stack as u8);
+		writer.byte(upval.index);
+	});
+
+	// debug info
+	writer.optional_string(proto.source.as_deref(), header.size_t_size);
+
+	// source lines
+	if let Some(source_lines) = &proto.source_lines {
+		dump_vector(writer, source_lines.iter(), source_lines.len() as u32, header, |writer, header, l| {
+			writer.int(*l, header.int_size)
+		});
+	} else {
+		writer.int(0, header.int_size);
+	}
+
+	// locals
+	if let Some(locals) = &proto.locals {
+		dump_vector(writer, locals.iter(), locals.len() as u32, header, |writer, header, local| {
+			writer.string(&local.name, header.size_t_size);
+			writer.int(local.start_pc, header.int_size);
+			writer.int(local.end_pc, header.int_size);
+		});
+	} else {
+		writer.int(0, header.int_size);
+	}
+
+	// upvalue names
+	if let Some(upvals) = &proto.upvals {
+		dump_vector(writer, upvals.iter(), upvals.len() as u32, header, |writer, header, upval| {
+			writer.string(upval, header.size_t_size);
+		});
+	} else {
+		writer.int(0, header.int_size);
+	}
+}
+
+pub fn serialize_bytecode(header: &Header, proto: &Proto) -> Vec<u8> {
+	let mut writer = Writer::new();
+	serialize_to(&mut writer, header, proto);
+	writer.as_bytes().into()
+}
+
+// appends one chunk to the writer, returning how many bytes it took up. errors from a
+// streaming writer only show up once it is flushed
+pub fn serialize_to(writer: &mut Writer, header: &Header, proto: &Proto) -> usize {
+	let start = writer.position();
+	writer.set_endianness(header.endianness);
+
+	dump_header(writer, header);
+	dump_chunk(writer, header, proto);
+
+	writer.position() - start
+}
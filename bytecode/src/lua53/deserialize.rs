@@ -0,0 +1,231 @@
+use crate::{Bytecode, DecodeError, DecodeErrorKind, Endianness, Field, LuaString, MAX_NESTING};
+use crate::lua51::expect_byte;
+use crate::lua52::LUAC_TAIL;
+use super::{Reader, Constants, LocalVar, Proto, UpvalueDesc, instruction::Instruction, Header};
+
+pub(crate) const LUAC_INT: i64 = 0x5678;
+pub(crate) const LUAC_NUM: f64 = 370.5;
+
+fn size_byte(reader: &mut Reader, what: &'static str, allowed: &[u8]) -> Result<u8, DecodeError> {
+	let at = reader.position();
+	let size = reader.byte()?;
+	if !allowed.contains(&size) {
+		return Err(reader.error_at(at, DecodeErrorKind::UnsupportedSize { what, size }));
+	}
+	Ok(size)
+}
+
+// a size byte, or 0xff followed by a size_t; NULL strings have a size of 0
+fn string(reader: &mut Reader, header: &Header) -> Result<Option<LuaString>, DecodeError> {
+	let mut size = reader.byte()? as u64;
+	if size == 0xff {
+		size = reader.uint(header.size_t_size as usize)?;
+	}
+	if size == 0 {
+		return Ok(None);
+	}
+
+	let str = reader.bytes((size - 1) as usize)?.to_vec(); // no trailing nul
+	Ok(Some(LuaString(str)))
+}
+
+fn header(reader: &mut Reader) -> Result<Header, DecodeError> {
+	reader.set_field(Field::Header);
+
+	let start = reader.position();
+	if reader.bytes(4)? != b"\x1BLua" {
+		return Err(reader.error_at(start, DecodeErrorKind::BadSignature));
+	}
+
+	expect_byte(reader, 0x53, DecodeErrorKind::UnsupportedVersion)?;
+	expect_byte(reader, 0, DecodeErrorKind::UnsupportedFormat)?;
+
+	let at = reader.position();
+	if reader.bytes(LUAC_TAIL.len())? != LUAC_TAIL {
+		return Err(reader.error_at(at, DecodeErrorKind::BadTail));
+	}
+
+	let int_size = size_byte(reader, "int", &[2, 4, 8])?;
+	let size_t_size = size_byte(reader, "size_t", &[4, 8])?;
+	let instruction_size = size_byte(reader, "Instruction", &[4, 8])?;
+	let integer_size = size_byte(reader, "lua_Integer", &[4, 8])?;
+	let number_size = size_byte(reader, "lua_Number", &[4, 8])?;
+
+	// whichever byte order reads LUAC_INT back is the chunk's
+	let at = reader.position();
+	let bytes = &reader.bytes(integer_size as usize)?.to_vec();
+	let endianness = if Reader::new(bytes).integer(bytes.len())? == LUAC_INT {
+		Endianness::Little
+	} else {
+		let mut big = Reader::new(bytes);
+		big.set_endianness(Endianness::Big);
+		if big.integer(bytes.len())? != LUAC_INT {
+			return Err(reader.error_at(at, DecodeErrorKind::BadCheckValue("LUAC_INT")));
+		}
+		Endianness::Big
+	};
+	reader.set_endianness(endianness);
+
+	let at = reader.position();
+	if reader.number(number_size, false)? != LUAC_NUM {
+		return Err(reader.error_at(at, DecodeErrorKind::BadCheckValue("LUAC_NUM")));
+	}
+
+	Ok(Header {
+		endianness,
+		int_size,
+		size_t_size,
+		instruction_size,
+		integer_size,
+		number_size
+	})
+}
+
+fn constant(reader: &mut Reader, header: &Header, i: usize) -> Result<Constants, DecodeError> {
+	reader.set_field(Field::ConstantTag(i));
+	let at = reader.position();
+	let t = reader.byte()?;
+
+	reader.set_field(Field::Constant(i));
+	Ok(match t {
+		0x00 => Constants::Nil,
+		0x01 => Constants::Boolean(reader.byte()? != 0u8),
+		0x03 => Constants::Float(reader.number(header.number_size, false)?),
+		0x13 => Constants::Integer(reader.integer(header.integer_size as usize)?),
+		0x04 | 0x14 => Constants::String(string(reader, header)?.unwrap_or_default()),
+		_ => {
+			reader.set_field(Field::ConstantTag(i));
+			return Err(reader.error_at(at, DecodeErrorKind::UnknownConstantTag(t)))
+		}
+	})
+}
+
+fn chunk(reader: &mut Reader, header: &Header) -> Result<Proto, DecodeError> {
+	reader.set_field(Field::Source);
+	let source = string(reader, header)?;
+	reader.set_field(Field::LineDefined);
+	let line_defined = reader.int(header.int_size as usize)?;
+	reader.set_field(Field::LastLineDefined);
+	let last_line_defined = reader.int(header.int_size as usize)?;
+	reader.set_field(Field::ProtoInfo);
+	let nparams = reader.byte()?;
+	let is_vararg_flag = reader.byte()?;
+	let max_stack_size = reader.byte()?;
+
+	// instructions
+	let instrs_start = reader.position() + header.int_size as usize;
+	reader.set_field(Field::InstructionCount);
+	let n = reader.int(header.int_size as usize)?;
+	let mut instrs = vec![];
+	for i in 0..n as usize {
+		reader.set_field(Field::Instruction(i));
+		instrs.push(reader.int(header.instruction_size as usize)?);
+	}
+	let mut instructions = vec![];
+	for (i, instr) in instrs.iter().enumerate() {
+		let instruction = Instruction::try_from_instr(*instr).map_err(|kind| {
+			reader.set_field(Field::Instruction(i));
+			reader.error_at(instrs_start + i * header.instruction_size as usize, kind)
+		})?;
+		instructions.push(instruction);
+	}
+
+	// constants
+	reader.set_field(Field::ConstantCount);
+	let n = reader.int(header.int_size as usize)?;
+	let mut constants = vec![];
+	for i in 0..n as usize {
+		constants.push(constant(reader, header, i)?);
+	}
+
+	// upvalues
+	reader.set_field(Field::UpvalueDescCount);
+	let n = reader.int(header.int_size as usize)?;
+	let mut upvalues = vec![];
+	for i in 0..n as usize {
+		reader.set_field(Field::UpvalueDesc(i));
+		let in_stack = reader.byte()? != 0;
+		let index = reader.byte()?;
+		upvalues.push(UpvalueDesc { in_stack, index });
+	}
+
+	// prototypes
+	reader.set_field(Field::ProtoCount);
+	if reader.path().len() >= MAX_NESTING {
+		return Err(reader.error(DecodeErrorKind::TooDeeplyNested));
+	}
+	let n = reader.int(header.int_size as usize)?;
+	let mut prototypes = vec![];
+	for i in 0..n as usize {
+		reader.enter_proto(i);
+		prototypes.push(chunk(reader, header)?);
+		reader.leave_proto();
+	}
+
+	// source lines
+	reader.set_field(Field::SourceLineCount);
+	let n = reader.int(header.int_size as usize)?;
+	let mut source_lines = vec![];
+	for i in 0..n as usize {
+		reader.set_field(Field::SourceLine(i));
+		source_lines.push(reader.int(header.int_size as usize)?);
+	}
+
+	// local list
+	reader.set_field(Field::LocalCount);
+	let n = reader.int(header.int_size as usize)?;
+	let mut locals = vec![];
+	for i in 0..n as usize {
+		reader.set_field(Field::Local(i));
+		let name = string(reader, header)?.unwrap_or_default();
+		let start = reader.int(header.int_size as usize)?;
+		let end = reader.int(header.int_size as usize)?;
+		locals.push(LocalVar { name, start_pc: start, end_pc: end });
+	}
+
+	// upvalue names
+	reader.set_field(Field::UpvalueCount);
+	let n = reader.int(header.int_size as usize)?;
+	let mut upvals = vec![];
+	for i in 0..n as usize {
+		reader.set_field(Field::Upvalue(i));
+		upvals.push(string(reader, header)?.unwrap_or_default());
+	}
+
+	Ok(Proto {
+		source,
+		line_defined,
+		last_line_defined,
+		nparams,
+		is_vararg_flag,
+		max_stack_size,
+		instructions,
+		constants,
+		upvalues,
+		prototypes,
+		source_lines: Some(source_lines),
+		locals: Some(locals),
+		upvals: Some(upvals)
+	})
+}
+
+pub fn try_deserialize_bytecode(bytecode: &Bytecode) -> Result<(Header, Proto), DecodeError> {
+	let (header_data, proto, _) = deserialize_from(&mut Reader::new(bytecode))?;
+	Ok((header_data, proto))
+}
+
+// reads one chunk from wherever the reader is, also returning how many bytes it took up
+pub fn deserialize_from(reader: &mut Reader) -> Result<(Header, Proto, usize), DecodeError> {
+	let start = reader.position();
+
+	let header_data = header(reader)?;
+	// size of the main closure's upvalue list, which the main proto repeats
+	reader.byte()?;
+	let proto = chunk(reader, &header_data)?;
+
+	Ok((header_data, proto, reader.position() - start))
+}
+
+pub fn deserialize_bytecode(bytecode: &Bytecode) -> (Header, Proto) {
+	try_deserialize_bytecode(bytecode).unwrap_or_else(|err| panic!("{}", err))
+}
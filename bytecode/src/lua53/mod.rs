@@ -0,0 +1,86 @@
+mod deserialize;
+pub mod instruction;
+mod serialize;
+
+use crate::{Endianness, LuaString};
+
+pub use crate::lua51::{Reader, Writer, LocalVar};
+pub use crate::lua52::UpvalueDesc;
+pub use deserialize::{deserialize_bytecode, try_deserialize_bytecode, deserialize_from};
+pub use serialize::{serialize_bytecode, serialize_to};
+pub(crate) use deserialize::{LUAC_INT, LUAC_NUM};
+
+// strings up to this length are dumped with the short string tag
+pub const MAX_SHORT_LEN: usize = 40;
+
+#[derive(Debug, PartialEq, PartialOrd, Clone)]
+pub enum Constants {
+	Nil,
+	Boolean(bool),
+	Float(f64),
+	Integer(i64),
+	String(LuaString)
+}
+
+#[derive(Debug, Clone)]
+pub struct Proto {
+	pub source: Option<LuaString>, // None when stripped or the same as the parent's
+	pub line_defined: u32,
+	pub last_line_defined: u32,
+	pub nparams: u8,
+	pub is_vararg_flag: u8,
+	pub max_stack_size: u8,
+	pub instructions: Vec<instruction::Instruction>,
+	pub constants: Vec<Constants>,
+	pub upvalues: Vec<UpvalueDesc>,
+	pub prototypes: Vec<Self>,
+	pub source_lines: Option<Vec<u32>>,
+	pub locals: Option<Vec<LocalVar>>,
+	pub upvals: Option<Vec<LuaString>>
+}
+
+impl Default for Proto {
+	fn default() -> Self {
+		Self {
+			source: Some("@default.lua".into()),
+			line_defined: 0,
+			last_line_defined: 0,
+			nparams: 0,
+			is_vararg_flag: 1,
+			max_stack_size: 2,
+			instructions: vec![],
+			constants: vec![],
+			// the main chunk's only upvalue is _ENV
+			upvalues: vec![UpvalueDesc { in_stack: true, index: 0 }],
+			prototypes: vec![],
+			source_lines: Some(vec![]),
+			locals: Some(vec![]),
+			upvals: Some(vec![])
+		}
+	}
+}
+
+// 5.3 drops the endianness and integral flags; byte order is inferred from LUAC_INT
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+	pub endianness: Endianness,
+	pub int_size: u8,
+	pub size_t_size: u8,
+	pub instruction_size: u8,
+	pub integer_size: u8, // lua_Integer
+	pub number_size: u8 // lua_Number
+}
+
+impl Default for Header {
+	// what a stock 64-bit luac emits
+	fn default() -> Self {
+		Self {
+			endianness: Endianness::Little,
+			int_size: 4,
+			size_t_size: 8,
+			instruction_size: 4,
+			integer_size: 8,
+			number_size: 8
+		}
+	}
+}
@@ -0,0 +1,124 @@
+use crate::lua52::LUAC_TAIL;
+use super::{Writer, Header, Proto, Constants, MAX_SHORT_LEN, LUAC_INT, LUAC_NUM};
+
+fn dump_string(writer: &mut Writer, header: &Header, str: Option<&[u8]>) {
+	let Some(str) = str else {
+		writer.byte(0);
+		return;
+	};
+
+	let size = str.len() as u64 + 1;
+	if size < 0xff {
+		writer.byte(size as u8);
+	} else {
+		writer.byte(0xff);
+		writer.uint(size, header.size_t_size);
+	}
+	writer.bytes(str.to_vec());
+}
+
+fn dump_header(writer: &mut Writer, header: &Header) {
+	writer.bytes(b"\x1BLua".to_vec());
+	writer.byte(b'\x53');
+	writer.byte(0);
+	writer.bytes(LUAC_TAIL.to_vec());
+	writer.byte(header.int_size);
+	writer.byte(header.size_t_size);
+	writer.byte(header.instruction_size);
+	writer.byte(header.integer_size);
+	writer.byte(header.number_size);
+	writer.integer(LUAC_INT, header.integer_size);
+	writer.number(LUAC_NUM, header.number_size, false);
+}
+
+fn dump_vector<I>(writer: &mut Writer, list: I, n: u32, header: &Header, dump: fn(&mut Writer, header: &Header, I::Item)) where I: Iterator {
+	writer.int(n, header.int_size);
+	list.for_each(|v| dump(writer, header, v));
+}
+
+fn dump_chunk(writer: &mut Writer, header: &Header, proto: &Proto) {
+	dump_string(writer, header, proto.source.as_deref());
+	writer.int(proto.line_defined, header.int_size);
+	writer.int(proto.last_line_defined, header.int_size);
+	writer.byte(proto.nparams);
+	writer.byte(proto.is_vararg_flag);
+	writer.byte(proto.max_stack_size);
+
+	// instructions
+	dump_vector(writer,
+		proto.instructions.iter(), proto.instructions.len() as u32, header,
+		|writer, header, instr| writer.int(instr.serialize(), header.instruction_size));
+
+	// constants
+	dump_vector(writer,
+		proto.constants.iter(), proto.constants.len() as u32, header,
+		|writer, header, kst| {
+			match kst {
+				Constants::Nil => writer.byte(0x00),
+				&Constants::Boolean(b) => {writer.byte(0x01); writer.byte(b as u8)},
+				&Constants::Float(n) => {writer.byte(0x03); writer.number(n, header.number_size, false)},
+				&Constants::Integer(n) => {writer.byte(0x13); writer.integer(n, header.integer_size)},
+				Constants::String(s) => {
+					writer.byte(if s.len() <= MAX_SHORT_LEN { 0x04 } else { 0x14 });
+					dump_string(writer, header, Some(s));
+				}
+			}
+	});
+
+	// upvalues
+	dump_vector(writer, proto.upvalues.iter(), proto.upvalues.len() as u32, header, |writer, _, upval| {
+		writer.byte(upval.in_stack as u8);
+		writer.byte(upval.index);
+	});
+
+	// protos
+	dump_vector(writer, proto.prototypes.iter(), proto.prototypes.len() as u32, header, dump_chunk);
+
+	// source lines
+	if let Some(source_lines) = &proto.source_lines {
+		dump_vector(writer, source_lines.iter(), source_lines.len() as u32, header, |writer, header, l| {
+			writer.int(*l, header.int_size)
+		});
+	} else {
+		writer.int(0, header.int_size);
+	}
+
+	// locals
+	if let Some(locals) = &proto.locals {
+		dump_vector(writer, locals.iter(), locals.len() as u32, header, |writer, header, local| {
+			dump_string(writer, header, Some(&local.name));
+			writer.int(local.start_pc, header.int_size);
+			writer.int(local.end_pc, header.int_size);
+		});
+	} else {
+		writer.int(0, header.int_size);
+	}
+
+	// upvalue names
+	if let Some(upvals) = &proto.upvals {
+		dump_vector(writer, upvals.iter(), upvals.len() as u32, header, |writer, header, upval| {
+			dump_string(writer, header, Some(upval));
+		});
+	} else {
+		writer.int(0, header.int_size);
+	}
+}
+
+pub fn serialize_bytecode(header: &Header, proto: &Proto) -> Vec<u8> {
+	let mut writer = Writer::new();
+	serialize_to(&mut writer, header, proto);
+	writer.as_bytes().into()
+}
+
+// appends one chunk to the writer, returning how many bytes it took up. errors from a
+// streaming writer only show up once it is flushed
+pub fn serialize_to(writer: &mut Writer, header: &Header, proto: &Proto) -> usize {
+	let start = writer.position();
+	writer.set_endianness(header.endianness);
+
+	dump_header(writer, header);
+	writer.byte(proto.upvalues.len() as u8);
+	dump_chunk(writer, header, proto);
+
+	writer.position() - start
+}
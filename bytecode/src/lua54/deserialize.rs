@@ -0,0 +1,247 @@
+use crate::{Bytecode, DecodeError, DecodeErrorKind, Endianness, Field, LuaString, MAX_NESTING};
+use crate::lua51::expect_byte;
+use crate::lua52::LUAC_TAIL;
+use crate::lua53::{LUAC_INT, LUAC_NUM};
+use super::{Reader, Constants, LocalVar, Proto, UpvalueDesc, UpvalueKind, AbsLineInfo, instruction::Instruction, Header};
+
+fn size_byte(reader: &mut Reader, what: &'static str, allowed: &[u8]) -> Result<u8, DecodeError> {
+	let at = reader.position();
+	let size = reader.byte()?;
+	if !allowed.contains(&size) {
+		return Err(reader.error_at(at, DecodeErrorKind::UnsupportedSize { what, size }));
+	}
+	Ok(size)
+}
+
+// most significant group first, the last byte has its high bit set
+fn varint(reader: &mut Reader) -> Result<u64, DecodeError> {
+	let start = reader.position();
+	let mut x: u64 = 0;
+	loop {
+		let b = reader.byte()?;
+		if x > u64::MAX >> 7 {
+			return Err(reader.error_at(start, DecodeErrorKind::IntegerOverflow));
+		}
+		x = (x << 7) | (b & 0x7f) as u64;
+		if b & 0x80 != 0 {
+			return Ok(x);
+		}
+	}
+}
+
+fn int(reader: &mut Reader) -> Result<u32, DecodeError> {
+	let start = reader.position();
+	let v = varint(reader)?;
+	u32::try_from(v).map_err(|_| reader.error_at(start, DecodeErrorKind::IntegerOverflow))
+}
+
+// NULL strings have a size of 0, the rest are stored without a trailing nul
+fn string(reader: &mut Reader) -> Result<Option<LuaString>, DecodeError> {
+	let size = varint(reader)?;
+	if size == 0 {
+		return Ok(None);
+	}
+
+	let str = reader.bytes((size - 1) as usize)?.to_vec();
+	Ok(Some(LuaString(str)))
+}
+
+fn header(reader: &mut Reader) -> Result<Header, DecodeError> {
+	reader.set_field(Field::Header);
+
+	let start = reader.position();
+	if reader.bytes(4)? != b"\x1BLua" {
+		return Err(reader.error_at(start, DecodeErrorKind::BadSignature));
+	}
+
+	expect_byte(reader, 0x54, DecodeErrorKind::UnsupportedVersion)?;
+	expect_byte(reader, 0, DecodeErrorKind::UnsupportedFormat)?;
+
+	let at = reader.position();
+	if reader.bytes(LUAC_TAIL.len())? != LUAC_TAIL {
+		return Err(reader.error_at(at, DecodeErrorKind::BadTail));
+	}
+
+	let instruction_size = size_byte(reader, "Instruction", &[4, 8])?;
+	let integer_size = size_byte(reader, "lua_Integer", &[4, 8])?;
+	let number_size = size_byte(reader, "lua_Number", &[4, 8])?;
+
+	// whichever byte order reads LUAC_INT back is the chunk's
+	let at = reader.position();
+	let bytes = &reader.bytes(integer_size as usize)?.to_vec();
+	let endianness = if Reader::new(bytes).integer(bytes.len())? == LUAC_INT {
+		Endianness::Little
+	} else {
+		let mut big = Reader::new(bytes);
+		big.set_endianness(Endianness::Big);
+		if big.integer(bytes.len())? != LUAC_INT {
+			return Err(reader.error_at(at, DecodeErrorKind::BadCheckValue("LUAC_INT")));
+		}
+		Endianness::Big
+	};
+	reader.set_endianness(endianness);
+
+	let at = reader.position();
+	if reader.number(number_size, false)? != LUAC_NUM {
+		return Err(reader.error_at(at, DecodeErrorKind::BadCheckValue("LUAC_NUM")));
+	}
+
+	Ok(Header {
+		endianness,
+		instruction_size,
+		integer_size,
+		number_size
+	})
+}
+
+fn constant(reader: &mut Reader, header: &Header, i: usize) -> Result<Constants, DecodeError> {
+	reader.set_field(Field::ConstantTag(i));
+	let at = reader.position();
+	let t = reader.byte()?;
+
+	reader.set_field(Field::Constant(i));
+	Ok(match t {
+		0x00 => Constants::Nil,
+		0x01 => Constants::Boolean(false),
+		0x11 => Constants::Boolean(true),
+		0x03 => Constants::Integer(reader.integer(header.integer_size as usize)?),
+		0x13 => Constants::Float(reader.number(header.number_size, false)?),
+		0x04 | 0x14 => Constants::String(string(reader)?.unwrap_or_default()),
+		_ => {
+			reader.set_field(Field::ConstantTag(i));
+			return Err(reader.error_at(at, DecodeErrorKind::UnknownConstantTag(t)))
+		}
+	})
+}
+
+fn upvalue(reader: &mut Reader) -> Result<UpvalueDesc, DecodeError> {
+	let in_stack = reader.byte()? != 0;
+	let index = reader.byte()?;
+	let at = reader.position();
+	let kind = match reader.byte()? {
+		0 => UpvalueKind::Regular,
+		1 => UpvalueKind::Const,
+		2 => UpvalueKind::ToClose,
+		3 => UpvalueKind::CompileTimeConst,
+		v => return Err(reader.error_at(at, DecodeErrorKind::UnknownUpvalueKind(v)))
+	};
+	Ok(UpvalueDesc { in_stack, index, kind })
+}
+
+fn load_vec<V>(
+	reader: &mut Reader, count: Field, item: fn(usize) -> Field,
+	read: impl Fn(&mut Reader) -> Result<V, DecodeError>
+) -> Result<Vec<V>, DecodeError> {
+	reader.set_field(count);
+	let n = int(reader)?;
+	let mut list: Vec<V> = vec![];
+	for i in 0..n as usize {
+		reader.set_field(item(i));
+		list.push(read(reader)?);
+	};
+
+	Ok(list)
+}
+
+fn chunk(reader: &mut Reader, header: &Header) -> Result<Proto, DecodeError> {
+	reader.set_field(Field::Source);
+	let source = string(reader)?;
+	reader.set_field(Field::LineDefined);
+	let line_defined = int(reader)?;
+	reader.set_field(Field::LastLineDefined);
+	let last_line_defined = int(reader)?;
+	reader.set_field(Field::ProtoInfo);
+	let nparams = reader.byte()?;
+	let is_vararg_flag = reader.byte()?;
+	let max_stack_size = reader.byte()?;
+
+	// instructions
+	let instrs = load_vec(reader, Field::InstructionCount, Field::Instruction, |reader| {
+		let at = reader.position();
+		let instr = reader.int(header.instruction_size as usize)?;
+		Instruction::try_from_instr(instr).map_err(|kind| reader.error_at(at, kind))
+	})?;
+
+	// constants
+	reader.set_field(Field::ConstantCount);
+	let n = int(reader)?;
+	let mut constants = vec![];
+	for i in 0..n as usize {
+		constants.push(constant(reader, header, i)?);
+	}
+
+	// upvalues
+	let upvalues = load_vec(reader, Field::UpvalueDescCount, Field::UpvalueDesc, upvalue)?;
+
+	// prototypes
+	reader.set_field(Field::ProtoCount);
+	if reader.path().len() >= MAX_NESTING {
+		return Err(reader.error(DecodeErrorKind::TooDeeplyNested));
+	}
+	let n = int(reader)?;
+	let mut prototypes = vec![];
+	for i in 0..n as usize {
+		reader.enter_proto(i);
+		prototypes.push(chunk(reader, header)?);
+		reader.leave_proto();
+	}
+
+	// line info
+	let line_info = Some(load_vec(reader, Field::SourceLineCount, Field::SourceLine, |reader|
+		Ok(reader.byte()? as i8))?);
+	let abs_line_info = Some(load_vec(reader, Field::AbsLineInfoCount, Field::AbsLineInfo, |reader| {
+		let pc = int(reader)?;
+		let line = int(reader)?;
+		Ok(AbsLineInfo { pc, line })
+	})?);
+
+	// local list
+	let locals = Some(load_vec(reader, Field::LocalCount, Field::Local, |reader| {
+		let name = string(reader)?.unwrap_or_default();
+		let start = int(reader)?;
+		let end = int(reader)?;
+		Ok(LocalVar { name, start_pc: start, end_pc: end })
+	})?);
+
+	// upvalue names
+	let upvals = Some(load_vec(reader, Field::UpvalueCount, Field::Upvalue, |reader|
+		Ok(string(reader)?.unwrap_or_default()))?);
+
+	Ok(Proto {
+		source,
+		line_defined,
+		last_line_defined,
+		nparams,
+		is_vararg_flag,
+		max_stack_size,
+		instructions: instrs,
+		constants,
+		upvalues,
+		prototypes,
+		line_info,
+		abs_line_info,
+		locals,
+		upvals
+	})
+}
+
+pub fn try_deserialize_bytecode(bytecode: &Bytecode) -> Result<(Header, Proto), DecodeError> {
+	let (header_data, proto, _) = deserialize_from(&mut Reader::new(bytecode))?;
+	Ok((header_data, proto))
+}
+
+// reads one chunk from wherever the reader is, also returning how many bytes it took up
+pub fn deserialize_from(reader: &mut Reader) -> Result<(Header, Proto, usize), DecodeError> {
+	let start = reader.position();
+
+	let header_data = header(reader)?;
+	// size of the main closure's upvalue list, which the main proto repeats
+	reader.byte()?;
+	let proto = chunk(reader, &header_data)?;
+
+	Ok((header_data, proto, reader.position() - start))
+}
+
+pub fn deserialize_bytecode(bytecode: &Bytecode) -> (Header, Proto) {
+	try_deserialize_bytecode(bytecode).unwrap_or_else(|err| panic!("{}", err))
+}
@@ -0,0 +1,549 @@
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+
+use crate::DecodeErrorKind;
+pub use crate::lua51::instruction::{Reg, Kst, RegKst, Upvalue, BinCondOp};
+pub use crate::lua53::instruction::{BinOp, UnOp};
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+static COUNTER: AtomicUsize = AtomicUsize::new(1);
+
+const OFFSET_SBX: i32 = 0x1ffff >> 1;
+const OFFSET_SJ: i32 = 0x1ffffff >> 1;
+const OFFSET_SC: i16 = 0xff >> 1;
+
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+	Move = 0,
+	LoadI,
+	LoadF,
+	LoadK,
+	LoadKx,
+	LoadFalse,
+	LFalseSkip,
+	LoadTrue,
+	LoadNil,
+	GetUpval,
+	SetUpval,
+	GetTabUp,
+	GetTable,
+	GetI,
+	GetField,
+	SetTabUp,
+	SetTable,
+	SetI,
+	SetField,
+	NewTable,
+	Self_,
+	AddI,
+	AddK,
+	SubK,
+	MulK,
+	ModK,
+	PowK,
+	DivK,
+	IDivK,
+	BAndK,
+	BOrK,
+	BXorK,
+	ShrI,
+	ShlI,
+	Add,
+	Sub,
+	Mul,
+	Mod,
+	Pow,
+	Div,
+	IDiv,
+	BAnd,
+	BOr,
+	BXor,
+	Shl,
+	Shr,
+	MmBin,
+	MmBinI,
+	MmBinK,
+	Unm,
+	BNot,
+	Not,
+	Len,
+	Concat,
+	Close,
+	Tbc,
+	Jump,
+	Eq,
+	Lt,
+	Le,
+	EqK,
+	EqI,
+	LtI,
+	LeI,
+	GtI,
+	GeI,
+	Test,
+	TestSet,
+	Call,
+	TailCall,
+	Return,
+	Return0,
+	Return1,
+	ForLoop,
+	ForPrep,
+	TForPrep,
+	TForCall,
+	TForLoop,
+	SetList,
+	Closure,
+	VarArg,
+	VarArgPrep,
+	ExtraArg
+}
+
+impl Opcode {
+	fn try_from_instr(instr: u32) -> Result<Self, DecodeErrorKind> {
+		Self::from_u8((instr & 0x7f) as u8).ok_or(DecodeErrorKind::InvalidOpcode((instr & 0x7f) as u8))
+	}
+
+	fn to_instr(self) -> u32 {
+		self as u32
+	}
+}
+
+// comparisons against a signed immediate
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CondOpI {
+	Eq, Lt, Le, Gt, Ge
+}
+
+// 7 bit opcode, then A (8), k (1), B (8) and C (8)
+struct Abck(u8, u8, u8, bool);
+impl Abck {
+	fn reg_a(&self) -> Reg { Reg(self.0) }
+	fn reg_b(&self) -> Reg { Reg(self.1) }
+	fn reg_c(&self) -> Reg { Reg(self.2) }
+
+	fn kst_b(&self) -> Kst { Kst(self.1 as u32) }
+	fn kst_c(&self) -> Kst { Kst(self.2 as u32) }
+
+	fn sb(&self) -> i16 { self.1 as i16 - OFFSET_SC }
+	fn sc(&self) -> i16 { self.2 as i16 - OFFSET_SC }
+
+	// C is a constant when k is set
+	fn rk_c(&self) -> RegKst {
+		if self.3 {
+			RegKst::K(self.kst_c())
+		} else {
+			RegKst::R(self.reg_c())
+		}
+	}
+}
+
+#[allow(non_snake_case)]
+fn ABCk(instr: u32) -> Abck {
+	Abck (
+		((instr >> 7) & 0xff) as u8,
+		((instr >> 16) & 0xff) as u8,
+		((instr >> 24) & 0xff) as u8,
+		(instr >> 15) & 1 != 0
+	)
+}
+
+#[allow(non_snake_case)]
+fn Bx(instr: u32) -> u32 {
+	(instr >> 15) & 0x1ffff
+}
+
+#[allow(non_snake_case)]
+fn sBx(instr: u32) -> i32 {
+	Bx(instr) as i32 - OFFSET_SBX
+}
+
+#[allow(non_snake_case)]
+fn Ax(instr: u32) -> u32 {
+	instr >> 7
+}
+
+#[allow(non_snake_case)]
+fn sJ(instr: u32) -> i32 {
+	Ax(instr) as i32 - OFFSET_SJ
+}
+
+#[derive(Debug, Clone)]
+pub enum Instr {
+	Move(Reg, Reg),
+	LoadI(Reg, i32),
+	LoadF(Reg, i32),
+	LoadK(Reg, Kst),
+	LoadKx(Reg), // constant index is in the following ExtraArg
+	LoadFalse(Reg),
+	LFalseSkip(Reg),
+	LoadTrue(Reg),
+	LoadNil(Reg, u8), // R(A) .. R(A + B)
+	GetUpval(Reg, Upvalue),
+	SetUpval(Reg, Upvalue),
+	GetTabUp(Reg, Upvalue, Kst),
+	GetTable(Reg, Reg, Reg),
+	GetI(Reg, Reg, u8),
+	GetField(Reg, Reg, Kst),
+	SetTabUp(Upvalue, Kst, RegKst),
+	SetTable(Reg, Reg, RegKst),
+	SetI(Reg, u8, RegKst),
+	SetField(Reg, Kst, RegKst),
+	NewTable(Reg, u8, u8, bool), // k = the following ExtraArg holds the rest of the array size
+	Self_(Reg, Reg, RegKst),
+	AddI(Reg, Reg, i16),
+	BinOpK(Reg, Reg, BinOp, Kst), // there are no constant forms of the shifts
+	ShrI(Reg, Reg, i16),
+	ShlI(Reg, Reg, i16), // immediate << R(B)
+	BinOp(Reg, Reg, BinOp, Reg),
+	MmBin(Reg, Reg, u8), // C is the metamethod event
+	MmBinI(Reg, i16, u8, bool), // k = operands were flipped
+	MmBinK(Reg, Kst, u8, bool),
+	UnOp(Reg, UnOp, Reg),
+	Concat(Reg, u8),
+	Close(Reg),
+	Tbc(Reg),
+	Jump(i32),
+	// the last flag of comparisons marks a float operand; luac keeps it in C for error messages
+	BinCondOp(bool, Reg, BinCondOp, Reg, bool),
+	EqK(bool, Reg, Kst, bool),
+	CondOpI(bool, Reg, CondOpI, i16, bool),
+	Test(Reg, bool),
+	TestSet(Reg, Reg, bool),
+	Call(Reg, u8, u8),
+	TailCall(Reg, u8, u8, bool), // k = upvalues need closing
+	Return(Reg, u8, u8, bool),
+	Return0(Reg),
+	Return1(Reg),
+	ForLoop(Reg, u32),
+	ForPrep(Reg, u32),
+	TForPrep(Reg, u32),
+	TForCall(Reg, u8),
+	TForLoop(Reg, u32),
+	SetList(Reg, u8, u8, bool), // k = the following ExtraArg holds the rest of the index
+	Closure(Reg, u32),
+	VarArg(Reg, u8),
+	VarArgPrep(Reg),
+	ExtraArg(u32)
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Debug, Clone, Copy)]
+pub enum Opmode {
+	iABC(u32, u32, u32, bool),
+	iABx(u32, u32),
+	iAsBx(u32, i32),
+	iAx(u32),
+	isJ(i32)
+}
+
+impl Instr {
+	pub fn from_instr(instr: u32) -> Self {
+		Self::try_from_instr(instr).expect("invalid instruction")
+	}
+
+	pub fn try_from_instr(instr: u32) -> Result<Self, DecodeErrorKind> {
+		let op = Opcode::try_from_instr(instr)?;
+		let abc = ABCk(instr);
+		let k = abc.3;
+
+		let bin_k = |op| Self::BinOpK(abc.reg_a(), abc.reg_b(), op, abc.kst_c());
+		let bin = |op| Self::BinOp(abc.reg_a(), abc.reg_b(), op, abc.reg_c());
+		let cond = |op| Self::BinCondOp(k, abc.reg_a(), op, abc.reg_b(), abc.2 != 0);
+		let cond_i = |op| Self::CondOpI(k, abc.reg_a(), op, abc.sb(), abc.2 != 0);
+
+		Ok(match op {
+			Opcode::Move => Self::Move(abc.reg_a(), abc.reg_b()),
+			Opcode::LoadI => Self::LoadI(abc.reg_a(), sBx(instr)),
+			Opcode::LoadF => Self::LoadF(abc.reg_a(), sBx(instr)),
+			Opcode::LoadK => Self::LoadK(abc.reg_a(), Kst(Bx(instr))),
+			Opcode::LoadKx => Self::LoadKx(abc.reg_a()),
+			Opcode::LoadFalse => Self::LoadFalse(abc.reg_a()),
+			Opcode::LFalseSkip => Self::LFalseSkip(abc.reg_a()),
+			Opcode::LoadTrue => Self::LoadTrue(abc.reg_a()),
+			Opcode::LoadNil => Self::LoadNil(abc.reg_a(), abc.1),
+			Opcode::GetUpval => Self::GetUpval(abc.reg_a(), abc.1 as Upvalue),
+			Opcode::SetUpval => Self::SetUpval(abc.reg_a(), abc.1 as Upvalue),
+			Opcode::GetTabUp => Self::GetTabUp(abc.reg_a(), abc.1 as Upvalue, abc.kst_c()),
+			Opcode::GetTable => Self::GetTable(abc.reg_a(), abc.reg_b(), abc.reg_c()),
+			Opcode::GetI => Self::GetI(abc.reg_a(), abc.reg_b(), abc.2),
+			Opcode::GetField => Self::GetField(abc.reg_a(), abc.reg_b(), abc.kst_c()),
+			Opcode::SetTabUp => Self::SetTabUp(abc.0 as Upvalue, abc.kst_b(), abc.rk_c()),
+			Opcode::SetTable => Self::SetTable(abc.reg_a(), abc.reg_b(), abc.rk_c()),
+			Opcode::SetI => Self::SetI(abc.reg_a(), abc.1, abc.rk_c()),
+			Opcode::SetField => Self::SetField(abc.reg_a(), abc.kst_b(), abc.rk_c()),
+			Opcode::NewTable => Self::NewTable(abc.reg_a(), abc.1, abc.2, k),
+			Opcode::Self_ => Self::Self_(abc.reg_a(), abc.reg_b(), abc.rk_c()),
+			Opcode::AddI => Self::AddI(abc.reg_a(), abc.reg_b(), abc.sc()),
+			Opcode::AddK => bin_k(BinOp::Add),
+			Opcode::SubK => bin_k(BinOp::Sub),
+			Opcode::MulK => bin_k(BinOp::Mul),
+			Opcode::ModK => bin_k(BinOp::Mod),
+			Opcode::PowK => bin_k(BinOp::Pow),
+			Opcode::DivK => bin_k(BinOp::Div),
+			Opcode::IDivK => bin_k(BinOp::IDiv),
+			Opcode::BAndK => bin_k(BinOp::BAnd),
+			Opcode::BOrK => bin_k(BinOp::BOr),
+			Opcode::BXorK => bin_k(BinOp::BXor),
+			Opcode::ShrI => Self::ShrI(abc.reg_a(), abc.reg_b(), abc.sc()),
+			Opcode::ShlI => Self::ShlI(abc.reg_a(), abc.reg_b(), abc.sc()),
+			Opcode::Add => bin(BinOp::Add),
+			Opcode::Sub => bin(BinOp::Sub),
+			Opcode::Mul => bin(BinOp::Mul),
+			Opcode::Mod => bin(BinOp::Mod),
+			Opcode::Pow => bin(BinOp::Pow),
+			Opcode::Div => bin(BinOp::Div),
+			Opcode::IDiv => bin(BinOp::IDiv),
+			Opcode::BAnd => bin(BinOp::BAnd),
+			Opcode::BOr => bin(BinOp::BOr),
+			Opcode::BXor => bin(BinOp::BXor),
+			Opcode::Shl => bin(BinOp::Shl),
+			Opcode::Shr => bin(BinOp::Shr),
+			Opcode::MmBin => Self::MmBin(abc.reg_a(), abc.reg_b(), abc.2),
+			Opcode::MmBinI => Self::MmBinI(abc.reg_a(), abc.sb(), abc.2, k),
+			Opcode::MmBinK => Self::MmBinK(abc.reg_a(), abc.kst_b(), abc.2, k),
+			Opcode::Unm => Self::UnOp(abc.reg_a(), UnOp::Unm, abc.reg_b()),
+			Opcode::BNot => Self::UnOp(abc.reg_a(), UnOp::BNot, abc.reg_b()),
+			Opcode::Not => Self::UnOp(abc.reg_a(), UnOp::Not, abc.reg_b()),
+			Opcode::Len => Self::UnOp(abc.reg_a(), UnOp::Len, abc.reg_b()),
+			Opcode::Concat => Self::Concat(abc.reg_a(), abc.1),
+			Opcode::Close => Self::Close(abc.reg_a()),
+			Opcode::Tbc => Self::Tbc(abc.reg_a()),
+			Opcode::Jump => Self::Jump(sJ(instr)),
+			Opcode::Eq => cond(BinCondOp::Eq),
+			Opcode::Lt => cond(BinCondOp::Lt),
+			Opcode::Le => cond(BinCondOp::Le),
+			Opcode::EqK => Self::EqK(k, abc.reg_a(), abc.kst_b(), abc.2 != 0),
+			Opcode::EqI => cond_i(CondOpI::Eq),
+			Opcode::LtI => cond_i(CondOpI::Lt),
+			Opcode::LeI => cond_i(CondOpI::Le),
+			Opcode::GtI => cond_i(CondOpI::Gt),
+			Opcode::GeI => cond_i(CondOpI::Ge),
+			Opcode::Test => Self::Test(abc.reg_a(), k),
+			Opcode::TestSet => Self::TestSet(abc.reg_a(), abc.reg_b(), k),
+			Opcode::Call => Self::Call(abc.reg_a(), abc.1, abc.2),
+			Opcode::TailCall => Self::TailCall(abc.reg_a(), abc.1, abc.2, k),
+			Opcode::Return => Self::Return(abc.reg_a(), abc.1, abc.2, k),
+			Opcode::Return0 => Self::Return0(abc.reg_a()),
+			Opcode::Return1 => Self::Return1(abc.reg_a()),
+			Opcode::ForLoop => Self::ForLoop(abc.reg_a(), Bx(instr)),
+			Opcode::ForPrep => Self::ForPrep(abc.reg_a(), Bx(instr)),
+			Opcode::TForPrep => Self::TForPrep(abc.reg_a(), Bx(instr)),
+			Opcode::TForCall => Self::TForCall(abc.reg_a(), abc.2),
+			Opcode::TForLoop => Self::TForLoop(abc.reg_a(), Bx(instr)),
+			Opcode::SetList => Self::SetList(abc.reg_a(), abc.1, abc.2, k),
+			Opcode::Closure => Self::Closure(abc.reg_a(), Bx(instr)),
+			Opcode::VarArg => Self::VarArg(abc.reg_a(), abc.2),
+			Opcode::VarArgPrep => Self::VarArgPrep(abc.reg_a()),
+			Opcode::ExtraArg => Self::ExtraArg(Ax(instr))
+		})
+	}
+
+	pub fn get_opcode(&self) -> Opcode {
+		match self {
+			Self::Move(..) => Opcode::Move,
+			Self::LoadI(..) => Opcode::LoadI,
+			Self::LoadF(..) => Opcode::LoadF,
+			Self::LoadK(..) => Opcode::LoadK,
+			Self::LoadKx(..) => Opcode::LoadKx,
+			Self::LoadFalse(..) => Opcode::LoadFalse,
+			Self::LFalseSkip(..) => Opcode::LFalseSkip,
+			Self::LoadTrue(..) => Opcode::LoadTrue,
+			Self::LoadNil(..) => Opcode::LoadNil,
+			Self::GetUpval(..) => Opcode::GetUpval,
+			Self::SetUpval(..) => Opcode::SetUpval,
+			Self::GetTabUp(..) => Opcode::GetTabUp,
+			Self::GetTable(..) => Opcode::GetTable,
+			Self::GetI(..) => Opcode::GetI,
+			Self::GetField(..) => Opcode::GetField,
+			Self::SetTabUp(..) => Opcode::SetTabUp,
+			Self::SetTable(..) => Opcode::SetTable,
+			Self::SetI(..) => Opcode::SetI,
+			Self::SetField(..) => Opcode::SetField,
+			Self::NewTable(..) => Opcode::NewTable,
+			Self::Self_(..) => Opcode::Self_,
+			Self::AddI(..) => Opcode::AddI,
+			Self::BinOpK(_, _, op, _) => match op {
+				BinOp::Add => Opcode::AddK,
+				BinOp::Sub => Opcode::SubK,
+				BinOp::Mul => Opcode::MulK,
+				BinOp::Mod => Opcode::ModK,
+				BinOp::Pow => Opcode::PowK,
+				BinOp::Div => Opcode::DivK,
+				BinOp::IDiv => Opcode::IDivK,
+				BinOp::BAnd => Opcode::BAndK,
+				BinOp::BOr => Opcode::BOrK,
+				BinOp::BXor => Opcode::BXorK,
+				BinOp::Shl | BinOp::Shr => panic!("shifts have no constant operand form")
+			},
+			Self::ShrI(..) => Opcode::ShrI,
+			Self::ShlI(..) => Opcode::ShlI,
+			Self::BinOp(_, _, op, _) => match op {
+				BinOp::Add => Opcode::Add,
+				BinOp::Sub => Opcode::Sub,
+				BinOp::Mul => Opcode::Mul,
+				BinOp::Mod => Opcode::Mod,
+				BinOp::Pow => Opcode::Pow,
+				BinOp::Div => Opcode::Div,
+				BinOp::IDiv => Opcode::IDiv,
+				BinOp::BAnd => Opcode::BAnd,
+				BinOp::BOr => Opcode::BOr,
+				BinOp::BXor => Opcode::BXor,
+				BinOp::Shl => Opcode::Shl,
+				BinOp::Shr => Opcode::Shr
+			},
+			Self::MmBin(..) => Opcode::MmBin,
+			Self::MmBinI(..) => Opcode::MmBinI,
+			Self::MmBinK(..) => Opcode::MmBinK,
+			Self::UnOp(_, op, _) => match op {
+				UnOp::Unm => Opcode::Unm,
+				UnOp::BNot => Opcode::BNot,
+				UnOp::Not => Opcode::Not,
+				UnOp::Len => Opcode::Len
+			},
+			Self::Concat(..) => Opcode::Concat,
+			Self::Close(..) => Opcode::Close,
+			Self::Tbc(..) => Opcode::Tbc,
+			Self::Jump(..) => Opcode::Jump,
+			Self::BinCondOp(_, _, op, _, _) => match op {
+				BinCondOp::Eq => Opcode::Eq,
+				BinCondOp::Lt => Opcode::Lt,
+				BinCondOp::Le => Opcode::Le
+			},
+			Self::EqK(..) => Opcode::EqK,
+			Self::CondOpI(_, _, op, _, _) => match op {
+				CondOpI::Eq => Opcode::EqI,
+				CondOpI::Lt => Opcode::LtI,
+				CondOpI::Le => Opcode::LeI,
+				CondOpI::Gt => Opcode::GtI,
+				CondOpI::Ge => Opcode::GeI
+			},
+			Self::Test(..) => Opcode::Test,
+			Self::TestSet(..) => Opcode::TestSet,
+			Self::Call(..) => Opcode::Call,
+			Self::TailCall(..) => Opcode::TailCall,
+			Self::Return(..) => Opcode::Return,
+			Self::Return0(..) => Opcode::Return0,
+			Self::Return1(..) => Opcode::Return1,
+			Self::ForLoop(..) => Opcode::ForLoop,
+			Self::ForPrep(..) => Opcode::ForPrep,
+			Self::TForPrep(..) => Opcode::TForPrep,
+			Self::TForCall(..) => Opcode::TForCall,
+			Self::TForLoop(..) => Opcode::TForLoop,
+			Self::SetList(..) => Opcode::SetList,
+			Self::Closure(..) => Opcode::Closure,
+			Self::VarArg(..) => Opcode::VarArg,
+			Self::VarArgPrep(..) => Opcode::VarArgPrep,
+			Self::ExtraArg(..) => Opcode::ExtraArg
+		}
+	}
+
+	pub fn get_opmode(&self) -> Opmode {
+		let rk = |rk: &RegKst| (rk.get(), matches!(rk, RegKst::K(_)));
+		let s = |v: i16| (v + OFFSET_SC) as u32;
+
+		match self {
+			Self::Move(a, b)
+			| Self::UnOp(a, _, b) => Opmode::iABC(a.0 as u32, b.0 as u32, 0, false),
+			Self::GetUpval(a, b)
+			| Self::SetUpval(a, b) => Opmode::iABC(a.0 as u32, *b as u32, 0, false),
+			Self::LoadI(a, sbx)
+			| Self::LoadF(a, sbx) => Opmode::iAsBx(a.0 as u32, *sbx),
+			Self::LoadK(a, kst) => Opmode::iABx(a.0 as u32, kst.0),
+			Self::LoadKx(a) => Opmode::iABx(a.0 as u32, 0),
+			Self::LoadFalse(a)
+			| Self::LFalseSkip(a)
+			| Self::LoadTrue(a)
+			| Self::Close(a)
+			| Self::Tbc(a)
+			| Self::VarArgPrep(a) => Opmode::iABC(a.0 as u32, 0, 0, false),
+			// B holds the number of results plus one, as emitted by luaK_ret
+			Self::Return0(a) => Opmode::iABC(a.0 as u32, 1, 0, false),
+			Self::Return1(a) => Opmode::iABC(a.0 as u32, 2, 0, false),
+			Self::LoadNil(a, b)
+			| Self::Concat(a, b) => Opmode::iABC(a.0 as u32, *b as u32, 0, false),
+			Self::GetTabUp(a, b, c) => Opmode::iABC(a.0 as u32, *b as u32, c.0, false),
+			Self::GetTable(a, b, c) => Opmode::iABC(a.0 as u32, b.0 as u32, c.0 as u32, false),
+			Self::GetI(a, b, c) => Opmode::iABC(a.0 as u32, b.0 as u32, *c as u32, false),
+			Self::GetField(a, b, c) => Opmode::iABC(a.0 as u32, b.0 as u32, c.0, false),
+			Self::SetTabUp(a, b, c) => {
+				let (c, k) = rk(c);
+				Opmode::iABC(*a as u32, b.0, c, k)
+			},
+			Self::SetTable(a, b, c)
+			| Self::Self_(a, b, c) => {
+				let (c, k) = rk(c);
+				Opmode::iABC(a.0 as u32, b.0 as u32, c, k)
+			},
+			Self::SetI(a, b, c) => {
+				let (c, k) = rk(c);
+				Opmode::iABC(a.0 as u32, *b as u32, c, k)
+			},
+			Self::SetField(a, b, c) => {
+				let (c, k) = rk(c);
+				Opmode::iABC(a.0 as u32, b.0, c, k)
+			},
+			Self::NewTable(a, b, c, k)
+			| Self::TailCall(a, b, c, k)
+			| Self::Return(a, b, c, k)
+			| Self::SetList(a, b, c, k) => Opmode::iABC(a.0 as u32, *b as u32, *c as u32, *k),
+			Self::AddI(a, b, sc)
+			| Self::ShrI(a, b, sc)
+			| Self::ShlI(a, b, sc) => Opmode::iABC(a.0 as u32, b.0 as u32, s(*sc), false),
+			Self::BinOpK(a, b, _, c) => Opmode::iABC(a.0 as u32, b.0 as u32, c.0, false),
+			Self::BinOp(a, b, _, c) => Opmode::iABC(a.0 as u32, b.0 as u32, c.0 as u32, false),
+			Self::MmBin(a, b, c) => Opmode::iABC(a.0 as u32, b.0 as u32, *c as u32, false),
+			Self::MmBinI(a, sb, c, k) => Opmode::iABC(a.0 as u32, s(*sb), *c as u32, *k),
+			Self::MmBinK(a, b, c, k) => Opmode::iABC(a.0 as u32, b.0, *c as u32, *k),
+			Self::Jump(sj) => Opmode::isJ(*sj),
+			Self::BinCondOp(k, a, _, b, float) => Opmode::iABC(a.0 as u32, b.0 as u32, *float as u32, *k),
+			Self::EqK(k, a, b, float) => Opmode::iABC(a.0 as u32, b.0, *float as u32, *k),
+			Self::CondOpI(k, a, _, sb, float) => Opmode::iABC(a.0 as u32, s(*sb), *float as u32, *k),
+			Self::Test(a, k) => Opmode::iABC(a.0 as u32, 0, 0, *k),
+			Self::TestSet(a, b, k) => Opmode::iABC(a.0 as u32, b.0 as u32, 0, *k),
+			Self::Call(a, b, c) => Opmode::iABC(a.0 as u32, *b as u32, *c as u32, false),
+			Self::ForLoop(a, bx)
+			| Self::ForPrep(a, bx)
+			| Self::TForPrep(a, bx)
+			| Self::TForLoop(a, bx)
+			| Self::Closure(a, bx) => Opmode::iABx(a.0 as u32, *bx),
+			Self::TForCall(a, c)
+			| Self::VarArg(a, c) => Opmode::iABC(a.0 as u32, 0, *c as u32, false),
+			Self::ExtraArg(ax) => Opmode::iAx(*ax)
+		}
+	}
+}
+
+// unique id
+fn get_id() -> usize {
+	COUNTER.fetch_add(1, Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone)]
+pub struct Instruction(pub Opcode, pub Instr, pub Opmode, pub usize);
+impl Instruction {
+	pub fn new(instr: Instr) -> Self {
+		let op = instr.get_opcode();
+		let mode = instr.get_opmode();
+		Self(op, instr, mode, get_id())
+	}
+
+	pub fn from_instr(instr: u32) -> Self {
+		Self::new(Instr::from_instr(instr))
+	}
+
+	pub fn try_from_instr(instr: u32) -> Result<Self, DecodeErrorKind> {
+		Ok(Self::new(Instr::try_from_instr(instr)?))
+	}
+
+	pub fn serialize(&self) -> u32 {
+		let serialized = self.0.to_instr();
+		match self.2 {
+			Opmode::iABC(a, b, c, k) => serialized | ((a & 0xff) << 7) | ((k as u32) << 15) | ((b & 0xff) << 16) | ((c & 0xff) << 24),
+			Opmode::iABx(a, bx) => serialized | ((a & 0xff) << 7) | ((bx & 0x1ffff) << 15),
+			Opmode::iAsBx(a, sbx) => serialized | ((a & 0xff) << 7) | ((((sbx + OFFSET_SBX) as u32) & 0x1ffff) << 15),
+			Opmode::iAx(ax) => serialized | ((ax & 0x1ffffff) << 7),
+			Opmode::isJ(sj) => serialized | ((((sj + OFFSET_SJ) as u32) & 0x1ffffff) << 7)
+		}
+	}
+}
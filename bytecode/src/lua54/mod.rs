@@ -0,0 +1,118 @@
+mod deserialize;
+pub mod instruction;
+mod serialize;
+
+use crate::{Endianness, LuaString};
+
+pub use crate::lua51::{Reader, Writer, LocalVar};
+pub use crate::lua53::{Constants, MAX_SHORT_LEN};
+pub use deserialize::{deserialize_bytecode, try_deserialize_bytecode, deserialize_from};
+pub use serialize::{serialize_bytecode, serialize_to};
+
+// lineinfo entry that defers to abs_line_info
+pub const ABSLINEINFO: i8 = -0x80;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpvalueKind {
+	Regular = 0,
+	Const = 1, // <const>
+	ToClose = 2, // <close>
+	CompileTimeConst = 3 // <const> folded at compile time
+}
+
+// where a closure finds an upvalue when it is created
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpvalueDesc {
+	pub in_stack: bool, // register of the enclosing function, otherwise one of its upvalues
+	pub index: u8,
+	pub kind: UpvalueKind
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AbsLineInfo {
+	pub pc: u32,
+	pub line: u32
+}
+
+#[derive(Debug, Clone)]
+pub struct Proto {
+	pub source: Option<LuaString>, // None when stripped or the same as the parent's
+	pub line_defined: u32,
+	pub last_line_defined: u32,
+	pub nparams: u8,
+	pub is_vararg_flag: u8,
+	pub max_stack_size: u8,
+	pub instructions: Vec<instruction::Instruction>,
+	pub constants: Vec<Constants>,
+	pub upvalues: Vec<UpvalueDesc>,
+	pub prototypes: Vec<Self>,
+	pub line_info: Option<Vec<i8>>, // line delta from the previous instruction
+	pub abs_line_info: Option<Vec<AbsLineInfo>>,
+	pub locals: Option<Vec<LocalVar>>,
+	pub upvals: Option<Vec<LuaString>>
+}
+
+impl Proto {
+	// source line of the instruction at pc, the same way luaG_getfuncline works it out
+	pub fn line(&self, pc: usize) -> Option<u32> {
+		let line_info = self.line_info.as_ref()?;
+		if pc >= line_info.len() {
+			return None;
+		}
+
+		let abs = self.abs_line_info.as_deref().unwrap_or_default();
+		let (mut base_pc, mut line) = match abs.iter().rev().find(|abs| abs.pc as usize <= pc) {
+			Some(abs) => (abs.pc as usize + 1, abs.line as i64),
+			None => (0, self.line_defined as i64)
+		};
+		while base_pc <= pc {
+			line += line_info[base_pc] as i64;
+			base_pc += 1;
+		}
+
+		u32::try_from(line).ok()
+	}
+}
+
+impl Default for Proto {
+	fn default() -> Self {
+		Self {
+			source: Some("@default.lua".into()),
+			line_defined: 0,
+			last_line_defined: 0,
+			nparams: 0,
+			is_vararg_flag: 1,
+			max_stack_size: 2,
+			instructions: vec![],
+			constants: vec![],
+			// the main chunk's only upvalue is _ENV
+			upvalues: vec![UpvalueDesc { in_stack: true, index: 0, kind: UpvalueKind::Regular }],
+			prototypes: vec![],
+			line_info: Some(vec![]),
+			abs_line_info: Some(vec![]),
+			locals: Some(vec![]),
+			upvals: Some(vec![])
+		}
+	}
+}
+
+// 5.4 dumps every count, size and line as a varint, so int and size_t are gone from the header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+	pub endianness: Endianness,
+	pub instruction_size: u8,
+	pub integer_size: u8, // lua_Integer
+	pub number_size: u8 // lua_Number
+}
+
+impl Default for Header {
+	// what a stock 64-bit luac emits
+	fn default() -> Self {
+		Self {
+			endianness: Endianness::Little,
+			instruction_size: 4,
+			integer_size: 8,
+			number_size: 8
+		}
+	}
+}
@@ -0,0 +1,119 @@
+use crate::lua52::LUAC_TAIL;
+use crate::lua53::{LUAC_INT, LUAC_NUM};
+use super::{Writer, Header, Proto, Constants, MAX_SHORT_LEN};
+
+fn dump_varint(writer: &mut Writer, x: u64) {
+	let mut groups = vec![(x & 0x7f) as u8 | 0x80];
+	let mut x = x >> 7;
+	while x != 0 {
+		groups.push((x & 0x7f) as u8);
+		x >>= 7;
+	}
+	groups.reverse();
+	writer.bytes(groups);
+}
+
+fn dump_string(writer: &mut Writer, str: Option<&[u8]>) {
+	match str {
+		Some(str) => {
+			dump_varint(writer, str.len() as u64 + 1);
+			writer.bytes(str.to_vec());
+		}
+		None => dump_varint(writer, 0)
+	}
+}
+
+fn dump_header(writer: &mut Writer, header: &Header) {
+	writer.bytes(b"\x1BLua".to_vec());
+	writer.byte(b'\x54');
+	writer.byte(0);
+	writer.bytes(LUAC_TAIL.to_vec());
+	writer.byte(header.instruction_size);
+	writer.byte(header.integer_size);
+	writer.byte(header.number_size);
+	writer.integer(LUAC_INT, header.integer_size);
+	writer.number(LUAC_NUM, header.number_size, false);
+}
+
+fn dump_vector<I>(writer: &mut Writer, list: I, n: usize, header: &Header, dump: fn(&mut Writer, header: &Header, I::Item)) where I: Iterator {
+	dump_varint(writer, n as u64);
+	list.for_each(|v| dump(writer, header, v));
+}
+
+fn dump_chunk(writer: &mut Writer, header: &Header, proto: &Proto) {
+	dump_string(writer, proto.source.as_deref());
+	dump_varint(writer, proto.line_defined.into());
+	dump_varint(writer, proto.last_line_defined.into());
+	writer.byte(proto.nparams);
+	writer.byte(proto.is_vararg_flag);
+	writer.byte(proto.max_stack_size);
+
+	// instructions
+	dump_vector(writer, proto.instructions.iter(), proto.instructions.len(), header,
+		|writer, header, instr| writer.int(instr.serialize(), header.instruction_size));
+
+	// constants
+	dump_vector(writer, proto.constants.iter(), proto.constants.len(), header, |writer, header, kst| {
+		match kst {
+			Constants::Nil => writer.byte(0x00),
+			Constants::Boolean(false) => writer.byte(0x01),
+			Constants::Boolean(true) => writer.byte(0x11),
+			&Constants::Integer(n) => {writer.byte(0x03); writer.integer(n, header.integer_size)},
+			&Constants::Float(n) => {writer.byte(0x13); writer.number(n, header.number_size, false)},
+			Constants::String(s) => {
+				writer.byte(if s.len() <= MAX_SHORT_LEN { 0x04 } else { 0x14 });
+				dump_string(writer, Some(s));
+			}
+		}
+	});
+
+	// upvalues
+	dump_vector(writer, proto.upvalues.iter(), proto.upvalues.len(), header, |writer, _, upval| {
+		writer.byte(upval.in_stack as u8);
+		writer.byte(upval.index);
+		writer.byte(upval.kind as u8);
+	});
+
+	// protos
+	dump_vector(writer, proto.prototypes.iter(), proto.prototypes.len(), header, dump_chunk);
+
+	// line info
+	let line_info = proto.line_info.as_deref().unwrap_or_default();
+	dump_vector(writer, line_info.iter(), line_info.len(), header, |writer, _, delta| writer.byte(*delta as u8));
+	let abs_line_info = proto.abs_line_info.as_deref().unwrap_or_default();
+	dump_vector(writer, abs_line_info.iter(), abs_line_info.len(), header, |writer, _, abs| {
+		dump_varint(writer, abs.pc.into());
+		dump_varint(writer, abs.line.into());
+	});
+
+	// locals
+	let locals = proto.locals.as_deref().unwrap_or_default();
+	dump_vector(writer, locals.iter(), locals.len(), header, |writer, _, local| {
+		dump_string(writer, Some(&local.name));
+		dump_varint(writer, local.start_pc.into());
+		dump_varint(writer, local.end_pc.into());
+	});
+
+	// upvalue names
+	let upvals = proto.upvals.as_deref().unwrap_or_default();
+	dump_vector(writer, upvals.iter(), upvals.len(), header, |writer, _, upval| dump_string(writer, Some(upval)));
+}
+
+pub fn serialize_bytecode(header: &Header, proto: &Proto) -> Vec<u8> {
+	let mut writer = Writer::new();
+	serialize_to(&mut writer, header, proto);
+	writer.as_bytes().into()
+}
+
+// appends one chunk to the writer, returning how many bytes it took up. errors from a
+// streaming writer only show up once it is flushed
+pub fn serialize_to(writer: &mut Writer, header: &Header, proto: &Proto) -> usize {
+	let start = writer.position();
+	writer.set_endianness(header.endianness);
+
+	dump_header(writer, header);
+	writer.byte(proto.upvalues.len() as u8);
+	dump_chunk(writer, header, proto);
+
+	writer.position() - start
+}
@@ -0,0 +1,323 @@
+use crate::{Bytecode, DecodeError, DecodeErrorKind, Endianness, Field, LuaString};
+use super::{
+	Reader, Proto, Header, UpvalueDesc, GcConstant, TableConstant, NumConstant, DebugInfo, VarInfo, VarName,
+	PROTO_UV_LOCAL, PROTO_UV_IMMUTABLE, instruction::Instruction
+};
+
+// dump header flags
+pub(crate) const BCDUMP_F_BE: u32 = 0x01;
+pub(crate) const BCDUMP_F_STRIP: u32 = 0x02;
+pub(crate) const BCDUMP_F_FFI: u32 = 0x04;
+pub(crate) const BCDUMP_F_FR2: u32 = 0x08;
+
+// first byte of a varinfo entry below this is an internal name rather than a string
+pub(crate) const VARNAME_MAX: u8 = 7;
+
+fn uleb128(reader: &mut Reader) -> Result<u32, DecodeError> {
+	let start = reader.position();
+	let v = uleb128_33(reader)?;
+	u32::try_from(v).map_err(|_| reader.error_at(start, DecodeErrorKind::IntegerOverflow))
+}
+
+// up to 5 groups of 7 bits, least significant first
+fn uleb128_33(reader: &mut Reader) -> Result<u64, DecodeError> {
+	let start = reader.position();
+	let mut v: u64 = 0;
+	let mut shift = 0;
+	loop {
+		let b = reader.byte()?;
+		if shift > 28 {
+			return Err(reader.error_at(start, DecodeErrorKind::IntegerOverflow));
+		}
+		v |= ((b & 0x7f) as u64) << shift;
+		shift += 7;
+		if b < 0x80 {
+			return Ok(v);
+		}
+	}
+}
+
+fn string(reader: &mut Reader, len: usize) -> Result<LuaString, DecodeError> {
+	Ok(LuaString(reader.bytes(len)?.to_vec()))
+}
+
+// nul terminated, with the first byte already read
+fn cstring(reader: &mut Reader, first: u8) -> Result<LuaString, DecodeError> {
+	let mut bytes = vec![first];
+	loop {
+		match reader.byte()? {
+			0 => break,
+			b => bytes.push(b)
+		}
+	}
+	Ok(LuaString(bytes))
+}
+
+fn header(reader: &mut Reader) -> Result<(Header, bool), DecodeError> {
+	reader.set_field(Field::Header);
+
+	let start = reader.position();
+	if reader.bytes(3)? != b"\x1BLJ" {
+		return Err(reader.error_at(start, DecodeErrorKind::BadSignature));
+	}
+
+	let at = reader.position();
+	let version = reader.byte()?;
+	if version != 1 && version != 2 {
+		return Err(reader.error_at(at, DecodeErrorKind::UnsupportedVersion(version)));
+	}
+
+	let at = reader.position();
+	let flags = uleb128(reader)?;
+	if flags & !(BCDUMP_F_FR2 * 2 - 1) != 0 {
+		return Err(reader.error_at(at, DecodeErrorKind::UnsupportedFormat(flags as u8)));
+	}
+	let endianness = if flags & BCDUMP_F_BE != 0 { Endianness::Big } else { Endianness::Little };
+	reader.set_endianness(endianness);
+
+	let strip = flags & BCDUMP_F_STRIP != 0;
+	let chunk_name = if strip {
+		None
+	} else {
+		reader.set_field(Field::Source);
+		let len = uleb128(reader)?;
+		Some(string(reader, len as usize)?)
+	};
+
+	Ok((Header {
+		version,
+		endianness,
+		ffi: flags & BCDUMP_F_FFI != 0,
+		fr2: flags & BCDUMP_F_FR2 != 0,
+		chunk_name
+	}, strip))
+}
+
+fn table_constant(reader: &mut Reader) -> Result<TableConstant, DecodeError> {
+	Ok(match uleb128(reader)? {
+		0 => TableConstant::Nil,
+		1 => TableConstant::Boolean(false),
+		2 => TableConstant::Boolean(true),
+		3 => TableConstant::Int(uleb128(reader)? as i32),
+		4 => {
+			let lo = uleb128(reader)? as u64;
+			let hi = uleb128(reader)? as u64;
+			TableConstant::Num(f64::from_bits(hi << 32 | lo))
+		}
+		tp => TableConstant::String(string(reader, tp as usize - 5)?)
+	})
+}
+
+fn gc_constant(reader: &mut Reader, children: &mut Vec<Proto>, stack: &mut Vec<Proto>, i: usize) -> Result<GcConstant, DecodeError> {
+	reader.set_field(Field::ConstantTag(i));
+	let at = reader.position();
+	let tp = uleb128(reader)?;
+
+	reader.set_field(Field::Constant(i));
+	let u64 = |reader: &mut Reader| -> Result<u64, DecodeError> {
+		let lo = uleb128(reader)? as u64;
+		let hi = uleb128(reader)? as u64;
+		Ok(hi << 32 | lo)
+	};
+	Ok(match tp {
+		0 => {
+			// children are dumped before their parent, so the latest one belongs to the highest index
+			let child = stack.pop().ok_or_else(|| reader.error_at(at, DecodeErrorKind::UnbalancedProtos))?;
+			children.push(child);
+			GcConstant::Child
+		}
+		1 => {
+			let narray = uleb128(reader)?;
+			let nhash = uleb128(reader)?;
+			let mut array = vec![];
+			for _ in 0..narray {
+				array.push(table_constant(reader)?);
+			}
+			let mut hash = vec![];
+			for _ in 0..nhash {
+				let key = table_constant(reader)?;
+				hash.push((key, table_constant(reader)?));
+			}
+			GcConstant::Table { array, hash }
+		}
+		2 => GcConstant::I64(u64(reader)? as i64),
+		3 => GcConstant::U64(u64(reader)?),
+		4 => {
+			let re = f64::from_bits(u64(reader)?);
+			GcConstant::Complex(re, f64::from_bits(u64(reader)?))
+		}
+		tp => GcConstant::String(string(reader, tp as usize - 5)?)
+	})
+}
+
+fn num_constant(reader: &mut Reader) -> Result<NumConstant, DecodeError> {
+	let start = reader.position();
+	// lowest bit tells numbers from narrowed ints
+	let v = uleb128_33(reader)?;
+	let lo = u32::try_from(v >> 1).map_err(|_| reader.error_at(start, DecodeErrorKind::IntegerOverflow))?;
+	if v & 1 == 0 {
+		return Ok(NumConstant::Int(lo as i32));
+	}
+	let hi = uleb128(reader)?;
+	Ok(NumConstant::Num(f64::from_bits((hi as u64) << 32 | lo as u64)))
+}
+
+fn debug_info(reader: &mut Reader, ninstrs: usize, nupvals: usize, first_line: u32, num_line: u32) -> Result<DebugInfo, DecodeError> {
+	let width = match num_line {
+		0..=0xff => 1,
+		0x100..=0xffff => 2,
+		_ => 4
+	};
+	let mut line_info = vec![];
+	for i in 0..ninstrs {
+		reader.set_field(Field::SourceLine(i));
+		line_info.push(reader.int(width)?);
+	}
+
+	let mut upvalue_names = vec![];
+	for i in 0..nupvals {
+		reader.set_field(Field::Upvalue(i));
+		let first = reader.byte()?;
+		upvalue_names.push(if first == 0 { LuaString::new() } else { cstring(reader, first)? });
+	}
+
+	let mut vars = vec![];
+	let mut last_pc = 0;
+	loop {
+		reader.set_field(Field::Local(vars.len()));
+		let name = match reader.byte()? {
+			0 => break,
+			t if t < VARNAME_MAX => VarName::Internal(t),
+			first => VarName::Named(cstring(reader, first)?)
+		};
+		let start_pc = last_pc + uleb128(reader)?;
+		let end_pc = start_pc + uleb128(reader)?;
+		last_pc = start_pc;
+		vars.push(VarInfo { name, start_pc, end_pc });
+	}
+
+	Ok(DebugInfo { first_line, num_line, line_info, upvalue_names, vars })
+}
+
+fn proto(reader: &mut Reader, header: &Header, strip: bool, stack: &mut Vec<Proto>) -> Result<Proto, DecodeError> {
+	reader.set_field(Field::ProtoInfo);
+	let flags = reader.byte()?;
+	let nparams = reader.byte()?;
+	let framesize = reader.byte()?;
+	let sizeuv = reader.byte()? as usize;
+	reader.set_field(Field::ConstantCount);
+	let sizekgc = uleb128(reader)? as usize;
+	let sizekn = uleb128(reader)? as usize;
+	reader.set_field(Field::InstructionCount);
+	let sizebc = uleb128(reader)? as usize;
+
+	let mut sizedbg = 0;
+	let (mut first_line, mut num_line) = (0, 0);
+	if !strip {
+		reader.set_field(Field::SourceLineCount);
+		sizedbg = uleb128(reader)? as usize;
+		if sizedbg != 0 {
+			first_line = uleb128(reader)?;
+			num_line = uleb128(reader)?;
+		}
+	}
+
+	// instructions
+	let mut instructions = vec![];
+	for i in 0..sizebc {
+		reader.set_field(Field::Instruction(i));
+		let at = reader.position();
+		let instr = reader.int(4)?;
+		instructions.push(Instruction::try_from_instr(instr, header.version).map_err(|kind| reader.error_at(at, kind))?);
+	}
+
+	// upvalues
+	let mut upvalues = vec![];
+	for i in 0..sizeuv {
+		reader.set_field(Field::UpvalueDesc(i));
+		let uv = reader.uint(2)? as u16;
+		upvalues.push(UpvalueDesc {
+			in_stack: uv & PROTO_UV_LOCAL != 0,
+			immutable: uv & PROTO_UV_IMMUTABLE != 0,
+			index: uv & !(PROTO_UV_LOCAL | PROTO_UV_IMMUTABLE)
+		});
+	}
+
+	// gc constants are dumped from the highest index down
+	let mut kgc = vec![];
+	let mut prototypes = vec![];
+	for i in (0..sizekgc).rev() {
+		kgc.push(gc_constant(reader, &mut prototypes, stack, i)?);
+	}
+	kgc.reverse();
+	prototypes.reverse();
+
+	// number constants
+	let mut knum = vec![];
+	for i in 0..sizekn {
+		reader.set_field(Field::NumConstant(i));
+		knum.push(num_constant(reader)?);
+	}
+
+	// debug info
+	let debug = if sizedbg != 0 {
+		let start = reader.position();
+		let debug = debug_info(reader, sizebc, sizeuv, first_line, num_line)?;
+		if reader.position() - start != sizedbg {
+			reader.set_field(Field::SourceLineCount);
+			return Err(reader.error_at(start, DecodeErrorKind::BadProtoLength));
+		}
+		Some(debug)
+	} else {
+		None
+	};
+
+	Ok(Proto {
+		flags,
+		nparams,
+		framesize,
+		instructions,
+		upvalues,
+		kgc,
+		knum,
+		prototypes,
+		debug
+	})
+}
+
+pub fn try_deserialize_bytecode(bytecode: &Bytecode) -> Result<(Header, Proto), DecodeError> {
+	let (header_data, proto, _) = deserialize_from(&mut Reader::new(bytecode))?;
+	Ok((header_data, proto))
+}
+
+// reads one chunk from wherever the reader is, also returning how many bytes it took up
+pub fn deserialize_from(reader: &mut Reader) -> Result<(Header, Proto, usize), DecodeError> {
+	let start = reader.position();
+	let (header_data, strip) = header(reader)?;
+
+	// protos come children first, each prefixed with its length; a zero length ends the dump
+	let mut stack = vec![];
+	loop {
+		reader.set_field(Field::ProtoCount);
+		let len = uleb128(reader)? as usize;
+		if len == 0 {
+			break;
+		}
+
+		let start = reader.position();
+		let proto = proto(reader, &header_data, strip, &mut stack)?;
+		if reader.position() - start != len {
+			return Err(reader.error_at(start, DecodeErrorKind::BadProtoLength));
+		}
+		stack.push(proto);
+	}
+
+	if stack.len() != 1 {
+		return Err(reader.error(DecodeErrorKind::UnbalancedProtos));
+	}
+	Ok((header_data, stack.remove(0), reader.position() - start))
+}
+
+pub fn deserialize_bytecode(bytecode: &Bytecode) -> (Header, Proto) {
+	try_deserialize_bytecode(bytecode).unwrap_or_else(|err| panic!("{}", err))
+}
@@ -0,0 +1,403 @@
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+
+use crate::DecodeErrorKind;
+pub use crate::lua51::instruction::{Reg, Kst, Upvalue};
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+static COUNTER: AtomicUsize = AtomicUsize::new(1);
+
+// jump targets are stored biased, relative to the next instruction
+pub const BCBIAS_J: i32 = 0x8000;
+
+// numbered the way 2.1 (dump version 2) numbers them
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+	IsLt = 0,
+	IsGe,
+	IsLe,
+	IsGt,
+	IsEqV,
+	IsNeV,
+	IsEqS,
+	IsNeS,
+	IsEqN,
+	IsNeN,
+	IsEqP,
+	IsNeP,
+	IsTC,
+	IsFC,
+	IsT,
+	IsF,
+	IsType,
+	IsNum,
+	Mov,
+	Not,
+	Unm,
+	Len,
+	AddVN,
+	SubVN,
+	MulVN,
+	DivVN,
+	ModVN,
+	AddNV,
+	SubNV,
+	MulNV,
+	DivNV,
+	ModNV,
+	AddVV,
+	SubVV,
+	MulVV,
+	DivVV,
+	ModVV,
+	Pow,
+	Cat,
+	KStr,
+	KCData,
+	KShort,
+	KNum,
+	KPri,
+	KNil,
+	UGet,
+	USetV,
+	USetS,
+	USetN,
+	USetP,
+	UClo,
+	FNew,
+	TNew,
+	TDup,
+	GGet,
+	GSet,
+	TGetV,
+	TGetS,
+	TGetB,
+	TGetR,
+	TSetV,
+	TSetS,
+	TSetB,
+	TSetM,
+	TSetR,
+	CallM,
+	Call,
+	CallMT,
+	CallT,
+	IterC,
+	IterN,
+	VArg,
+	IsNext,
+	RetM,
+	Ret,
+	Ret0,
+	Ret1,
+	ForI,
+	JForI,
+	ForL,
+	IForL,
+	JForL,
+	IterL,
+	IIterL,
+	JIterL,
+	Loop,
+	ILoop,
+	JLoop,
+	Jmp,
+	FuncF,
+	IFuncF,
+	JFuncF,
+	FuncV,
+	IFuncV,
+	JFuncV,
+	FuncC,
+	FuncCW
+}
+
+// operand kinds, same as BCMode in lj_bc.h
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+	None,
+	Dst, // register written to
+	Base, // first of a range of registers
+	Var, // register read from
+	RBase, // base register, read only
+	Uv, // upvalue index
+	Lit, // unsigned literal
+	LitS, // signed literal
+	Pri, // nil, false or true
+	Num, // index into the number constants
+	Str, // index into the gc constants
+	Tab,
+	Func,
+	Jump,
+	CData
+}
+
+// ops 2.0 (dump version 1) doesn't have, everything after them is shifted down
+const NOT_IN_V1: [Opcode; 4] = [Opcode::IsType, Opcode::IsNum, Opcode::TGetR, Opcode::TSetR];
+
+impl Opcode {
+	fn try_from_byte(op: u8, version: u8) -> Result<Self, DecodeErrorKind> {
+		let mut v2 = op;
+		if version == 1 {
+			for missing in NOT_IN_V1 {
+				if v2 >= missing as u8 {
+					v2 += 1;
+				}
+			}
+		}
+		Self::from_u8(v2)
+			.filter(|op| version != 1 || !NOT_IN_V1.contains(op))
+			.ok_or(DecodeErrorKind::InvalidOpcode(op))
+	}
+
+	fn to_byte(self, version: u8) -> u8 {
+		let mut op = self as u8;
+		if version == 1 {
+			assert!(!NOT_IN_V1.contains(&self), "{:?} doesn't exist before dump version 2", self);
+			op -= NOT_IN_V1.iter().filter(|missing| (**missing as u8) < self as u8).count() as u8;
+		}
+		op
+	}
+
+	// modes of A, B and C or D; ops without a B operand take a 16 bit D
+	pub fn modes(self) -> (Mode, Mode, Mode) {
+		match self {
+			Self::IsLt => (Mode::Var, Mode::None, Mode::Var),
+			Self::IsGe => (Mode::Var, Mode::None, Mode::Var),
+			Self::IsLe => (Mode::Var, Mode::None, Mode::Var),
+			Self::IsGt => (Mode::Var, Mode::None, Mode::Var),
+			Self::IsEqV => (Mode::Var, Mode::None, Mode::Var),
+			Self::IsNeV => (Mode::Var, Mode::None, Mode::Var),
+			Self::IsEqS => (Mode::Var, Mode::None, Mode::Str),
+			Self::IsNeS => (Mode::Var, Mode::None, Mode::Str),
+			Self::IsEqN => (Mode::Var, Mode::None, Mode::Num),
+			Self::IsNeN => (Mode::Var, Mode::None, Mode::Num),
+			Self::IsEqP => (Mode::Var, Mode::None, Mode::Pri),
+			Self::IsNeP => (Mode::Var, Mode::None, Mode::Pri),
+			Self::IsTC => (Mode::Dst, Mode::None, Mode::Var),
+			Self::IsFC => (Mode::Dst, Mode::None, Mode::Var),
+			Self::IsT => (Mode::None, Mode::None, Mode::Var),
+			Self::IsF => (Mode::None, Mode::None, Mode::Var),
+			Self::IsType => (Mode::Var, Mode::None, Mode::Lit),
+			Self::IsNum => (Mode::Var, Mode::None, Mode::Lit),
+			Self::Mov => (Mode::Dst, Mode::None, Mode::Var),
+			Self::Not => (Mode::Dst, Mode::None, Mode::Var),
+			Self::Unm => (Mode::Dst, Mode::None, Mode::Var),
+			Self::Len => (Mode::Dst, Mode::None, Mode::Var),
+			Self::AddVN => (Mode::Dst, Mode::Var, Mode::Num),
+			Self::SubVN => (Mode::Dst, Mode::Var, Mode::Num),
+			Self::MulVN => (Mode::Dst, Mode::Var, Mode::Num),
+			Self::DivVN => (Mode::Dst, Mode::Var, Mode::Num),
+			Self::ModVN => (Mode::Dst, Mode::Var, Mode::Num),
+			Self::AddNV => (Mode::Dst, Mode::Var, Mode::Num),
+			Self::SubNV => (Mode::Dst, Mode::Var, Mode::Num),
+			Self::MulNV => (Mode::Dst, Mode::Var, Mode::Num),
+			Self::DivNV => (Mode::Dst, Mode::Var, Mode::Num),
+			Self::ModNV => (Mode::Dst, Mode::Var, Mode::Num),
+			Self::AddVV => (Mode::Dst, Mode::Var, Mode::Var),
+			Self::SubVV => (Mode::Dst, Mode::Var, Mode::Var),
+			Self::MulVV => (Mode::Dst, Mode::Var, Mode::Var),
+			Self::DivVV => (Mode::Dst, Mode::Var, Mode::Var),
+			Self::ModVV => (Mode::Dst, Mode::Var, Mode::Var),
+			Self::Pow => (Mode::Dst, Mode::Var, Mode::Var),
+			Self::Cat => (Mode::Dst, Mode::RBase, Mode::RBase),
+			Self::KStr => (Mode::Dst, Mode::None, Mode::Str),
+			Self::KCData => (Mode::Dst, Mode::None, Mode::CData),
+			Self::KShort => (Mode::Dst, Mode::None, Mode::LitS),
+			Self::KNum => (Mode::Dst, Mode::None, Mode::Num),
+			Self::KPri => (Mode::Dst, Mode::None, Mode::Pri),
+			Self::KNil => (Mode::Base, Mode::None, Mode::Base),
+			Self::UGet => (Mode::Dst, Mode::None, Mode::Uv),
+			Self::USetV => (Mode::Uv, Mode::None, Mode::Var),
+			Self::USetS => (Mode::Uv, Mode::None, Mode::Str),
+			Self::USetN => (Mode::Uv, Mode::None, Mode::Num),
+			Self::USetP => (Mode::Uv, Mode::None, Mode::Pri),
+			Self::UClo => (Mode::RBase, Mode::None, Mode::Jump),
+			Self::FNew => (Mode::Dst, Mode::None, Mode::Func),
+			Self::TNew => (Mode::Dst, Mode::None, Mode::Lit),
+			Self::TDup => (Mode::Dst, Mode::None, Mode::Tab),
+			Self::GGet => (Mode::Dst, Mode::None, Mode::Str),
+			Self::GSet => (Mode::Var, Mode::None, Mode::Str),
+			Self::TGetV => (Mode::Dst, Mode::Var, Mode::Var),
+			Self::TGetS => (Mode::Dst, Mode::Var, Mode::Str),
+			Self::TGetB => (Mode::Dst, Mode::Var, Mode::Lit),
+			Self::TGetR => (Mode::Dst, Mode::Var, Mode::Var),
+			Self::TSetV => (Mode::Var, Mode::Var, Mode::Var),
+			Self::TSetS => (Mode::Var, Mode::Var, Mode::Str),
+			Self::TSetB => (Mode::Var, Mode::Var, Mode::Lit),
+			Self::TSetM => (Mode::Base, Mode::None, Mode::Num),
+			Self::TSetR => (Mode::Var, Mode::Var, Mode::Var),
+			Self::CallM => (Mode::Base, Mode::Lit, Mode::Lit),
+			Self::Call => (Mode::Base, Mode::Lit, Mode::Lit),
+			Self::CallMT => (Mode::Base, Mode::None, Mode::Lit),
+			Self::CallT => (Mode::Base, Mode::None, Mode::Lit),
+			Self::IterC => (Mode::Base, Mode::Lit, Mode::Lit),
+			Self::IterN => (Mode::Base, Mode::Lit, Mode::Lit),
+			Self::VArg => (Mode::Base, Mode::Lit, Mode::Lit),
+			Self::IsNext => (Mode::Base, Mode::None, Mode::Jump),
+			Self::RetM => (Mode::Base, Mode::None, Mode::Lit),
+			Self::Ret => (Mode::RBase, Mode::None, Mode::Lit),
+			Self::Ret0 => (Mode::RBase, Mode::None, Mode::Lit),
+			Self::Ret1 => (Mode::RBase, Mode::None, Mode::Lit),
+			Self::ForI => (Mode::Base, Mode::None, Mode::Jump),
+			Self::JForI => (Mode::Base, Mode::None, Mode::Jump),
+			Self::ForL => (Mode::Base, Mode::None, Mode::Jump),
+			Self::IForL => (Mode::Base, Mode::None, Mode::Jump),
+			Self::JForL => (Mode::Base, Mode::None, Mode::Lit),
+			Self::IterL => (Mode::Base, Mode::None, Mode::Jump),
+			Self::IIterL => (Mode::Base, Mode::None, Mode::Jump),
+			Self::JIterL => (Mode::Base, Mode::None, Mode::Lit),
+			Self::Loop => (Mode::RBase, Mode::None, Mode::Jump),
+			Self::ILoop => (Mode::RBase, Mode::None, Mode::Jump),
+			Self::JLoop => (Mode::RBase, Mode::None, Mode::Lit),
+			Self::Jmp => (Mode::RBase, Mode::None, Mode::Jump),
+			Self::FuncF => (Mode::RBase, Mode::None, Mode::None),
+			Self::IFuncF => (Mode::RBase, Mode::None, Mode::None),
+			Self::JFuncF => (Mode::RBase, Mode::None, Mode::Lit),
+			Self::FuncV => (Mode::RBase, Mode::None, Mode::None),
+			Self::IFuncV => (Mode::RBase, Mode::None, Mode::None),
+			Self::JFuncV => (Mode::RBase, Mode::None, Mode::Lit),
+			Self::FuncC => (Mode::RBase, Mode::None, Mode::None),
+			Self::FuncCW => (Mode::RBase, Mode::None, Mode::None)
+		}
+	}
+
+	pub fn has_d(self) -> bool {
+		self.modes().1 == Mode::None
+	}
+}
+
+// a typed view of one operand
+#[derive(Debug, Clone, Copy)]
+pub enum Operand {
+	Reg(Reg),
+	Upvalue(Upvalue),
+	Lit(u16),
+	LitS(i16),
+	Pri(u16), // 0 = nil, 1 = false, 2 = true
+	Num(Kst), // into Proto::knum
+	Gc(Kst), // into Proto::kgc
+	Jump(i32) // relative to the next instruction
+}
+
+impl Operand {
+	fn from_mode(mode: Mode, v: u16) -> Option<Self> {
+		Some(match mode {
+			Mode::None => return None,
+			Mode::Dst | Mode::Base | Mode::Var | Mode::RBase => Self::Reg(Reg(v as u8)),
+			Mode::Uv => Self::Upvalue(v),
+			Mode::Lit => Self::Lit(v),
+			Mode::LitS => Self::LitS(v as i16),
+			Mode::Pri => Self::Pri(v),
+			Mode::Num => Self::Num(Kst(v as u32)),
+			Mode::Str | Mode::Tab | Mode::Func | Mode::CData => Self::Gc(Kst(v as u32)),
+			Mode::Jump => Self::Jump(v as i32 - BCBIAS_J)
+		})
+	}
+
+	fn get(&self) -> u16 {
+		match *self {
+			Self::Reg(r) => r.0 as u16,
+			Self::Upvalue(v) | Self::Lit(v) | Self::Pri(v) => v,
+			Self::LitS(v) => v as u16,
+			Self::Num(k) | Self::Gc(k) => k.0 as u16,
+			Self::Jump(j) => (j + BCBIAS_J) as u16
+		}
+	}
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy)]
+pub enum Opmode {
+	ABC(u8, u8, u8),
+	AD(u8, u16)
+}
+
+// unique id
+fn get_id() -> usize {
+	COUNTER.fetch_add(1, Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone)]
+pub struct Instruction(pub Opcode, pub Opmode, pub usize);
+impl Instruction {
+	pub fn new(op: Opcode, mode: Opmode) -> Self {
+		Self(op, mode, get_id())
+	}
+
+	pub fn try_from_instr(instr: u32, version: u8) -> Result<Self, DecodeErrorKind> {
+		let op = Opcode::try_from_byte((instr & 0xff) as u8, version)?;
+		let a = ((instr >> 8) & 0xff) as u8;
+		let mode = if op.has_d() {
+			Opmode::AD(a, (instr >> 16) as u16)
+		} else {
+			Opmode::ABC(a, (instr >> 24) as u8, ((instr >> 16) & 0xff) as u8)
+		};
+		Ok(Self::new(op, mode))
+	}
+
+	pub fn from_instr(instr: u32, version: u8) -> Self {
+		Self::try_from_instr(instr, version).expect("invalid instruction")
+	}
+
+	pub fn serialize(&self, version: u8) -> u32 {
+		let op = self.0.to_byte(version) as u32;
+		match self.1 {
+			Opmode::ABC(a, b, c) => op | (a as u32) << 8 | (c as u32) << 16 | (b as u32) << 24,
+			Opmode::AD(a, d) => op | (a as u32) << 8 | (d as u32) << 16
+		}
+	}
+
+	pub fn a(&self) -> Option<Operand> {
+		let a = match self.1 {
+			Opmode::ABC(a, ..) | Opmode::AD(a, _) => a
+		};
+		Operand::from_mode(self.0.modes().0, a as u16)
+	}
+
+	pub fn b(&self) -> Option<Operand> {
+		match self.1 {
+			Opmode::ABC(_, b, _) => Operand::from_mode(self.0.modes().1, b as u16),
+			Opmode::AD(..) => None
+		}
+	}
+
+	pub fn c(&self) -> Option<Operand> {
+		match self.1 {
+			Opmode::ABC(_, _, c) => Operand::from_mode(self.0.modes().2, c as u16),
+			Opmode::AD(..) => None
+		}
+	}
+
+	pub fn d(&self) -> Option<Operand> {
+		match self.1 {
+			Opmode::AD(_, d) => Operand::from_mode(self.0.modes().2, d),
+			Opmode::ABC(..) => None
+		}
+	}
+
+	// replaces an operand, keeping the instruction's format
+	pub fn set_a(&mut self, v: Operand) {
+		match &mut self.1 {
+			Opmode::ABC(a, ..) | Opmode::AD(a, _) => *a = v.get() as u8
+		}
+	}
+
+	pub fn set_b(&mut self, v: Operand) {
+		if let Opmode::ABC(_, b, _) = &mut self.1 {
+			*b = v.get() as u8;
+		}
+	}
+
+	pub fn set_c(&mut self, v: Operand) {
+		if let Opmode::ABC(_, _, c) = &mut self.1 {
+			*c = v.get() as u8;
+		}
+	}
+
+	pub fn set_d(&mut self, v: Operand) {
+		if let Opmode::AD(_, d) = &mut self.1 {
+			*d = v.get();
+		}
+	}
+}
@@ -0,0 +1,125 @@
+mod deserialize;
+pub mod instruction;
+mod serialize;
+
+use crate::{Endianness, LuaString};
+
+pub use crate::lua51::{Reader, Writer};
+pub use deserialize::{deserialize_bytecode, try_deserialize_bytecode, deserialize_from};
+pub use serialize::{serialize_bytecode, serialize_to};
+
+// Proto::flags
+pub const PROTO_CHILD: u8 = 0x01;
+pub const PROTO_VARARG: u8 = 0x02;
+pub const PROTO_FFI: u8 = 0x04;
+
+// UpvalueDesc bits as they are dumped
+pub const PROTO_UV_LOCAL: u16 = 0x8000;
+pub const PROTO_UV_IMMUTABLE: u16 = 0x4000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpvalueDesc {
+	pub in_stack: bool, // slot of the enclosing function, otherwise one of its upvalues
+	pub immutable: bool,
+	pub index: u16
+}
+
+// keys and values of template tables
+#[derive(Debug, Clone, PartialEq)]
+pub enum TableConstant {
+	Nil,
+	Boolean(bool),
+	Int(i32),
+	Num(f64),
+	String(LuaString)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GcConstant {
+	Child, // the next entry of Proto::prototypes
+	Table { array: Vec<TableConstant>, hash: Vec<(TableConstant, TableConstant)> },
+	I64(i64),
+	U64(u64),
+	Complex(f64, f64),
+	String(LuaString)
+}
+
+// numbers that fit an int32 are dumped narrowed
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NumConstant {
+	Int(i32),
+	Num(f64)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VarName {
+	Internal(u8), // one of the (for ...) slots, 1 to 6
+	Named(LuaString)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VarInfo {
+	pub name: VarName,
+	pub start_pc: u32,
+	pub end_pc: u32
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugInfo {
+	pub first_line: u32,
+	pub num_line: u32,
+	pub line_info: Vec<u32>, // per instruction, relative to first_line
+	pub upvalue_names: Vec<LuaString>,
+	pub vars: Vec<VarInfo>
+}
+
+#[derive(Debug, Clone)]
+pub struct Proto {
+	pub flags: u8,
+	pub nparams: u8,
+	pub framesize: u8,
+	pub instructions: Vec<instruction::Instruction>, // without the FUNCF/FUNCV header the loader adds
+	pub upvalues: Vec<UpvalueDesc>,
+	pub kgc: Vec<GcConstant>, // in the order D operands index them
+	pub knum: Vec<NumConstant>,
+	pub prototypes: Vec<Self>, // one per GcConstant::Child, in kgc order
+	pub debug: Option<DebugInfo>
+}
+
+impl Default for Proto {
+	fn default() -> Self {
+		Self {
+			flags: PROTO_VARARG,
+			nparams: 0,
+			framesize: 2,
+			instructions: vec![],
+			upvalues: vec![],
+			kgc: vec![],
+			knum: vec![],
+			prototypes: vec![],
+			debug: None
+		}
+	}
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Header {
+	pub version: u8, // 1 for 2.0, 2 for 2.1
+	pub endianness: Endianness,
+	pub ffi: bool,
+	pub fr2: bool, // two slot frames, set by GC64 builds
+	pub chunk_name: Option<LuaString> // None when stripped, which also drops every proto's debug info
+}
+
+impl Default for Header {
+	// what `luajit -b` on x64 emits
+	fn default() -> Self {
+		Self {
+			version: 2,
+			endianness: Endianness::Little,
+			ffi: false,
+			fr2: true,
+			chunk_name: None
+		}
+	}
+}
@@ -0,0 +1,184 @@
+use crate::Endianness;
+use super::{
+	Writer, Header, Proto, GcConstant, TableConstant, NumConstant, DebugInfo, VarName,
+	PROTO_UV_LOCAL, PROTO_UV_IMMUTABLE
+};
+use super::deserialize::{BCDUMP_F_BE, BCDUMP_F_STRIP, BCDUMP_F_FFI, BCDUMP_F_FR2};
+
+fn uleb128(writer: &mut Writer, v: u64) {
+	let mut v = v;
+	while v >= 0x80 {
+		writer.byte((v & 0x7f) as u8 | 0x80);
+		v >>= 7;
+	}
+	writer.byte(v as u8);
+}
+
+fn dump_header(writer: &mut Writer, header: &Header) {
+	writer.bytes(b"\x1BLJ".to_vec());
+	writer.byte(header.version);
+
+	let mut flags = 0;
+	if header.endianness == Endianness::Big { flags |= BCDUMP_F_BE }
+	if header.chunk_name.is_none() { flags |= BCDUMP_F_STRIP }
+	if header.ffi { flags |= BCDUMP_F_FFI }
+	if header.fr2 { flags |= BCDUMP_F_FR2 }
+	uleb128(writer, flags.into());
+
+	if let Some(name) = &header.chunk_name {
+		uleb128(writer, name.len() as u64);
+		writer.bytes(name.as_bytes().to_vec());
+	}
+}
+
+fn dump_table_constant(writer: &mut Writer, kst: &TableConstant) {
+	match kst {
+		TableConstant::Nil => uleb128(writer, 0),
+		TableConstant::Boolean(b) => uleb128(writer, 1 + *b as u64),
+		&TableConstant::Int(n) => {uleb128(writer, 3); uleb128(writer, n as u32 as u64)},
+		TableConstant::Num(n) => {
+			uleb128(writer, 4);
+			uleb128(writer, n.to_bits() & 0xffffffff);
+			uleb128(writer, n.to_bits() >> 32);
+		}
+		TableConstant::String(s) => {
+			uleb128(writer, 5 + s.len() as u64);
+			writer.bytes(s.as_bytes().to_vec());
+		}
+	}
+}
+
+fn dump_gc_constant(writer: &mut Writer, kst: &GcConstant) {
+	let u64 = |writer: &mut Writer, v: u64| {
+		uleb128(writer, v & 0xffffffff);
+		uleb128(writer, v >> 32);
+	};
+	match kst {
+		GcConstant::Child => uleb128(writer, 0),
+		GcConstant::Table { array, hash } => {
+			uleb128(writer, 1);
+			uleb128(writer, array.len() as u64);
+			uleb128(writer, hash.len() as u64);
+			array.iter().for_each(|v| dump_table_constant(writer, v));
+			hash.iter().for_each(|(k, v)| {
+				dump_table_constant(writer, k);
+				dump_table_constant(writer, v);
+			});
+		}
+		&GcConstant::I64(n) => {uleb128(writer, 2); u64(writer, n as u64)},
+		&GcConstant::U64(n) => {uleb128(writer, 3); u64(writer, n)},
+		GcConstant::Complex(re, im) => {
+			uleb128(writer, 4);
+			u64(writer, re.to_bits());
+			u64(writer, im.to_bits());
+		}
+		GcConstant::String(s) => {
+			uleb128(writer, 5 + s.len() as u64);
+			writer.bytes(s.as_bytes().to_vec());
+		}
+	}
+}
+
+fn dump_num_constant(writer: &mut Writer, kst: &NumConstant) {
+	// 33 bit uleb128, the lowest bit marks a full number
+	match *kst {
+		NumConstant::Int(n) => uleb128(writer, (n as u32 as u64) << 1),
+		NumConstant::Num(n) => {
+			let bits = n.to_bits();
+			uleb128(writer, (bits & 0xffffffff) << 1 | 1);
+			uleb128(writer, bits >> 32);
+		}
+	}
+}
+
+fn dump_debug_info(writer: &mut Writer, debug: &DebugInfo) {
+	let width = match debug.num_line {
+		0..=0xff => 1,
+		0x100..=0xffff => 2,
+		_ => 4
+	};
+	debug.line_info.iter().for_each(|line| writer.int(*line, width));
+
+	for name in &debug.upvalue_names {
+		writer.bytes(name.as_bytes().to_vec());
+		writer.byte(0);
+	}
+
+	let mut last_pc = 0;
+	for var in &debug.vars {
+		match &var.name {
+			VarName::Internal(t) => writer.byte(*t),
+			VarName::Named(name) => {
+				writer.bytes(name.as_bytes().to_vec());
+				writer.byte(0);
+			}
+		}
+		uleb128(writer, (var.start_pc - last_pc).into());
+		uleb128(writer, (var.end_pc - var.start_pc).into());
+		last_pc = var.start_pc;
+	}
+	writer.byte(0);
+}
+
+fn dump_proto(writer: &mut Writer, header: &Header, proto: &Proto) {
+	// children go first so the loader can pick them up from its stack
+	proto.prototypes.iter().for_each(|child| dump_proto(writer, header, child));
+
+	let mut body = Writer::new();
+	body.set_endianness(header.endianness);
+	body.byte(proto.flags);
+	body.byte(proto.nparams);
+	body.byte(proto.framesize);
+	body.byte(proto.upvalues.len() as u8);
+	uleb128(&mut body, proto.kgc.len() as u64);
+	uleb128(&mut body, proto.knum.len() as u64);
+	uleb128(&mut body, proto.instructions.len() as u64);
+
+	let mut debug = Writer::new();
+	debug.set_endianness(header.endianness);
+	if header.chunk_name.is_some() {
+		match &proto.debug {
+			Some(info) => {
+				dump_debug_info(&mut debug, info);
+				uleb128(&mut body, debug.as_bytes().len() as u64);
+				uleb128(&mut body, info.first_line.into());
+				uleb128(&mut body, info.num_line.into());
+			}
+			None => uleb128(&mut body, 0)
+		}
+	}
+
+	proto.instructions.iter().for_each(|instr| body.int(instr.serialize(header.version), 4));
+	for uv in &proto.upvalues {
+		let mut v = uv.index;
+		if uv.in_stack { v |= PROTO_UV_LOCAL }
+		if uv.immutable { v |= PROTO_UV_IMMUTABLE }
+		body.uint(v.into(), 2);
+	}
+
+	proto.kgc.iter().rev().for_each(|kst| dump_gc_constant(&mut body, kst));
+	proto.knum.iter().for_each(|kst| dump_num_constant(&mut body, kst));
+	body.bytes(debug.as_bytes().to_vec());
+
+	uleb128(writer, body.as_bytes().len() as u64);
+	writer.bytes(body.as_bytes().to_vec());
+}
+
+pub fn serialize_bytecode(header: &Header, proto: &Proto) -> Vec<u8> {
+	let mut writer = Writer::new();
+	serialize_to(&mut writer, header, proto);
+	writer.as_bytes().into()
+}
+
+// appends one chunk to the writer, returning how many bytes it took up. errors from a
+// streaming writer only show up once it is flushed
+pub fn serialize_to(writer: &mut Writer, header: &Header, proto: &Proto) -> usize {
+	let start = writer.position();
+	writer.set_endianness(header.endianness);
+
+	dump_header(writer, header);
+	dump_proto(writer, header, proto);
+	writer.byte(0);
+
+	writer.position() - start
+}
@@ -0,0 +1,337 @@
+use crate::{Bytecode, DecodeError, DecodeErrorKind, Endianness, Field, LuaString};
+use super::{
+	Reader, Header, Chunk, Proto, Constants, TypeInfo, TypedLocal, LineInfo, LocVar, DebugInfo, UserdataType, StringRef,
+	instruction::Instruction
+};
+
+pub(crate) const LBC_VERSION_MIN: u8 = 3;
+pub(crate) const LBC_VERSION_MAX: u8 = 6;
+pub(crate) const LBC_TYPE_VERSION_MIN: u8 = 1;
+pub(crate) const LBC_TYPE_VERSION_MAX: u8 = 3;
+
+pub(crate) const LBC_CONSTANT_NIL: u8 = 0;
+pub(crate) const LBC_CONSTANT_BOOLEAN: u8 = 1;
+pub(crate) const LBC_CONSTANT_NUMBER: u8 = 2;
+pub(crate) const LBC_CONSTANT_STRING: u8 = 3;
+pub(crate) const LBC_CONSTANT_IMPORT: u8 = 4;
+pub(crate) const LBC_CONSTANT_TABLE: u8 = 5;
+pub(crate) const LBC_CONSTANT_CLOSURE: u8 = 6;
+pub(crate) const LBC_CONSTANT_VECTOR: u8 = 7;
+
+// 7 bits per byte, least significant first
+fn varint(reader: &mut Reader) -> Result<u32, DecodeError> {
+	let start = reader.position();
+	let mut result: u32 = 0;
+	let mut shift = 0;
+	loop {
+		let b = reader.byte()?;
+		if shift > 28 || (shift == 28 && b & 0x70 != 0) {
+			return Err(reader.error_at(start, DecodeErrorKind::IntegerOverflow));
+		}
+		result |= ((b & 0x7f) as u32) << shift;
+		shift += 7;
+		if b & 0x80 == 0 {
+			return Ok(result);
+		}
+	}
+}
+
+fn string_ref(reader: &mut Reader, strings: &[LuaString]) -> Result<StringRef, DecodeError> {
+	let start = reader.position();
+	let s = varint(reader)?;
+	if s as usize > strings.len() {
+		return Err(reader.error_at(start, DecodeErrorKind::InvalidOperand));
+	}
+	Ok(s)
+}
+
+// an index of a proto that was already read
+fn proto_ref(reader: &mut Reader, loaded: usize) -> Result<u32, DecodeError> {
+	let start = reader.position();
+	let id = varint(reader)?;
+	if id as usize >= loaded {
+		return Err(reader.error_at(start, DecodeErrorKind::InvalidOperand));
+	}
+	Ok(id)
+}
+
+fn header(reader: &mut Reader) -> Result<Header, DecodeError> {
+	reader.set_field(Field::Header);
+
+	let at = reader.position();
+	let version = reader.byte()?;
+	if version == 0 {
+		// the rest is the error message
+		let msg = reader.rest()?;
+		return Err(reader.error_at(at, DecodeErrorKind::CompileError(String::from_utf8_lossy(&msg).into_owned())));
+	}
+	if !(LBC_VERSION_MIN..=LBC_VERSION_MAX).contains(&version) {
+		return Err(reader.error_at(at, DecodeErrorKind::UnsupportedVersion(version)));
+	}
+
+	let mut types_version = 0;
+	if version >= 4 {
+		let at = reader.position();
+		types_version = reader.byte()?;
+		if !(LBC_TYPE_VERSION_MIN..=LBC_TYPE_VERSION_MAX).contains(&types_version) {
+			return Err(reader.error_at(at, DecodeErrorKind::UnsupportedFormat(types_version)));
+		}
+	}
+
+	Ok(Header { version, types_version })
+}
+
+fn type_info(reader: &mut Reader, header: &Header) -> Result<Option<TypeInfo>, DecodeError> {
+	reader.set_field(Field::TypeInfo);
+	let size = varint(reader)? as usize;
+	if size == 0 {
+		return Ok(None);
+	}
+
+	if header.types_version == 1 {
+		let function = reader.bytes(size)?.to_vec();
+		return Ok(Some(TypeInfo { function, ..TypeInfo::default() }));
+	}
+
+	let start = reader.position();
+	let function_size = varint(reader)? as usize;
+	let upvalue_count = varint(reader)? as usize;
+	let local_count = varint(reader)?;
+
+	let function = reader.bytes(function_size)?.to_vec();
+	let upvalues = reader.bytes(upvalue_count)?.to_vec();
+	let mut locals = vec![];
+	for _ in 0..local_count {
+		let ty = reader.byte()?;
+		let reg = reader.byte()?;
+		let start_pc = varint(reader)?;
+		let end_pc = start_pc.wrapping_add(varint(reader)?);
+		locals.push(TypedLocal { ty, reg, start_pc, end_pc });
+	}
+
+	if reader.position() - start != size {
+		return Err(reader.error_at(start, DecodeErrorKind::BadProtoLength));
+	}
+	Ok(Some(TypeInfo { function, upvalues, locals }))
+}
+
+fn constant(reader: &mut Reader, strings: &[LuaString], loaded: usize, i: usize) -> Result<Constants, DecodeError> {
+	reader.set_field(Field::ConstantTag(i));
+	let at = reader.position();
+	let tag = reader.byte()?;
+
+	reader.set_field(Field::Constant(i));
+	Ok(match tag {
+		LBC_CONSTANT_NIL => Constants::Nil,
+		LBC_CONSTANT_BOOLEAN => Constants::Boolean(reader.byte()? != 0),
+		LBC_CONSTANT_NUMBER => Constants::Number(f64::from_bits(reader.uint(8)?)),
+		LBC_CONSTANT_STRING => Constants::String(string_ref(reader, strings)?),
+		LBC_CONSTANT_IMPORT => Constants::Import(reader.int(4)?),
+		LBC_CONSTANT_TABLE => {
+			let count = varint(reader)?;
+			let mut keys = vec![];
+			for _ in 0..count {
+				keys.push(varint(reader)?);
+			}
+			Constants::Table(keys)
+		},
+		LBC_CONSTANT_CLOSURE => Constants::Closure(proto_ref(reader, loaded)?),
+		LBC_CONSTANT_VECTOR => {
+			let mut v = [0.0; 4];
+			for c in v.iter_mut() {
+				*c = f32::from_bits(reader.int(4)?);
+			}
+			Constants::Vector(v[0], v[1], v[2], v[3])
+		},
+		tag => return Err(reader.error_at(at, DecodeErrorKind::UnknownConstantTag(tag)))
+	})
+}
+
+fn line_info(reader: &mut Reader, code_size: usize) -> Result<LineInfo, DecodeError> {
+	reader.set_field(Field::SourceLineCount);
+	let gap_log2 = reader.byte()?;
+	let intervals = match code_size {
+		0 => 0,
+		n => (n - 1).checked_shr(gap_log2 as u32).unwrap_or(0) + 1
+	};
+
+	// both tables are delta encoded
+	let mut line_offsets = vec![];
+	let mut last_offset: u8 = 0;
+	for i in 0..code_size {
+		reader.set_field(Field::SourceLine(i));
+		last_offset = last_offset.wrapping_add(reader.byte()?);
+		line_offsets.push(last_offset);
+	}
+
+	let mut abs_line_info = vec![];
+	let mut last_line: i32 = 0;
+	for i in 0..intervals {
+		reader.set_field(Field::AbsLineInfo(i));
+		last_line = last_line.wrapping_add(reader.int(4)? as i32);
+		abs_line_info.push(last_line);
+	}
+
+	Ok(LineInfo { gap_log2, line_offsets, abs_line_info })
+}
+
+fn debug_info(reader: &mut Reader, strings: &[LuaString]) -> Result<DebugInfo, DecodeError> {
+	reader.set_field(Field::LocalCount);
+	let local_count = varint(reader)?;
+	let mut locals = vec![];
+	for i in 0..local_count as usize {
+		reader.set_field(Field::Local(i));
+		let name = string_ref(reader, strings)?;
+		let start_pc = varint(reader)?;
+		let end_pc = varint(reader)?;
+		let reg = reader.byte()?;
+		locals.push(LocVar { name, start_pc, end_pc, reg });
+	}
+
+	reader.set_field(Field::UpvalueCount);
+	let upvalue_count = varint(reader)?;
+	let mut upvalues = vec![];
+	for i in 0..upvalue_count as usize {
+		reader.set_field(Field::Upvalue(i));
+		upvalues.push(string_ref(reader, strings)?);
+	}
+
+	Ok(DebugInfo { locals, upvalues })
+}
+
+fn proto(reader: &mut Reader, header: &Header, strings: &[LuaString], loaded: usize) -> Result<Proto, DecodeError> {
+	reader.set_field(Field::ProtoInfo);
+	let max_stack_size = reader.byte()?;
+	let nparams = reader.byte()?;
+	let nupvals = reader.byte()?;
+	let is_vararg = reader.byte()?;
+
+	let (flags, type_info) = if header.version >= 4 {
+		(reader.byte()?, type_info(reader, header)?)
+	} else {
+		(0, None)
+	};
+
+	// instructions, AUX words are folded into the instruction before them
+	reader.set_field(Field::InstructionCount);
+	let code_size = varint(reader)? as usize;
+	let code_start = reader.position();
+	let mut code = vec![];
+	for i in 0..code_size {
+		reader.set_field(Field::Instruction(i));
+		code.push(reader.int(4)?);
+	}
+	let mut instructions = vec![];
+	let mut pc = 0;
+	while pc < code.len() {
+		let instruction = Instruction::try_from_instr(code[pc], code.get(pc + 1)).map_err(|kind| {
+			reader.set_field(Field::Instruction(pc));
+			reader.error_at(code_start + pc * 4, kind)
+		})?;
+		pc += instruction.size();
+		instructions.push(instruction);
+	}
+
+	// constants
+	reader.set_field(Field::ConstantCount);
+	let constant_count = varint(reader)?;
+	let mut constants = vec![];
+	for i in 0..constant_count as usize {
+		constants.push(constant(reader, strings, loaded, i)?);
+	}
+
+	// child protos
+	reader.set_field(Field::ProtoCount);
+	let proto_count = varint(reader)?;
+	let mut prototypes = vec![];
+	for _ in 0..proto_count {
+		prototypes.push(proto_ref(reader, loaded)?);
+	}
+
+	reader.set_field(Field::LineDefined);
+	let line_defined = varint(reader)?;
+	reader.set_field(Field::Source);
+	let debug_name = string_ref(reader, strings)?;
+
+	reader.set_field(Field::SourceLineCount);
+	let line_info = match reader.byte()? {
+		0 => None,
+		_ => Some(line_info(reader, code_size)?)
+	};
+
+	reader.set_field(Field::LocalCount);
+	let debug = match reader.byte()? {
+		0 => None,
+		_ => Some(debug_info(reader, strings)?)
+	};
+
+	Ok(Proto {
+		max_stack_size,
+		nparams,
+		nupvals,
+		is_vararg,
+		flags,
+		type_info,
+		instructions,
+		constants,
+		prototypes,
+		line_defined,
+		debug_name,
+		line_info,
+		debug
+	})
+}
+
+pub fn try_deserialize_bytecode(bytecode: &Bytecode) -> Result<(Header, Chunk), DecodeError> {
+	let (header_data, chunk, _) = deserialize_from(&mut Reader::new(bytecode))?;
+	Ok((header_data, chunk))
+}
+
+// reads one chunk from wherever the reader is, also returning how many bytes it took up
+pub fn deserialize_from(reader: &mut Reader) -> Result<(Header, Chunk, usize), DecodeError> {
+	let start = reader.position();
+	reader.set_endianness(Endianness::Little);
+	let header_data = header(reader)?;
+
+	// string table
+	reader.set_field(Field::ConstantCount);
+	let string_count = varint(reader)?;
+	let mut strings = vec![];
+	for i in 0..string_count as usize {
+		reader.set_field(Field::String(i));
+		let len = varint(reader)? as usize;
+		strings.push(LuaString(reader.bytes(len)?.to_vec()));
+	}
+
+	// userdata type names, terminated by a 0 index
+	let mut userdata_types = vec![];
+	if header_data.types_version == 3 {
+		reader.set_field(Field::TypeInfo);
+		loop {
+			let index = reader.byte()?;
+			if index == 0 {
+				break;
+			}
+			userdata_types.push(UserdataType { index, name: string_ref(reader, &strings)? });
+		}
+	}
+
+	// protos, children always come before the protos referring to them
+	reader.set_field(Field::ProtoCount);
+	let proto_count = varint(reader)?;
+	let mut protos = vec![];
+	for i in 0..proto_count as usize {
+		reader.enter_proto(i);
+		protos.push(proto(reader, &header_data, &strings, i)?);
+		reader.leave_proto();
+	}
+
+	reader.set_field(Field::MainProto);
+	let main = proto_ref(reader, protos.len())?;
+
+	Ok((header_data, Chunk { strings, userdata_types, protos, main }, reader.position() - start))
+}
+
+pub fn deserialize_bytecode(bytecode: &Bytecode) -> (Header, Chunk) {
+	try_deserialize_bytecode(bytecode).unwrap_or_else(|err| panic!("{}", err))
+}
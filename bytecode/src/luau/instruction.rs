@@ -0,0 +1,597 @@
+use num_derive::FromPrimitive;
+use num_traits::FromPrimitive;
+
+use crate::DecodeErrorKind;
+pub use crate::lua51::instruction::{Reg, Kst, Upvalue};
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+static COUNTER: AtomicUsize = AtomicUsize::new(1);
+
+#[derive(FromPrimitive, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Opcode {
+	Nop = 0,
+	Break,
+	LoadNil,
+	LoadB,
+	LoadN,
+	LoadK,
+	Move,
+	GetGlobal,
+	SetGlobal,
+	GetUpval,
+	SetUpval,
+	CloseUpvals,
+	GetImport,
+	GetTable,
+	SetTable,
+	GetTableKs,
+	SetTableKs,
+	GetTableN,
+	SetTableN,
+	NewClosure,
+	NameCall,
+	Call,
+	Return,
+	Jump,
+	JumpBack,
+	JumpIf,
+	JumpIfNot,
+	JumpIfEq,
+	JumpIfLe,
+	JumpIfLt,
+	JumpIfNotEq,
+	JumpIfNotLe,
+	JumpIfNotLt,
+	Add,
+	Sub,
+	Mul,
+	Div,
+	Mod,
+	Pow,
+	AddK,
+	SubK,
+	MulK,
+	DivK,
+	ModK,
+	PowK,
+	And,
+	Or,
+	AndK,
+	OrK,
+	Concat,
+	Not,
+	Minus,
+	Length,
+	NewTable,
+	DupTable,
+	SetList,
+	ForNPrep,
+	ForNLoop,
+	ForGLoop,
+	ForGPrepINext,
+	FastCall3,
+	ForGPrepNext,
+	NativeCall,
+	GetVarArgs,
+	DupClosure,
+	PrepVarArgs,
+	LoadKx,
+	JumpX,
+	FastCall,
+	Coverage,
+	Capture,
+	SubRK,
+	DivRK,
+	FastCall1,
+	FastCall2,
+	FastCall2K,
+	ForGPrep,
+	JumpXEqKNil,
+	JumpXEqKB,
+	JumpXEqKN,
+	JumpXEqKS,
+	IDiv,
+	IDivK
+}
+
+impl Opcode {
+	fn try_from_instr(instr: u32) -> Result<Self, DecodeErrorKind> {
+		Self::from_u8((instr & 0xff) as u8).ok_or(DecodeErrorKind::InvalidOpcode((instr & 0xff) as u8))
+	}
+
+	fn to_instr(self) -> u32 {
+		self as u32
+	}
+
+	// whether the instruction is followed by an AUX word
+	pub fn has_aux(self) -> bool {
+		matches!(self,
+			Self::GetGlobal | Self::SetGlobal | Self::GetImport | Self::GetTableKs | Self::SetTableKs | Self::NameCall
+			| Self::JumpIfEq | Self::JumpIfLe | Self::JumpIfLt | Self::JumpIfNotEq | Self::JumpIfNotLe | Self::JumpIfNotLt
+			| Self::NewTable | Self::SetList | Self::ForGLoop | Self::LoadKx | Self::FastCall2 | Self::FastCall2K | Self::FastCall3
+			| Self::JumpXEqKNil | Self::JumpXEqKB | Self::JumpXEqKN | Self::JumpXEqKS)
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinOp {
+	Add, Sub, Mul, Div, Mod, Pow, IDiv
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnOp {
+	Not, Minus, Length
+}
+
+// comparisons of JUMPIFEQ and friends, the second register is in AUX
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CondOp {
+	Eq, Le, Lt, NotEq, NotLe, NotLt
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CaptureType {
+	Val = 0,
+	Ref,
+	Upval
+}
+
+impl CaptureType {
+	fn try_from_byte(b: u8) -> Result<Self, DecodeErrorKind> {
+		match b {
+			0 => Ok(Self::Val),
+			1 => Ok(Self::Ref),
+			2 => Ok(Self::Upval),
+			_ => Err(DecodeErrorKind::InvalidOperand)
+		}
+	}
+}
+
+// set in AUX of JUMPXEQK* to flip the comparison
+pub const AUX_NOT: u32 = 1 << 31;
+
+// opcode in the low byte, then A, B and C
+struct Abc(u8, u8, u8);
+impl Abc {
+	fn reg_a(&self) -> Reg { Reg(self.0) }
+	fn reg_b(&self) -> Reg { Reg(self.1) }
+	fn reg_c(&self) -> Reg { Reg(self.2) }
+
+	fn kst_b(&self) -> Kst { Kst(self.1 as u32) }
+	fn kst_c(&self) -> Kst { Kst(self.2 as u32) }
+}
+
+#[allow(non_snake_case)]
+fn ABC(instr: u32) -> Abc {
+	Abc (
+		((instr >> 8) & 0xff) as u8,
+		((instr >> 16) & 0xff) as u8,
+		((instr >> 24) & 0xff) as u8
+	)
+}
+
+#[allow(non_snake_case)]
+fn D(instr: u32) -> i16 {
+	(instr >> 16) as i16
+}
+
+#[allow(non_snake_case)]
+fn E(instr: u32) -> i32 {
+	instr as i32 >> 8
+}
+
+// jump offsets count words from the next instruction, AUX words included
+#[derive(Debug, Clone)]
+pub enum Instr {
+	Nop,
+	Break,
+	LoadNil(Reg),
+	LoadB(Reg, bool, u8),
+	LoadN(Reg, i16),
+	LoadK(Reg, Kst),
+	Move(Reg, Reg),
+	GetGlobal(Reg, u8, Kst), // C is a predicted hash slot
+	SetGlobal(Reg, u8, Kst),
+	GetUpval(Reg, Upvalue),
+	SetUpval(Reg, Upvalue),
+	CloseUpvals(Reg),
+	GetImport(Reg, Kst, u32), // AUX is the import path, see super::import_path
+	GetTable(Reg, Reg, Reg),
+	SetTable(Reg, Reg, Reg),
+	GetTableKs(Reg, Reg, u8, Kst),
+	SetTableKs(Reg, Reg, u8, Kst),
+	GetTableN(Reg, Reg, u8), // index - 1
+	SetTableN(Reg, Reg, u8),
+	NewClosure(Reg, u16), // index into Proto::prototypes
+	NameCall(Reg, Reg, u8, Kst),
+	Call(Reg, u8, u8),
+	Return(Reg, u8),
+	Jump(i16),
+	JumpBack(i16),
+	JumpIf(Reg, i16),
+	JumpIfNot(Reg, i16),
+	JumpIfCond(Reg, CondOp, Reg, i16),
+	BinOp(Reg, Reg, BinOp, Reg),
+	BinOpK(Reg, Reg, BinOp, Kst),
+	BinOpRK(Reg, Kst, BinOp, Reg), // only Sub and Div
+	And(Reg, Reg, Reg),
+	Or(Reg, Reg, Reg),
+	AndK(Reg, Reg, Kst),
+	OrK(Reg, Reg, Kst),
+	Concat(Reg, Reg, Reg),
+	UnOp(Reg, UnOp, Reg),
+	NewTable(Reg, u8, u32), // B is the encoded hash size, AUX the array size
+	DupTable(Reg, Kst),
+	SetList(Reg, Reg, u8, u32), // AUX is the first table index
+	ForNPrep(Reg, i16),
+	ForNLoop(Reg, i16),
+	ForGLoop(Reg, i16, u32), // AUX holds the variable count and the ipairs bit
+	ForGPrepINext(Reg, i16),
+	ForGPrepNext(Reg, i16),
+	ForGPrep(Reg, i16),
+	NativeCall,
+	GetVarArgs(Reg, u8),
+	DupClosure(Reg, Kst),
+	PrepVarArgs(u8),
+	LoadKx(Reg, Kst),
+	JumpX(i32),
+	Coverage(i32),
+	Capture(CaptureType, u8), // register, or upvalue index for Upval
+	// A is the builtin id and C the jump to the following CALL
+	FastCall(u8, u8),
+	FastCall1(u8, Reg, u8),
+	FastCall2(u8, Reg, Reg, u8),
+	FastCall2K(u8, Reg, Kst, u8),
+	FastCall3(u8, Reg, Reg, Reg, u8),
+	JumpXEqKNil(Reg, i16, bool), // the flag is the NOT bit
+	JumpXEqKB(Reg, i16, bool, bool),
+	JumpXEqKN(Reg, i16, Kst, bool),
+	JumpXEqKS(Reg, i16, Kst, bool)
+}
+
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy)]
+pub enum Opmode {
+	ABC(u8, u8, u8),
+	AD(u8, i16),
+	E(i32)
+}
+
+impl Instr {
+	pub fn from_instr(instr: u32, aux: Option<&u32>) -> Self {
+		Self::try_from_instr(instr, aux).expect("invalid instruction")
+	}
+
+	pub fn try_from_instr(instr: u32, aux: Option<&u32>) -> Result<Self, DecodeErrorKind> {
+		let op = Opcode::try_from_instr(instr)?;
+		let abc = ABC(instr);
+		let d = D(instr);
+		let aux = match (op.has_aux(), aux) {
+			(true, Some(aux)) => *aux,
+			(true, None) => return Err(DecodeErrorKind::MissingAuxWord),
+			(false, _) => 0
+		};
+		let aux_kst = Kst(aux & 0xffffff);
+		let aux_not = aux & AUX_NOT != 0;
+
+		let bin = |op| Self::BinOp(abc.reg_a(), abc.reg_b(), op, abc.reg_c());
+		let bin_k = |op| Self::BinOpK(abc.reg_a(), abc.reg_b(), op, abc.kst_c());
+		let cond = |op| Self::JumpIfCond(abc.reg_a(), op, Reg(aux as u8), d);
+
+		Ok(match op {
+			Opcode::Nop => Self::Nop,
+			Opcode::Break => Self::Break,
+			Opcode::LoadNil => Self::LoadNil(abc.reg_a()),
+			Opcode::LoadB => Self::LoadB(abc.reg_a(), abc.1 != 0, abc.2),
+			Opcode::LoadN => Self::LoadN(abc.reg_a(), d),
+			Opcode::LoadK => Self::LoadK(abc.reg_a(), Kst(d as u16 as u32)),
+			Opcode::Move => Self::Move(abc.reg_a(), abc.reg_b()),
+			Opcode::GetGlobal => Self::GetGlobal(abc.reg_a(), abc.2, Kst(aux)),
+			Opcode::SetGlobal => Self::SetGlobal(abc.reg_a(), abc.2, Kst(aux)),
+			Opcode::GetUpval => Self::GetUpval(abc.reg_a(), abc.1 as Upvalue),
+			Opcode::SetUpval => Self::SetUpval(abc.reg_a(), abc.1 as Upvalue),
+			Opcode::CloseUpvals => Self::CloseUpvals(abc.reg_a()),
+			Opcode::GetImport => Self::GetImport(abc.reg_a(), Kst(d as u16 as u32), aux),
+			Opcode::GetTable => Self::GetTable(abc.reg_a(), abc.reg_b(), abc.reg_c()),
+			Opcode::SetTable => Self::SetTable(abc.reg_a(), abc.reg_b(), abc.reg_c()),
+			Opcode::GetTableKs => Self::GetTableKs(abc.reg_a(), abc.reg_b(), abc.2, Kst(aux)),
+			Opcode::SetTableKs => Self::SetTableKs(abc.reg_a(), abc.reg_b(), abc.2, Kst(aux)),
+			Opcode::GetTableN => Self::GetTableN(abc.reg_a(), abc.reg_b(), abc.2),
+			Opcode::SetTableN => Self::SetTableN(abc.reg_a(), abc.reg_b(), abc.2),
+			Opcode::NewClosure => Self::NewClosure(abc.reg_a(), d as u16),
+			Opcode::NameCall => Self::NameCall(abc.reg_a(), abc.reg_b(), abc.2, Kst(aux)),
+			Opcode::Call => Self::Call(abc.reg_a(), abc.1, abc.2),
+			Opcode::Return => Self::Return(abc.reg_a(), abc.1),
+			Opcode::Jump => Self::Jump(d),
+			Opcode::JumpBack => Self::JumpBack(d),
+			Opcode::JumpIf => Self::JumpIf(abc.reg_a(), d),
+			Opcode::JumpIfNot => Self::JumpIfNot(abc.reg_a(), d),
+			Opcode::JumpIfEq => cond(CondOp::Eq),
+			Opcode::JumpIfLe => cond(CondOp::Le),
+			Opcode::JumpIfLt => cond(CondOp::Lt),
+			Opcode::JumpIfNotEq => cond(CondOp::NotEq),
+			Opcode::JumpIfNotLe => cond(CondOp::NotLe),
+			Opcode::JumpIfNotLt => cond(CondOp::NotLt),
+			Opcode::Add => bin(BinOp::Add),
+			Opcode::Sub => bin(BinOp::Sub),
+			Opcode::Mul => bin(BinOp::Mul),
+			Opcode::Div => bin(BinOp::Div),
+			Opcode::Mod => bin(BinOp::Mod),
+			Opcode::Pow => bin(BinOp::Pow),
+			Opcode::IDiv => bin(BinOp::IDiv),
+			Opcode::AddK => bin_k(BinOp::Add),
+			Opcode::SubK => bin_k(BinOp::Sub),
+			Opcode::MulK => bin_k(BinOp::Mul),
+			Opcode::DivK => bin_k(BinOp::Div),
+			Opcode::ModK => bin_k(BinOp::Mod),
+			Opcode::PowK => bin_k(BinOp::Pow),
+			Opcode::IDivK => bin_k(BinOp::IDiv),
+			Opcode::SubRK => Self::BinOpRK(abc.reg_a(), abc.kst_b(), BinOp::Sub, abc.reg_c()),
+			Opcode::DivRK => Self::BinOpRK(abc.reg_a(), abc.kst_b(), BinOp::Div, abc.reg_c()),
+			Opcode::And => Self::And(abc.reg_a(), abc.reg_b(), abc.reg_c()),
+			Opcode::Or => Self::Or(abc.reg_a(), abc.reg_b(), abc.reg_c()),
+			Opcode::AndK => Self::AndK(abc.reg_a(), abc.reg_b(), abc.kst_c()),
+			Opcode::OrK => Self::OrK(abc.reg_a(), abc.reg_b(), abc.kst_c()),
+			Opcode::Concat => Self::Concat(abc.reg_a(), abc.reg_b(), abc.reg_c()),
+			Opcode::Not => Self::UnOp(abc.reg_a(), UnOp::Not, abc.reg_b()),
+			Opcode::Minus => Self::UnOp(abc.reg_a(), UnOp::Minus, abc.reg_b()),
+			Opcode::Length => Self::UnOp(abc.reg_a(), UnOp::Length, abc.reg_b()),
+			Opcode::NewTable => Self::NewTable(abc.reg_a(), abc.1, aux),
+			Opcode::DupTable => Self::DupTable(abc.reg_a(), Kst(d as u16 as u32)),
+			Opcode::SetList => Self::SetList(abc.reg_a(), abc.reg_b(), abc.2, aux),
+			Opcode::ForNPrep => Self::ForNPrep(abc.reg_a(), d),
+			Opcode::ForNLoop => Self::ForNLoop(abc.reg_a(), d),
+			Opcode::ForGLoop => Self::ForGLoop(abc.reg_a(), d, aux),
+			Opcode::ForGPrepINext => Self::ForGPrepINext(abc.reg_a(), d),
+			Opcode::ForGPrepNext => Self::ForGPrepNext(abc.reg_a(), d),
+			Opcode::ForGPrep => Self::ForGPrep(abc.reg_a(), d),
+			Opcode::NativeCall => Self::NativeCall,
+			Opcode::GetVarArgs => Self::GetVarArgs(abc.reg_a(), abc.1),
+			Opcode::DupClosure => Self::DupClosure(abc.reg_a(), Kst(d as u16 as u32)),
+			Opcode::PrepVarArgs => Self::PrepVarArgs(abc.0),
+			Opcode::LoadKx => Self::LoadKx(abc.reg_a(), Kst(aux)),
+			Opcode::JumpX => Self::JumpX(E(instr)),
+			Opcode::Coverage => Self::Coverage(E(instr)),
+			Opcode::Capture => Self::Capture(CaptureType::try_from_byte(abc.0)?, abc.1),
+			Opcode::FastCall => Self::FastCall(abc.0, abc.2),
+			Opcode::FastCall1 => Self::FastCall1(abc.0, abc.reg_b(), abc.2),
+			Opcode::FastCall2 => Self::FastCall2(abc.0, abc.reg_b(), Reg(aux as u8), abc.2),
+			Opcode::FastCall2K => Self::FastCall2K(abc.0, abc.reg_b(), Kst(aux), abc.2),
+			Opcode::FastCall3 => Self::FastCall3(abc.0, abc.reg_b(), Reg(aux as u8), Reg((aux >> 8) as u8), abc.2),
+			Opcode::JumpXEqKNil => Self::JumpXEqKNil(abc.reg_a(), d, aux_not),
+			Opcode::JumpXEqKB => Self::JumpXEqKB(abc.reg_a(), d, aux & 1 != 0, aux_not),
+			Opcode::JumpXEqKN => Self::JumpXEqKN(abc.reg_a(), d, aux_kst, aux_not),
+			Opcode::JumpXEqKS => Self::JumpXEqKS(abc.reg_a(), d, aux_kst, aux_not)
+		})
+	}
+
+	pub fn get_opcode(&self) -> Opcode {
+		match self {
+			Self::Nop => Opcode::Nop,
+			Self::Break => Opcode::Break,
+			Self::LoadNil(..) => Opcode::LoadNil,
+			Self::LoadB(..) => Opcode::LoadB,
+			Self::LoadN(..) => Opcode::LoadN,
+			Self::LoadK(..) => Opcode::LoadK,
+			Self::Move(..) => Opcode::Move,
+			Self::GetGlobal(..) => Opcode::GetGlobal,
+			Self::SetGlobal(..) => Opcode::SetGlobal,
+			Self::GetUpval(..) => Opcode::GetUpval,
+			Self::SetUpval(..) => Opcode::SetUpval,
+			Self::CloseUpvals(..) => Opcode::CloseUpvals,
+			Self::GetImport(..) => Opcode::GetImport,
+			Self::GetTable(..) => Opcode::GetTable,
+			Self::SetTable(..) => Opcode::SetTable,
+			Self::GetTableKs(..) => Opcode::GetTableKs,
+			Self::SetTableKs(..) => Opcode::SetTableKs,
+			Self::GetTableN(..) => Opcode::GetTableN,
+			Self::SetTableN(..) => Opcode::SetTableN,
+			Self::NewClosure(..) => Opcode::NewClosure,
+			Self::NameCall(..) => Opcode::NameCall,
+			Self::Call(..) => Opcode::Call,
+			Self::Return(..) => Opcode::Return,
+			Self::Jump(..) => Opcode::Jump,
+			Self::JumpBack(..) => Opcode::JumpBack,
+			Self::JumpIf(..) => Opcode::JumpIf,
+			Self::JumpIfNot(..) => Opcode::JumpIfNot,
+			Self::JumpIfCond(_, op, _, _) => match op {
+				CondOp::Eq => Opcode::JumpIfEq,
+				CondOp::Le => Opcode::JumpIfLe,
+				CondOp::Lt => Opcode::JumpIfLt,
+				CondOp::NotEq => Opcode::JumpIfNotEq,
+				CondOp::NotLe => Opcode::JumpIfNotLe,
+				CondOp::NotLt => Opcode::JumpIfNotLt
+			},
+			Self::BinOp(_, _, op, _) => match op {
+				BinOp::Add => Opcode::Add,
+				BinOp::Sub => Opcode::Sub,
+				BinOp::Mul => Opcode::Mul,
+				BinOp::Div => Opcode::Div,
+				BinOp::Mod => Opcode::Mod,
+				BinOp::Pow => Opcode::Pow,
+				BinOp::IDiv => Opcode::IDiv
+			},
+			Self::BinOpK(_, _, op, _) => match op {
+				BinOp::Add => Opcode::AddK,
+				BinOp::Sub => Opcode::SubK,
+				BinOp::Mul => Opcode::MulK,
+				BinOp::Div => Opcode::DivK,
+				BinOp::Mod => Opcode::ModK,
+				BinOp::Pow => Opcode::PowK,
+				BinOp::IDiv => Opcode::IDivK
+			},
+			Self::BinOpRK(_, _, op, _) => match op {
+				BinOp::Div => Opcode::DivRK,
+				_ => Opcode::SubRK
+			},
+			Self::And(..) => Opcode::And,
+			Self::Or(..) => Opcode::Or,
+			Self::AndK(..) => Opcode::AndK,
+			Self::OrK(..) => Opcode::OrK,
+			Self::Concat(..) => Opcode::Concat,
+			Self::UnOp(_, op, _) => match op {
+				UnOp::Not => Opcode::Not,
+				UnOp::Minus => Opcode::Minus,
+				UnOp::Length => Opcode::Length
+			},
+			Self::NewTable(..) => Opcode::NewTable,
+			Self::DupTable(..) => Opcode::DupTable,
+			Self::SetList(..) => Opcode::SetList,
+			Self::ForNPrep(..) => Opcode::ForNPrep,
+			Self::ForNLoop(..) => Opcode::ForNLoop,
+			Self::ForGLoop(..) => Opcode::ForGLoop,
+			Self::ForGPrepINext(..) => Opcode::ForGPrepINext,
+			Self::ForGPrepNext(..) => Opcode::ForGPrepNext,
+			Self::ForGPrep(..) => Opcode::ForGPrep,
+			Self::NativeCall => Opcode::NativeCall,
+			Self::GetVarArgs(..) => Opcode::GetVarArgs,
+			Self::DupClosure(..) => Opcode::DupClosure,
+			Self::PrepVarArgs(..) => Opcode::PrepVarArgs,
+			Self::LoadKx(..) => Opcode::LoadKx,
+			Self::JumpX(..) => Opcode::JumpX,
+			Self::Coverage(..) => Opcode::Coverage,
+			Self::Capture(..) => Opcode::Capture,
+			Self::FastCall(..) => Opcode::FastCall,
+			Self::FastCall1(..) => Opcode::FastCall1,
+			Self::FastCall2(..) => Opcode::FastCall2,
+			Self::FastCall2K(..) => Opcode::FastCall2K,
+			Self::FastCall3(..) => Opcode::FastCall3,
+			Self::JumpXEqKNil(..) => Opcode::JumpXEqKNil,
+			Self::JumpXEqKB(..) => Opcode::JumpXEqKB,
+			Self::JumpXEqKN(..) => Opcode::JumpXEqKN,
+			Self::JumpXEqKS(..) => Opcode::JumpXEqKS
+		}
+	}
+
+	pub fn get_opmode(&self) -> Opmode {
+		match self {
+			Self::Nop
+			| Self::Break
+			| Self::NativeCall => Opmode::ABC(0, 0, 0),
+			Self::LoadNil(a)
+			| Self::CloseUpvals(a) => Opmode::ABC(a.0, 0, 0),
+			Self::LoadB(a, b, c) => Opmode::ABC(a.0, *b as u8, *c),
+			Self::LoadN(a, d)
+			| Self::JumpIf(a, d)
+			| Self::JumpIfNot(a, d)
+			| Self::JumpIfCond(a, _, _, d)
+			| Self::ForNPrep(a, d)
+			| Self::ForNLoop(a, d)
+			| Self::ForGLoop(a, d, _)
+			| Self::ForGPrepINext(a, d)
+			| Self::ForGPrepNext(a, d)
+			| Self::ForGPrep(a, d)
+			| Self::JumpXEqKNil(a, d, _)
+			| Self::JumpXEqKB(a, d, _, _)
+			| Self::JumpXEqKN(a, d, _, _)
+			| Self::JumpXEqKS(a, d, _, _) => Opmode::AD(a.0, *d),
+			Self::LoadK(a, k)
+			| Self::GetImport(a, k, _)
+			| Self::DupTable(a, k)
+			| Self::DupClosure(a, k) => Opmode::AD(a.0, k.0 as i16),
+			Self::Move(a, b)
+			| Self::UnOp(a, _, b) => Opmode::ABC(a.0, b.0, 0),
+			Self::GetGlobal(a, c, _)
+			| Self::SetGlobal(a, c, _) => Opmode::ABC(a.0, 0, *c),
+			Self::GetUpval(a, b)
+			| Self::SetUpval(a, b) => Opmode::ABC(a.0, *b as u8, 0),
+			Self::GetTable(a, b, c)
+			| Self::SetTable(a, b, c)
+			| Self::BinOp(a, b, _, c)
+			| Self::And(a, b, c)
+			| Self::Or(a, b, c)
+			| Self::Concat(a, b, c) => Opmode::ABC(a.0, b.0, c.0),
+			Self::GetTableKs(a, b, c, _)
+			| Self::SetTableKs(a, b, c, _)
+			| Self::NameCall(a, b, c, _)
+			| Self::GetTableN(a, b, c)
+			| Self::SetTableN(a, b, c)
+			| Self::SetList(a, b, c, _) => Opmode::ABC(a.0, b.0, *c),
+			Self::BinOpK(a, b, _, c)
+			| Self::AndK(a, b, c)
+			| Self::OrK(a, b, c) => Opmode::ABC(a.0, b.0, c.0 as u8),
+			Self::BinOpRK(a, b, _, c) => Opmode::ABC(a.0, b.0 as u8, c.0),
+			Self::NewClosure(a, d) => Opmode::AD(a.0, *d as i16),
+			Self::Call(a, b, c) => Opmode::ABC(a.0, *b, *c),
+			Self::Return(a, b)
+			| Self::NewTable(a, b, _)
+			| Self::GetVarArgs(a, b) => Opmode::ABC(a.0, *b, 0),
+			Self::Jump(d)
+			| Self::JumpBack(d) => Opmode::AD(0, *d),
+			Self::PrepVarArgs(a) => Opmode::ABC(*a, 0, 0),
+			Self::LoadKx(a, _) => Opmode::ABC(a.0, 0, 0),
+			Self::JumpX(e)
+			| Self::Coverage(e) => Opmode::E(*e),
+			Self::Capture(a, b) => Opmode::ABC(*a as u8, *b, 0),
+			Self::FastCall(a, c) => Opmode::ABC(*a, 0, *c),
+			Self::FastCall1(a, b, c)
+			| Self::FastCall2(a, b, _, c)
+			| Self::FastCall2K(a, b, _, c)
+			| Self::FastCall3(a, b, _, _, c) => Opmode::ABC(*a, b.0, *c)
+		}
+	}
+
+	pub fn get_aux(&self) -> Option<u32> {
+		let not = |not: bool| if not { AUX_NOT } else { 0 };
+
+		Some(match self {
+			Self::GetGlobal(_, _, k)
+			| Self::SetGlobal(_, _, k)
+			| Self::GetTableKs(_, _, _, k)
+			| Self::SetTableKs(_, _, _, k)
+			| Self::NameCall(_, _, _, k)
+			| Self::LoadKx(_, k)
+			| Self::FastCall2K(_, _, k, _) => k.0,
+			Self::GetImport(_, _, aux)
+			| Self::NewTable(_, _, aux)
+			| Self::SetList(_, _, _, aux)
+			| Self::ForGLoop(_, _, aux) => *aux,
+			Self::JumpIfCond(_, _, b, _)
+			| Self::FastCall2(_, _, b, _) => b.0 as u32,
+			Self::FastCall3(_, _, b, c, _) => b.0 as u32 | (c.0 as u32) << 8,
+			Self::JumpXEqKNil(_, _, n) => not(*n),
+			Self::JumpXEqKB(_, _, v, n) => *v as u32 | not(*n),
+			Self::JumpXEqKN(_, _, k, n)
+			| Self::JumpXEqKS(_, _, k, n) => k.0 & 0xffffff | not(*n),
+			_ => return None
+		})
+	}
+}
+
+// unique id
+fn get_id() -> usize {
+	COUNTER.fetch_add(1, Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone)]
+pub struct Instruction(pub Opcode, pub Instr, pub Opmode, pub usize);
+impl Instruction {
+	pub fn new(instr: Instr) -> Self {
+		let op = instr.get_opcode();
+		let mode = instr.get_opmode();
+		Self(op, instr, mode, get_id())
+	}
+
+	pub fn from_instr(instr: u32, aux: Option<&u32>) -> Self {
+		Self::new(Instr::from_instr(instr, aux))
+	}
+
+	pub fn try_from_instr(instr: u32, aux: Option<&u32>) -> Result<Self, DecodeErrorKind> {
+		Ok(Self::new(Instr::try_from_instr(instr, aux)?))
+	}
+
+	// number of code words, 2 when an AUX word follows
+	pub fn size(&self) -> usize {
+		1 + self.0.has_aux() as usize
+	}
+
+	pub fn serialize(&self) -> (u32, Option<u32>) {
+		let serialized = self.0.to_instr();
+		let word = match self.2 {
+			Opmode::ABC(a, b, c) => serialized | (a as u32) << 8 | (b as u32) << 16 | (c as u32) << 24,
+			Opmode::AD(a, d) => serialized | (a as u32) << 8 | (d as u16 as u32) << 16,
+			Opmode::E(e) => serialized | (e as u32) << 8
+		};
+		(word, self.1.get_aux())
+	}
+}
@@ -0,0 +1,174 @@
+mod deserialize;
+pub mod instruction;
+mod serialize;
+
+use crate::LuaString;
+
+pub use crate::lua51::{Reader, Writer};
+pub use deserialize::{deserialize_bytecode, try_deserialize_bytecode, deserialize_from};
+pub use serialize::{serialize_bytecode, serialize_to};
+
+// Proto::flags
+pub const LPF_NATIVE_MODULE: u8 = 1 << 0;
+pub const LPF_NATIVE_COLD: u8 = 1 << 1;
+pub const LPF_NATIVE_FUNCTION: u8 = 1 << 2;
+
+// 1 based index into Chunk::strings, 0 for none
+pub type StringRef = u32;
+
+// globals like `a.b.c` are resolved at load time from up to 3 constant indices of 10 bits each,
+// the top 2 bits hold the length of the path
+pub fn import_path(id: u32) -> Vec<u32> {
+	let count = id >> 30;
+	[id >> 20 & 1023, id >> 10 & 1023, id & 1023].into_iter().take(count as usize).collect()
+}
+
+pub fn import_id(path: &[u32]) -> u32 {
+	let mut id = (path.len() as u32) << 30;
+	for (i, k) in path.iter().take(3).enumerate() {
+		id |= (k & 1023) << (20 - i * 10);
+	}
+	id
+}
+
+#[derive(Debug, PartialEq, PartialOrd, Clone)]
+pub enum Constants {
+	Nil,
+	Boolean(bool),
+	Number(f64),
+	String(StringRef),
+	Import(u32), // see import_path
+	Table(Vec<u32>), // constant indices of the template's keys
+	Closure(u32), // index into Chunk::protos
+	Vector(f32, f32, f32, f32)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypedLocal {
+	pub ty: u8,
+	pub reg: u8,
+	pub start_pc: u32,
+	pub end_pc: u32
+}
+
+// types version 1 only has the function signature
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TypeInfo {
+	pub function: Vec<u8>,
+	pub upvalues: Vec<u8>,
+	pub locals: Vec<TypedLocal>
+}
+
+// line of pc is abs_line_info[pc >> gap_log2] + line_offsets[pc]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineInfo {
+	pub gap_log2: u8,
+	pub line_offsets: Vec<u8>, // one per code word
+	pub abs_line_info: Vec<i32>
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LocVar {
+	pub name: StringRef,
+	pub start_pc: u32,
+	pub end_pc: u32,
+	pub reg: u8
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugInfo {
+	pub locals: Vec<LocVar>,
+	pub upvalues: Vec<StringRef>
+}
+
+#[derive(Debug, Clone)]
+pub struct Proto {
+	pub max_stack_size: u8,
+	pub nparams: u8,
+	pub nupvals: u8,
+	pub is_vararg: u8,
+	pub flags: u8, // from version 4
+	pub type_info: Option<TypeInfo>, // from version 4
+	pub instructions: Vec<instruction::Instruction>, // pcs count code words, see Instruction::size
+	pub constants: Vec<Constants>,
+	pub prototypes: Vec<u32>, // indices into Chunk::protos
+	pub line_defined: u32,
+	pub debug_name: StringRef,
+	pub line_info: Option<LineInfo>,
+	pub debug: Option<DebugInfo>
+}
+
+impl Default for Proto {
+	fn default() -> Self {
+		Self {
+			max_stack_size: 2,
+			nparams: 0,
+			nupvals: 0,
+			is_vararg: 1,
+			flags: 0,
+			type_info: None,
+			instructions: vec![],
+			constants: vec![],
+			prototypes: vec![],
+			line_defined: 0,
+			debug_name: 0,
+			line_info: None,
+			debug: None
+		}
+	}
+}
+
+impl Proto {
+	pub fn code_size(&self) -> usize {
+		self.instructions.iter().map(|instr| instr.size()).sum()
+	}
+
+	pub fn line(&self, pc: usize) -> Option<i32> {
+		let info = self.line_info.as_ref()?;
+		let offset = *info.line_offsets.get(pc)?;
+		let base = *info.abs_line_info.get(pc >> info.gap_log2)?;
+		Some(base + offset as i32)
+	}
+}
+
+// userdata type names for types version 3
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UserdataType {
+	pub index: u8, // 1 based
+	pub name: StringRef
+}
+
+// unlike the other formats protos are kept in one flat table and refer to each other by index
+#[derive(Debug, Clone, Default)]
+pub struct Chunk {
+	pub strings: Vec<LuaString>,
+	pub userdata_types: Vec<UserdataType>,
+	pub protos: Vec<Proto>,
+	pub main: u32
+}
+
+impl Chunk {
+	pub fn string(&self, s: StringRef) -> Option<&LuaString> {
+		self.strings.get((s as usize).checked_sub(1)?)
+	}
+
+	pub fn main(&self) -> &Proto {
+		&self.protos[self.main as usize]
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Header {
+	pub version: u8, // 3 to 6
+	pub types_version: u8 // 1 to 3, not dumped before version 4
+}
+
+impl Default for Header {
+	// what luau-compile emits
+	fn default() -> Self {
+		Self {
+			version: 5,
+			types_version: 2
+		}
+	}
+}
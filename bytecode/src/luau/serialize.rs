@@ -0,0 +1,196 @@
+use crate::Endianness;
+use super::{Writer, Header, Chunk, Proto, Constants, TypeInfo, LineInfo, DebugInfo};
+use super::deserialize::{
+	LBC_CONSTANT_NIL, LBC_CONSTANT_BOOLEAN, LBC_CONSTANT_NUMBER, LBC_CONSTANT_STRING,
+	LBC_CONSTANT_IMPORT, LBC_CONSTANT_TABLE, LBC_CONSTANT_CLOSURE, LBC_CONSTANT_VECTOR
+};
+
+fn varint(writer: &mut Writer, v: u32) {
+	let mut v = v;
+	while v >= 0x80 {
+		writer.byte((v & 0x7f) as u8 | 0x80);
+		v >>= 7;
+	}
+	writer.byte(v as u8);
+}
+
+fn dump_type_info(writer: &mut Writer, header: &Header, info: &Option<TypeInfo>) {
+	let Some(info) = info else {
+		return varint(writer, 0);
+	};
+
+	if header.types_version == 1 {
+		varint(writer, info.function.len() as u32);
+		return writer.bytes(info.function.clone());
+	}
+
+	// the size of the whole blob comes first
+	let mut blob = Writer::new();
+	varint(&mut blob, info.function.len() as u32);
+	varint(&mut blob, info.upvalues.len() as u32);
+	varint(&mut blob, info.locals.len() as u32);
+	blob.bytes(info.function.clone());
+	blob.bytes(info.upvalues.clone());
+	for local in &info.locals {
+		blob.byte(local.ty);
+		blob.byte(local.reg);
+		varint(&mut blob, local.start_pc);
+		varint(&mut blob, local.end_pc.wrapping_sub(local.start_pc));
+	}
+
+	varint(writer, blob.as_bytes().len() as u32);
+	writer.bytes(blob.as_bytes().to_vec());
+}
+
+fn dump_constant(writer: &mut Writer, kst: &Constants) {
+	match kst {
+		Constants::Nil => writer.byte(LBC_CONSTANT_NIL),
+		Constants::Boolean(b) => {
+			writer.byte(LBC_CONSTANT_BOOLEAN);
+			writer.byte(*b as u8);
+		},
+		Constants::Number(n) => {
+			writer.byte(LBC_CONSTANT_NUMBER);
+			writer.uint(n.to_bits(), 8);
+		},
+		Constants::String(s) => {
+			writer.byte(LBC_CONSTANT_STRING);
+			varint(writer, *s);
+		},
+		Constants::Import(id) => {
+			writer.byte(LBC_CONSTANT_IMPORT);
+			writer.int(*id, 4);
+		},
+		Constants::Table(keys) => {
+			writer.byte(LBC_CONSTANT_TABLE);
+			varint(writer, keys.len() as u32);
+			keys.iter().for_each(|k| varint(writer, *k));
+		},
+		Constants::Closure(id) => {
+			writer.byte(LBC_CONSTANT_CLOSURE);
+			varint(writer, *id);
+		},
+		Constants::Vector(x, y, z, w) => {
+			writer.byte(LBC_CONSTANT_VECTOR);
+			[x, y, z, w].iter().for_each(|c| writer.int(c.to_bits(), 4));
+		}
+	}
+}
+
+fn dump_line_info(writer: &mut Writer, info: &LineInfo) {
+	writer.byte(info.gap_log2);
+
+	let mut last_offset: u8 = 0;
+	for offset in &info.line_offsets {
+		writer.byte(offset.wrapping_sub(last_offset));
+		last_offset = *offset;
+	}
+
+	let mut last_line: i32 = 0;
+	for line in &info.abs_line_info {
+		writer.int(line.wrapping_sub(last_line) as u32, 4);
+		last_line = *line;
+	}
+}
+
+fn dump_debug_info(writer: &mut Writer, info: &DebugInfo) {
+	varint(writer, info.locals.len() as u32);
+	for local in &info.locals {
+		varint(writer, local.name);
+		varint(writer, local.start_pc);
+		varint(writer, local.end_pc);
+		writer.byte(local.reg);
+	}
+
+	varint(writer, info.upvalues.len() as u32);
+	info.upvalues.iter().for_each(|name| varint(writer, *name));
+}
+
+fn dump_proto(writer: &mut Writer, header: &Header, proto: &Proto) {
+	writer.byte(proto.max_stack_size);
+	writer.byte(proto.nparams);
+	writer.byte(proto.nupvals);
+	writer.byte(proto.is_vararg);
+
+	if header.version >= 4 {
+		writer.byte(proto.flags);
+		dump_type_info(writer, header, &proto.type_info);
+	}
+
+	// instructions
+	varint(writer, proto.code_size() as u32);
+	for instr in &proto.instructions {
+		let (word, aux) = instr.serialize();
+		writer.int(word, 4);
+		if let Some(aux) = aux {
+			writer.int(aux, 4);
+		}
+	}
+
+	// constants
+	varint(writer, proto.constants.len() as u32);
+	proto.constants.iter().for_each(|kst| dump_constant(writer, kst));
+
+	// child protos
+	varint(writer, proto.prototypes.len() as u32);
+	proto.prototypes.iter().for_each(|id| varint(writer, *id));
+
+	// debug info
+	varint(writer, proto.line_defined);
+	varint(writer, proto.debug_name);
+
+	match &proto.line_info {
+		Some(info) => {
+			writer.byte(1);
+			dump_line_info(writer, info);
+		},
+		None => writer.byte(0)
+	}
+
+	match &proto.debug {
+		Some(info) => {
+			writer.byte(1);
+			dump_debug_info(writer, info);
+		},
+		None => writer.byte(0)
+	}
+}
+
+pub fn serialize_bytecode(header: &Header, chunk: &Chunk) -> Vec<u8> {
+	let mut writer = Writer::new();
+	serialize_to(&mut writer, header, chunk);
+	writer.as_bytes().into()
+}
+
+// appends one chunk to the writer, returning how many bytes it took up. errors from a
+// streaming writer only show up once it is flushed
+pub fn serialize_to(writer: &mut Writer, header: &Header, chunk: &Chunk) -> usize {
+	let start = writer.position();
+	writer.set_endianness(Endianness::Little);
+
+	writer.byte(header.version);
+	if header.version >= 4 {
+		writer.byte(header.types_version);
+	}
+
+	// string table
+	varint(writer, chunk.strings.len() as u32);
+	for s in &chunk.strings {
+		varint(writer, s.len() as u32);
+		writer.bytes(s.as_bytes().to_vec());
+	}
+
+	if header.types_version == 3 {
+		for ty in &chunk.userdata_types {
+			writer.byte(ty.index);
+			varint(writer, ty.name);
+		}
+		writer.byte(0);
+	}
+
+	varint(writer, chunk.protos.len() as u32);
+	chunk.protos.iter().for_each(|proto| dump_proto(writer, header, proto));
+	varint(writer, chunk.main);
+
+	writer.position() - start
+}
@@ -0,0 +1,147 @@
+use std::fmt;
+use std::ops::Deref;
+
+// lua strings are plain bytes and don't have to be valid utf-8
+#[derive(Clone, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct LuaString(pub Vec<u8>);
+
+impl LuaString {
+	pub fn new() -> Self {
+		Self(vec![])
+	}
+
+	pub fn as_bytes(&self) -> &[u8] {
+		&self.0
+	}
+
+	pub fn into_bytes(self) -> Vec<u8> {
+		self.0
+	}
+
+	pub fn to_str(&self) -> Option<&str> {
+		std::str::from_utf8(&self.0).ok()
+	}
+
+	pub fn to_string_lossy(&self) -> String {
+		String::from_utf8_lossy(&self.0).into_owned()
+	}
+
+	// escaped like luac -l prints string constants, without the quotes
+	pub fn escaped(&self) -> String {
+		let mut out = String::with_capacity(self.0.len());
+		for &b in &self.0 {
+			match b {
+				b'"' => out.push_str("\\\""),
+				b'\\' => out.push_str("\\\\"),
+				0x07 => out.push_str("\\a"),
+				0x08 => out.push_str("\\b"),
+				0x0c => out.push_str("\\f"),
+				b'\n' => out.push_str("\\n"),
+				b'\r' => out.push_str("\\r"),
+				b'\t' => out.push_str("\\t"),
+				0x0b => out.push_str("\\v"),
+				0x20..=0x7e => out.push(b as char),
+				_ => out.push_str(&format!("\\{:03}", b))
+			}
+		}
+		out
+	}
+}
+
+impl Deref for LuaString {
+	type Target = [u8];
+
+	fn deref(&self) -> &[u8] {
+		&self.0
+	}
+}
+
+impl fmt::Display for LuaString {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "{}", self.escaped())
+	}
+}
+
+// valid utf-8 goes out as a string and anything else as bytes, either can be read back
+#[cfg(feature = "serde")]
+impl serde::Serialize for LuaString {
+	fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		match self.to_str() {
+			Some(s) => serializer.serialize_str(s),
+			None => serializer.serialize_bytes(&self.0)
+		}
+	}
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for LuaString {
+	fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		struct Visitor;
+		impl<'de> serde::de::Visitor<'de> for Visitor {
+			type Value = LuaString;
+
+			fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+				f.write_str("a string or a list of bytes")
+			}
+
+			fn visit_str<E>(self, s: &str) -> Result<LuaString, E> {
+				Ok(s.into())
+			}
+
+			fn visit_bytes<E>(self, b: &[u8]) -> Result<LuaString, E> {
+				Ok(b.into())
+			}
+
+			fn visit_seq<A: serde::de::SeqAccess<'de>>(self, mut seq: A) -> Result<LuaString, A::Error> {
+				let mut bytes = vec![];
+				while let Some(b) = seq.next_element()? {
+					bytes.push(b);
+				}
+				Ok(bytes.into())
+			}
+		}
+		deserializer.deserialize_any(Visitor)
+	}
+}
+
+impl fmt::Debug for LuaString {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "\"{}\"", self.escaped())
+	}
+}
+
+impl From<&str> for LuaString {
+	fn from(s: &str) -> Self {
+		Self(s.as_bytes().to_vec())
+	}
+}
+
+impl From<String> for LuaString {
+	fn from(s: String) -> Self {
+		Self(s.into_bytes())
+	}
+}
+
+impl From<&[u8]> for LuaString {
+	fn from(b: &[u8]) -> Self {
+		Self(b.to_vec())
+	}
+}
+
+impl From<Vec<u8>> for LuaString {
+	fn from(b: Vec<u8>) -> Self {
+		Self(b)
+	}
+}
+
+impl PartialEq<str> for LuaString {
+	fn eq(&self, other: &str) -> bool {
+		self.0 == other.as_bytes()
+	}
+}
+
+impl PartialEq<&str> for LuaString {
+	fn eq(&self, other: &&str) -> bool {
+		self.0 == other.as_bytes()
+	}
+}
@@ -0,0 +1,178 @@
+// control-flow graph of a single proto. pcs are instruction indices, same as the jump offsets
+//
+// a test (EQ/LT/LE/TEST/TESTSET) and the JMP after it make up one branch and end up in the same
+// block, unless something else jumps straight to the JMP. TFORLOOP and its JMP back are treated
+// the same way
+
+use std::ops::Range;
+
+use bytecode::lua51::{instruction::Instr, Proto};
+
+use crate::context::InstructionPointer;
+
+pub type BlockId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EdgeKind {
+	Fallthrough,
+	Jump, // JMP on its own, or LOADBOOL skipping the next instruction
+	CondTrue, // the test came out as asked for, so the JMP after it runs
+	CondFalse, // the JMP gets skipped
+	LoopBack, // FORLOOP or TFORLOOP going round again
+	LoopExit, // and leaving
+	ForPrep,
+	Return
+}
+
+#[derive(Debug, Clone)]
+pub struct BasicBlock {
+	pub code: Range<InstructionPointer>, // empty for the entry and exit nodes
+	pub succs: Vec<(BlockId, EdgeKind)>,
+	pub preds: Vec<(BlockId, EdgeKind)>
+}
+
+#[derive(Debug, Clone)]
+pub struct Cfg {
+	pub blocks: Vec<BasicBlock>,
+	pub entry: BlockId, // no code, goes to the block at pc 0
+	pub exit: BlockId // no code, every RETURN goes here
+}
+
+fn is_test(instr: &Instr) -> bool {
+	matches!(instr, Instr::BinCondOp(..) | Instr::Test(..) | Instr::TestSet(..))
+}
+
+fn target(pc: InstructionPointer, sbx: i32) -> Option<InstructionPointer> {
+	usize::try_from(pc as i64 + 1 + sbx as i64).ok()
+}
+
+impl Cfg {
+	pub fn new(proto: &Proto) -> Self {
+		let code = &proto.instructions;
+		let len = code.len();
+
+		// the first instruction of every block
+		let mut leaders = vec![false; len + 1];
+		leaders[0] = true;
+		for (pc, instruction) in code.iter().enumerate() {
+			let mut lead = |at: Option<usize>| if let Some(at) = at.filter(|&at| at <= len) {
+				leaders[at] = true;
+			};
+			match &instruction.1 {
+				Instr::Jump(_, sbx) | Instr::ForPrep(_, sbx) | Instr::ForLoop(_, sbx) => {
+					lead(target(pc, *sbx));
+					lead(Some(pc + 1));
+				},
+				instr if is_test(instr) || matches!(instr, Instr::TForLoop(..)) => {
+					if !matches!(code.get(pc + 1).map(|next| &next.1), Some(Instr::Jump(..))) {
+						lead(Some(pc + 1));
+					}
+					lead(Some(pc + 2));
+				},
+				Instr::LoadBool(_, _, true) => {
+					lead(Some(pc + 1));
+					lead(Some(pc + 2));
+				},
+				Instr::Return(..) => lead(Some(pc + 1)),
+				_ => {}
+			}
+		}
+
+		let starts: Vec<_> = (0..len).filter(|&pc| leaders[pc]).collect();
+		let exit = starts.len() + 1;
+		let mut blocks = vec![BasicBlock { code: 0..0, succs: vec![], preds: vec![] }];
+		blocks.extend(starts.iter().enumerate().map(|(i, &start)| BasicBlock {
+			code: start..starts.get(i + 1).copied().unwrap_or(len),
+			succs: vec![],
+			preds: vec![]
+		}));
+		blocks.push(BasicBlock { code: len..len, succs: vec![], preds: vec![] });
+		let mut cfg = Self { blocks, entry: 0, exit };
+
+		let first = cfg.block_of(0).unwrap_or(exit);
+		cfg.add_edge(0, first, EdgeKind::Fallthrough);
+		for id in 1..exit {
+			let Range { start, end } = cfg.blocks[id].code.clone();
+			let last = end - 1;
+			let before = (last > start).then(|| &code[last - 1].1);
+			let edges = match (&code[last].1, before) {
+				(Instr::Jump(_, sbx), Some(instr)) if is_test(instr) => vec![(target(last, *sbx), EdgeKind::CondTrue), (Some(end), EdgeKind::CondFalse)],
+				(Instr::Jump(_, sbx), Some(Instr::TForLoop(..))) => vec![(target(last, *sbx), EdgeKind::LoopBack), (Some(end), EdgeKind::LoopExit)],
+				(Instr::Jump(_, sbx), _) => vec![(target(last, *sbx), EdgeKind::Jump)],
+				(instr, _) if is_test(instr) => vec![(Some(end), EdgeKind::CondTrue), (Some(end + 1), EdgeKind::CondFalse)],
+				(Instr::TForLoop(..), _) => vec![(Some(end), EdgeKind::Fallthrough), (Some(end + 1), EdgeKind::LoopExit)],
+				(Instr::ForLoop(_, sbx), _) => vec![(target(last, *sbx), EdgeKind::LoopBack), (Some(end), EdgeKind::LoopExit)],
+				(Instr::ForPrep(_, sbx), _) => vec![(target(last, *sbx), EdgeKind::ForPrep)],
+				(Instr::LoadBool(_, _, true), _) => vec![(Some(end + 1), EdgeKind::Jump)],
+				(Instr::Return(..), _) => {
+					cfg.add_edge(id, exit, EdgeKind::Return);
+					continue;
+				},
+				_ => vec![(Some(end), EdgeKind::Fallthrough)]
+			};
+
+			// running off the end goes to the exit, jumps to before the start go nowhere
+			for (pc, kind) in edges {
+				let to = match pc {
+					Some(pc) if pc >= len => Some(exit),
+					Some(pc) => cfg.block_of(pc),
+					None => None
+				};
+				if let Some(to) = to {
+					cfg.add_edge(id, to, kind);
+				}
+			}
+		}
+		cfg
+	}
+
+	fn add_edge(&mut self, from: BlockId, to: BlockId, kind: EdgeKind) {
+		self.blocks[from].succs.push((to, kind));
+		self.blocks[to].preds.push((from, kind));
+	}
+
+	// the block holding pc
+	pub fn block_of(&self, pc: InstructionPointer) -> Option<BlockId> {
+		let code = &self.blocks[1..self.exit];
+		let i = code.partition_point(|block| block.code.start <= pc);
+		(i > 0 && code[i - 1].code.contains(&pc)).then_some(i)
+	}
+
+	pub fn successors(&self, id: BlockId) -> impl Iterator<Item = BlockId> + '_ {
+		self.blocks[id].succs.iter().map(|(to, _)| *to)
+	}
+
+	pub fn predecessors(&self, id: BlockId) -> impl Iterator<Item = BlockId> + '_ {
+		self.blocks[id].preds.iter().map(|(from, _)| *from)
+	}
+
+	// blocks reachable from root, each after everything it leads to. walks preds instead of
+	// succs when backwards, for going from the exit
+	pub(crate) fn postorder(&self, root: BlockId, backwards: bool) -> Vec<BlockId> {
+		let next = |id: BlockId| if backwards { &self.blocks[id].preds } else { &self.blocks[id].succs };
+		let mut seen = vec![false; self.blocks.len()];
+		let mut order = vec![];
+		let mut stack = vec![(root, 0)];
+		seen[root] = true;
+		while let Some((id, i)) = stack.pop() {
+			match next(id).get(i) {
+				Some(&(to, _)) => {
+					stack.push((id, i + 1));
+					if !seen[to] {
+						seen[to] = true;
+						stack.push((to, 0));
+					}
+				},
+				None => order.push(id)
+			}
+		}
+		order
+	}
+
+	// every block reachable from the entry, before the blocks it leads to where loops allow
+	pub fn reverse_postorder(&self) -> Vec<BlockId> {
+		let mut order = self.postorder(self.entry, false);
+		order.reverse();
+		order
+	}
+}
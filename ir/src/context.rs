@@ -1,6 +1,6 @@
-use bytecode::{lua51::{Constants, Proto, instruction::{Opcode, Instr, Instruction}, serialize_bytecode, Header}};
+use bytecode::{lua51::{Constants, Proto, instruction::{Opcode, Instr, Instruction, RegKst}, serialize_bytecode_with, Header, SerializeOptions}};
 
-use crate::control_flow::{self, Block};
+use crate::{Cfg, control_flow::{self, Block}};
 
 pub type InstructionPointer = usize;
 #[allow(non_camel_case_types)]
@@ -25,14 +25,14 @@ impl Context {
 	pub fn new(header: Header, chunk: Proto) -> Self {
 		let mut chunk = chunk;
 
-		// not gonna bother fixing these when mutation of the IR occurs 
+		// passes edit chunk.instructions directly, so there's no keeping these in step with it
 		chunk.source_lines = None;
 		chunk.locals = None;
 		chunk.upvals = None;
 
 		Self {
 			header,
-			chunk: chunk,
+			chunk,
 			constant_refs: vec![]
 		 }
 	}
@@ -60,14 +60,8 @@ impl Context {
 					| Instr::SetGlobal(_r, k) => {
 						k.0 = *kst;
 					},
-					Instr::BinOp(_r, mut a_rk, _op, mut b_rk) => {
-						match op {
-							Op::B => a_rk.set(*kst),
-							Op::C => b_rk.set(*kst),
-							_ => unreachable!()
-						}
-					},
-					Instr::BinCondOp(_r, mut a_rk, _op, mut b_rk) => {
+					Instr::BinOp(_, a_rk, _, b_rk)
+					| Instr::BinCondOp(_, a_rk, _, b_rk) => {
 						match op {
 							Op::B => a_rk.set(*kst),
 							Op::C => b_rk.set(*kst),
@@ -118,30 +112,15 @@ impl Context {
 					refs.push((ip, k.0, Op::Bx))
 				}
 			},
-			Instr::BinOp(_r, a_rk, _op, b_rk) => {
-				let ark = a_rk.get();
-				let a_kst = self.get_constant(ark);
-				if a_kst.is_some() {
-					refs.push((ip, ark, Op::B));
-				}
-
-				let brk = b_rk.get();
-				let b_kst = self.get_constant(brk);
-				if b_kst.is_some() {
-					refs.push((ip, brk, Op::C));
-				}
-			},
-			Instr::BinCondOp(_r, a_rk, _op, b_rk) => {
-				let ark = a_rk.get();
-				let a_kst = self.get_constant(ark);
-				if a_kst.is_some() {
-					refs.push((ip, ark, Op::B));
-				}
-
-				let brk = b_rk.get();
-				let b_kst = self.get_constant(brk);
-				if b_kst.is_some() {
-					refs.push((ip, brk, Op::C));
+			// register operands aren't constant refs, whatever their number
+			Instr::BinOp(_, a_rk, _, b_rk)
+			| Instr::BinCondOp(_, a_rk, _, b_rk) => {
+				for (rk, op) in [(a_rk, Op::B), (b_rk, Op::C)] {
+					if let RegKst::K(k) = rk {
+						if self.get_constant(k.0).is_some() {
+							refs.push((ip, k.0, op));
+						}
+					}
 				}
 			},
 			_ => {}
@@ -168,7 +147,7 @@ impl Context {
 		let id = clone.3;
 
 		// add instruction
-		self.chunk.instructions.insert(idx, instr);
+		self.chunk.insert_instruction(idx, instr);
 
 		// update references that references the old IP
 		// println!("added instr, remapping constant refs");
@@ -214,7 +193,10 @@ impl Context {
 	pub fn map_control_flow(&self) -> Vec<Block> {
 		let blocks = control_flow::Mapper::new().map(&self.chunk.instructions);
 		blocks
-		// control_flow::ControlFlow::new(blocks, &self.chunk)
+	}
+
+	pub fn cfg(&self) -> Cfg {
+		Cfg::new(&self.chunk)
 	}
 
 	pub fn map(&mut self) {
@@ -231,6 +213,11 @@ impl Context {
 
 
 	pub fn assemble(&self) -> Vec<u8> {
-		serialize_bytecode(&self.header, &self.chunk)
+		self.assemble_with(&SerializeOptions::default())
+	}
+
+	// stripped or with made up line info, see SerializeOptions
+	pub fn assemble_with(&self, options: &SerializeOptions) -> Vec<u8> {
+		serialize_bytecode_with(&self.header, &self.chunk, options)
 	}
 }
\ No newline at end of file
@@ -1,6 +1,6 @@
 use std::{collections::{HashMap, BTreeSet}};
 
-use bytecode::lua51::{instruction::{Instr, Instruction, Opcode}, Proto};
+use bytecode::lua51::instruction::{Instr, Instruction, Opcode};
 
 use crate::context::InstructionPointer;
 
@@ -127,32 +127,4 @@ impl Mapper {
 
 		list
 	}
-}
-
-pub struct ControlFlow<'a>  {
-	code: &'a Proto
-}
-
-impl<'a> ControlFlow<'a> {
-	pub fn new(blocks: Vec<Block>, chunk: &'a Proto) -> Self {
-		let s = Self {
-			code: chunk,
-		};
-
-		blocks.into_iter().enumerate().for_each(|(t, b)| s.map_control(t as u32, b));
-
-		s
-	}
-
-	pub fn map_control(&self, _target: u32, block: Block) {
-		let last_pc = block.code.last().unwrap();
-		let instr = &self.code.instructions[*last_pc];
-
-		match instr.0 {
-			Opcode::ForPrep => {
-
-			},
-			_ => {}
-		}
-	}
 }
\ No newline at end of file
@@ -0,0 +1,121 @@
+// dominator and post-dominator trees of a cfg, with dominance frontiers. built with the
+// iterative algorithm from Cooper, Harvey & Kennedy, "A Simple, Fast Dominance Algorithm"
+
+use crate::{BlockId, Cfg};
+
+#[derive(Debug, Clone)]
+pub struct DomTree {
+	pub root: BlockId, // the entry, or the exit for post-dominators
+	idom: Vec<Option<BlockId>>, // None for the root and blocks that aren't reached from it
+	children: Vec<Vec<BlockId>>,
+	frontiers: Vec<Vec<BlockId>>
+}
+
+impl DomTree {
+	pub fn dominators(cfg: &Cfg) -> Self {
+		Self::build(cfg, cfg.entry, false)
+	}
+
+	// blocks that never reach the exit, like the body of an endless loop, are left out
+	pub fn post_dominators(cfg: &Cfg) -> Self {
+		Self::build(cfg, cfg.exit, true)
+	}
+
+	fn build(cfg: &Cfg, root: BlockId, backwards: bool) -> Self {
+		let n = cfg.blocks.len();
+		let preds = |id: BlockId| if backwards { &cfg.blocks[id].succs } else { &cfg.blocks[id].preds };
+
+		let postorder = cfg.postorder(root, backwards);
+		let mut number = vec![None; n];
+		for (i, &id) in postorder.iter().enumerate() {
+			number[id] = Some(i);
+		}
+
+		// walks both up the tree until they meet, by postorder number
+		let intersect = |idom: &[Option<BlockId>], mut a: BlockId, mut b: BlockId| {
+			while a != b {
+				while number[a] < number[b] {
+					a = idom[a].unwrap();
+				}
+				while number[b] < number[a] {
+					b = idom[b].unwrap();
+				}
+			}
+			a
+		};
+
+		let mut idom = vec![None; n];
+		idom[root] = Some(root);
+		let mut changed = true;
+		while changed {
+			changed = false;
+			for &id in postorder.iter().rev().skip(1) {
+				let new = preds(id).iter()
+					.map(|&(pred, _)| pred)
+					.filter(|&pred| idom[pred].is_some())
+					.reduce(|a, b| intersect(&idom, a, b));
+				if new.is_some() && new != idom[id] {
+					idom[id] = new;
+					changed = true;
+				}
+			}
+		}
+
+		let mut frontiers = vec![vec![]; n];
+		for id in 0..n {
+			let preds: Vec<_> = preds(id).iter().map(|&(pred, _)| pred).filter(|&pred| idom[pred].is_some()).collect();
+			if preds.len() < 2 || idom[id].is_none() {
+				continue;
+			}
+			for pred in preds {
+				let mut runner = pred;
+				while Some(runner) != idom[id] {
+					if !frontiers[runner].contains(&id) {
+						frontiers[runner].push(id);
+					}
+					runner = idom[runner].unwrap();
+				}
+			}
+		}
+
+		idom[root] = None;
+		let mut children = vec![vec![]; n];
+		for (id, parent) in idom.iter().enumerate() {
+			if let Some(parent) = parent {
+				children[*parent].push(id);
+			}
+		}
+		Self { root, idom, children, frontiers }
+	}
+
+	pub fn immediate_dominator(&self, id: BlockId) -> Option<BlockId> {
+		self.idom[id]
+	}
+
+	pub fn reachable(&self, id: BlockId) -> bool {
+		id == self.root || self.idom[id].is_some()
+	}
+
+	// every block dominates itself
+	pub fn dominates(&self, a: BlockId, b: BlockId) -> bool {
+		if !self.reachable(b) {
+			return false;
+		}
+		let mut at = Some(b);
+		while let Some(id) = at {
+			if id == a {
+				return true;
+			}
+			at = self.idom[id];
+		}
+		false
+	}
+
+	pub fn children(&self, id: BlockId) -> &[BlockId] {
+		&self.children[id]
+	}
+
+	pub fn frontier(&self, id: BlockId) -> &[BlockId] {
+		&self.frontiers[id]
+	}
+}
@@ -1,3 +1,13 @@
 mod context;
+mod cfg;
+mod dominators;
+mod loops;
+mod liveness;
+mod reaching;
 pub mod control_flow;
-pub use context::{Context, Op};
\ No newline at end of file
+pub use context::{Context, Op};
+pub use cfg::{Cfg, BasicBlock, BlockId, EdgeKind};
+pub use dominators::DomTree;
+pub use loops::{Loops, Loop, LoopId, LoopKind};
+pub use liveness::{Liveness, Access, RegSet};
+pub use reaching::{ReachingDefs, Site};
\ No newline at end of file
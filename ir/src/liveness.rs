@@ -0,0 +1,257 @@
+// which registers hold a value that can still be read, before and after every instruction
+//
+// CALL, VARARG and the rest that leave an open number of values are taken to write everything
+// from A to the top of the stack, and whatever reads them to read that far. a call with a result
+// count still clobbers everything from A up, it's only the results that are sure to be written.
+// registers a closure
+// has captured are never taken as dead until a CLOSE or RETURN lets go of them, since the
+// closure could read them at any call
+
+use std::ops::Range;
+
+use bytecode::lua51::{instruction::{Capture, Instr, RegKst}, Proto};
+
+use crate::{BlockId, Cfg};
+use crate::context::InstructionPointer;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RegSet([u64; 4]);
+
+impl RegSet {
+	// from..to, cut off at 256
+	pub fn range(from: usize, to: usize) -> Self {
+		let mut set = Self::default();
+		(from..to.min(256)).for_each(|reg| set.insert(reg as u8));
+		set
+	}
+
+	pub fn insert(&mut self, reg: u8) {
+		self.0[reg as usize / 64] |= 1 << (reg % 64);
+	}
+
+	pub fn remove(&mut self, reg: u8) {
+		self.0[reg as usize / 64] &= !(1 << (reg % 64));
+	}
+
+	pub fn contains(&self, reg: u8) -> bool {
+		self.0[reg as usize / 64] & (1 << (reg % 64)) != 0
+	}
+
+	pub fn union(&self, other: &Self) -> Self {
+		Self(std::array::from_fn(|i| self.0[i] | other.0[i]))
+	}
+
+	pub fn difference(&self, other: &Self) -> Self {
+		Self(std::array::from_fn(|i| self.0[i] & !other.0[i]))
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.0 == [0; 4]
+	}
+
+	pub fn len(&self) -> usize {
+		self.0.iter().map(|word| word.count_ones() as usize).sum()
+	}
+
+	pub fn iter(&self) -> impl Iterator<Item = u8> + '_ {
+		(0..=255).filter(|&reg| self.contains(reg))
+	}
+}
+
+// what a single instruction reads and writes. kills are the writes that always happen, so
+// whatever was there before is gone
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Access {
+	pub uses: RegSet,
+	pub defs: RegSet,
+	pub kills: RegSet
+}
+
+impl Access {
+	pub fn of(instr: &Instr, stack: usize) -> Self {
+		let mut uses = RegSet::default();
+		let mut defs = RegSet::default();
+		let rk = |rk: &RegKst, uses: &mut RegSet| if let RegKst::R(reg) = rk {
+			uses.insert(reg.0);
+		};
+		let reg = |a: u8, n: usize| RegSet::range(a as usize, a as usize + n);
+		let above = |a: u8, by: usize| RegSet::range(a as usize + by, a as usize + by + 1);
+		let mut conditional = RegSet::default();
+
+		match instr {
+			Instr::Move(a, b) | Instr::UnOp(a, _, b) => {
+				uses.insert(b.0);
+				defs.insert(a.0);
+			},
+			Instr::LoadK(a, _) | Instr::LoadBool(a, ..) | Instr::GetUpval(a, _) | Instr::GetGlobal(a, _) | Instr::NewTable(a, ..) => defs.insert(a.0),
+			Instr::LoadNil(a, b) => defs = RegSet::range(a.0 as usize, b.0 as usize + 1),
+			Instr::GetTable(a, b, c) => {
+				uses.insert(b.0);
+				rk(c, &mut uses);
+				defs.insert(a.0);
+			},
+			Instr::SetGlobal(a, _) | Instr::SetUpval(a, _) | Instr::Test(a, _) => uses.insert(a.0),
+			Instr::SetTable(a, b, c) => {
+				uses.insert(a.0);
+				rk(b, &mut uses);
+				rk(c, &mut uses);
+			},
+			Instr::Self_(a, b, c) => {
+				uses.insert(b.0);
+				rk(c, &mut uses);
+				defs = reg(a.0, 2);
+			},
+			Instr::BinOp(a, b, _, c) => {
+				rk(b, &mut uses);
+				rk(c, &mut uses);
+				defs.insert(a.0);
+			},
+			Instr::Concat(a, b, c) => {
+				uses = RegSet::range(b.0 as usize, c.0 as usize + 1);
+				defs.insert(a.0);
+			},
+			Instr::BinCondOp(_, b, _, c) => {
+				rk(b, &mut uses);
+				rk(c, &mut uses);
+			},
+			Instr::TestSet(a, b, _) => {
+				uses.insert(b.0);
+				conditional.insert(a.0);
+			},
+			// the callee's frame starts right above the function, so nothing from A up survives
+			Instr::Call(a, b, c) | Instr::TailCall(a, b, c) => {
+				uses = if *b == 0 { RegSet::range(a.0 as usize, stack) } else { reg(a.0, *b as usize) };
+				if matches!(instr, Instr::Call(..)) {
+					defs = if *c == 0 { RegSet::range(a.0 as usize, stack) } else { reg(a.0, *c as usize - 1) };
+					conditional = RegSet::range(a.0 as usize, stack);
+				}
+			},
+			Instr::Return(a, b) => uses = if *b == 0 { RegSet::range(a.0 as usize, stack) } else { reg(a.0, *b as usize - 1) },
+			// only written when going round again, but nothing reads the loop's registers once it's done
+			Instr::ForLoop(a, _) => {
+				uses = reg(a.0, 3);
+				defs = reg(a.0, 1).union(&above(a.0, 3));
+			},
+			Instr::ForPrep(a, _) => {
+				uses = reg(a.0, 3);
+				defs.insert(a.0);
+			},
+			// the iterator is called at A+3 and clobbers everything from A+2 up. its C results are
+			// always written, A+2 takes the first unless the loop is done
+			Instr::TForLoop(a, c) => {
+				uses = reg(a.0, 3);
+				defs = RegSet::range(a.0 as usize + 3, a.0 as usize + 3 + *c as usize);
+				conditional = RegSet::range(a.0 as usize + 2, stack);
+			},
+			Instr::SetList(a, b, _) => uses = if *b == 0 { RegSet::range(a.0 as usize, stack) } else { reg(a.0, *b as usize + 1) },
+			Instr::Closure(a, _, captures) => {
+				for capture in captures {
+					if let Capture::Local(reg) = capture {
+						uses.insert(reg.0);
+					}
+				}
+				defs.insert(a.0);
+			},
+			Instr::VarArg(a, b) => defs = if *b == 0 { RegSet::range(a.0 as usize, stack) } else { reg(a.0, (*b - 1).max(0) as usize) },
+			Instr::Jump(..) | Instr::Close(_) | Instr::NOP => {}
+		}
+		Self { uses, defs: defs.union(&conditional), kills: defs }
+	}
+}
+
+// registers any closure in the proto keeps as an upvalue
+pub(crate) fn captured(proto: &Proto) -> RegSet {
+	proto.instructions.iter()
+		.flat_map(|instruction| match &instruction.1 {
+			Instr::Closure(_, _, captures) => captures.as_slice(),
+			_ => &[]
+		})
+		.fold(RegSet::default(), |mut set, capture| {
+			if let Capture::Local(reg) = capture {
+				set.insert(reg.0);
+			}
+			set
+		})
+}
+
+#[derive(Debug, Clone)]
+pub struct Liveness {
+	pub live_in: Vec<RegSet>, // per block
+	pub live_out: Vec<RegSet>,
+	after: Vec<RegSet>, // per instruction
+	access: Vec<Access>
+}
+
+impl Liveness {
+	pub fn new(cfg: &Cfg, proto: &Proto) -> Self {
+		let stack = proto.max_stack_size as usize;
+		let captured = captured(proto);
+
+		// letting go of captured registers counts as reading them
+		let access: Vec<_> = proto.instructions.iter().map(|instruction| {
+			let mut access = Access::of(&instruction.1, stack);
+			match &instruction.1 {
+				Instr::Close(a) => access.uses = access.uses.union(&captured.difference(&RegSet::range(0, a.0 as usize))),
+				Instr::Return(..) | Instr::TailCall(..) => access.uses = access.uses.union(&captured),
+				_ => {}
+			}
+			access.kills = access.kills.difference(&captured);
+			access
+		}).collect();
+
+		let through = |id: BlockId, mut live: RegSet| {
+			for pc in cfg.blocks[id].code.clone().rev() {
+				live = access[pc].uses.union(&live.difference(&access[pc].kills));
+			}
+			live
+		};
+
+		let n = cfg.blocks.len();
+		let mut live_in = vec![RegSet::default(); n];
+		let mut live_out = vec![RegSet::default(); n];
+		let mut changed = true;
+		while changed {
+			changed = false;
+			for id in (0..n).rev() {
+				let out = cfg.successors(id).fold(RegSet::default(), |set, to| set.union(&live_in[to]));
+				let new = through(id, out);
+				if new != live_in[id] || out != live_out[id] {
+					(live_in[id], live_out[id]) = (new, out);
+					changed = true;
+				}
+			}
+		}
+
+		let mut after = vec![RegSet::default(); proto.instructions.len()];
+		for (block, &out) in cfg.blocks.iter().zip(&live_out) {
+			let mut live = out;
+			for pc in block.code.clone().rev() {
+				after[pc] = live;
+				live = access[pc].uses.union(&live.difference(&access[pc].kills));
+			}
+		}
+		Self { live_in, live_out, after, access }
+	}
+
+	pub fn live_before(&self, pc: InstructionPointer) -> RegSet {
+		let access = &self.access[pc];
+		access.uses.union(&self.after[pc].difference(&access.kills))
+	}
+
+	pub fn live_after(&self, pc: InstructionPointer) -> RegSet {
+		self.after[pc]
+	}
+
+	pub fn access(&self, pc: InstructionPointer) -> &Access {
+		&self.access[pc]
+	}
+
+	// registers under the stack size that nothing reads or writes from the start of pcs to its
+	// end, so a pass can keep its own values in them over that stretch
+	pub fn dead_across(&self, pcs: Range<InstructionPointer>, stack: u8) -> RegSet {
+		let busy = pcs.fold(RegSet::default(), |set, pc| {
+			set.union(&self.live_before(pc)).union(&self.after[pc]).union(&self.access[pc].defs)
+		});
+		RegSet::range(0, stack as usize).difference(&busy)
+	}
+}
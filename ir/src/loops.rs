@@ -0,0 +1,135 @@
+// natural loops of a cfg and how they nest
+//
+// a back edge goes to a block that dominates where it comes from. numeric and generic fors are
+// entered by a jump to their FORLOOP or TFORLOOP, so that block is the header and the back edge
+// is the one coming into it from the end of the body
+
+use crate::{BlockId, Cfg, DomTree, EdgeKind};
+
+use bytecode::lua51::instruction::Instr;
+
+pub type LoopId = usize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoopKind {
+	NumericFor,
+	GenericFor,
+	While, // also repeats whose until has to close upvalues, which jump back unconditionally too
+	Repeat
+}
+
+#[derive(Debug, Clone)]
+pub struct Loop {
+	pub header: BlockId,
+	pub latches: Vec<BlockId>, // where the back edges come from
+	pub body: Vec<BlockId>, // sorted, header and nested loops included
+	pub exits: Vec<(BlockId, BlockId)>, // edges from the body to outside it
+	pub kind: LoopKind,
+	pub parent: Option<LoopId>,
+	pub children: Vec<LoopId>,
+	pub depth: usize // 1 for loops that aren't nested in another
+}
+
+#[derive(Debug, Clone)]
+pub struct Loops {
+	pub loops: Vec<Loop>,
+	pub irreducible: Vec<(BlockId, BlockId)> // edges that close a cycle without a header
+}
+
+fn kind(cfg: &Cfg, proto: &bytecode::lua51::Proto, header: BlockId, back_edges: &[EdgeKind]) -> LoopKind {
+	let code = &proto.instructions[cfg.blocks[header].code.clone()];
+	let last = code.iter().rev().take(2).map(|instruction| &instruction.1);
+	match code.last().map(|instruction| &instruction.1) {
+		Some(Instr::ForLoop(..)) => LoopKind::NumericFor,
+		_ if last.clone().any(|instr| matches!(instr, Instr::TForLoop(..))) => LoopKind::GenericFor,
+		_ if back_edges.iter().any(|kind| matches!(kind, EdgeKind::CondTrue | EdgeKind::CondFalse)) => LoopKind::Repeat,
+		_ => LoopKind::While
+	}
+}
+
+impl Loops {
+	pub fn new(cfg: &Cfg, dom: &DomTree, proto: &bytecode::lua51::Proto) -> Self {
+		let order = cfg.reverse_postorder();
+		let mut number = vec![usize::MAX; cfg.blocks.len()];
+		for (i, &id) in order.iter().enumerate() {
+			number[id] = i;
+		}
+
+		// going against the order means closing a cycle, which needs the target to dominate
+		let mut latches: Vec<(BlockId, Vec<BlockId>, Vec<EdgeKind>)> = vec![];
+		let mut irreducible = vec![];
+		for &from in &order {
+			for &(to, kind) in &cfg.blocks[from].succs {
+				if number[to] > number[from] {
+					continue;
+				}
+				if !dom.dominates(to, from) {
+					irreducible.push((from, to));
+					continue;
+				}
+				match latches.iter_mut().find(|(header, ..)| *header == to) {
+					Some((_, tails, kinds)) => {
+						tails.push(from);
+						kinds.push(kind);
+					},
+					None => latches.push((to, vec![from], vec![kind]))
+				}
+			}
+		}
+		// outer loops first
+		latches.sort_by_key(|(header, ..)| number[*header]);
+
+		let mut loops: Vec<Loop> = latches.into_iter().map(|(header, tails, kinds)| {
+			// everything that reaches a latch without going through the header
+			let mut body = vec![header];
+			let mut stack = tails.clone();
+			while let Some(id) = stack.pop() {
+				if body.contains(&id) {
+					continue;
+				}
+				body.push(id);
+				stack.extend(cfg.predecessors(id).filter(|&pred| dom.reachable(pred)));
+			}
+			body.sort();
+			let exits = body.iter()
+				.flat_map(|&id| cfg.successors(id).map(move |to| (id, to)))
+				.filter(|(_, to)| body.binary_search(to).is_err())
+				.collect();
+			Loop { header, latches: tails, body, exits, kind: kind(cfg, proto, header, &kinds), parent: None, children: vec![], depth: 1 }
+		}).collect();
+
+		// the parent is the smallest other loop holding the header
+		for id in 0..loops.len() {
+			let header = loops[id].header;
+			loops[id].parent = (0..loops.len())
+				.filter(|&other| other != id && loops[other].body.len() > loops[id].body.len() && loops[other].body.binary_search(&header).is_ok())
+				.min_by_key(|&other| loops[other].body.len());
+		}
+		for id in 0..loops.len() {
+			if let Some(parent) = loops[id].parent {
+				loops[parent].children.push(id);
+			}
+			let mut at = loops[id].parent;
+			while let Some(parent) = at {
+				loops[id].depth += 1;
+				at = loops[parent].parent;
+			}
+		}
+		Self { loops, irreducible }
+	}
+
+	pub fn roots(&self) -> impl Iterator<Item = LoopId> + '_ {
+		(0..self.loops.len()).filter(|&id| self.loops[id].parent.is_none())
+	}
+
+	// the most deeply nested loop the block is in
+	pub fn innermost(&self, block: BlockId) -> Option<LoopId> {
+		(0..self.loops.len())
+			.filter(|&id| self.loops[id].body.binary_search(&block).is_ok())
+			.max_by_key(|&id| self.loops[id].depth)
+	}
+
+	pub fn is_reducible(&self) -> bool {
+		self.irreducible.is_empty()
+	}
+}
@@ -0,0 +1,148 @@
+// reaching definitions, and the def-use and use-def chains that come out of them
+//
+// writes that may not happen, like TESTSET's or a call's above its results, add a definition
+// without getting rid of the ones before. registers a closure has captured can be written through
+// its upvalue by anything that runs lua code, so every call and everything that can hit a
+// metamethod counts as a possible definition of them too
+
+use std::{collections::{BTreeMap, BTreeSet}, ops::Range};
+
+use bytecode::lua51::{instruction::{Instr, UnOp}, Proto};
+
+use crate::{Access, Cfg, RegSet};
+use crate::context::InstructionPointer;
+use crate::liveness::captured;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Site {
+	Entry, // whatever the register held when the proto was called, the parameters or nil
+	Pc(InstructionPointer)
+}
+
+type State = Vec<BTreeSet<Site>>; // indexed by register
+
+#[derive(Debug, Clone)]
+pub struct ReachingDefs {
+	block_in: Vec<State>,
+	access: Vec<Access>,
+	use_def: BTreeMap<(InstructionPointer, u8), BTreeSet<Site>>,
+	def_use: BTreeMap<Site, Vec<(InstructionPointer, u8)>>,
+	code: Vec<Range<InstructionPointer>> // of each block
+}
+
+// calls, and whatever can end up in a metamethod. SETLIST is raw but kept in to be safe
+fn may_call(instr: &Instr) -> bool {
+	match instr {
+		Instr::UnOp(_, op, _) => !matches!(op, UnOp::Not),
+		Instr::Call(..)
+		| Instr::TailCall(..)
+		| Instr::TForLoop(..)
+		| Instr::GetTable(..)
+		| Instr::SetTable(..)
+		| Instr::Self_(..)
+		| Instr::BinOp(..)
+		| Instr::Concat(..)
+		| Instr::BinCondOp(..)
+		| Instr::GetGlobal(..)
+		| Instr::SetGlobal(..)
+		| Instr::SetList(..) => true,
+		_ => false
+	}
+}
+
+fn step(state: &mut State, pc: InstructionPointer, access: &Access) {
+	for reg in access.kills.iter() {
+		state[reg as usize] = BTreeSet::from([Site::Pc(pc)]);
+	}
+	for reg in access.defs.difference(&access.kills).iter() {
+		state[reg as usize].insert(Site::Pc(pc));
+	}
+}
+
+impl ReachingDefs {
+	pub fn new(cfg: &Cfg, proto: &Proto) -> Self {
+		let stack = proto.max_stack_size as usize;
+		let captured = captured(proto);
+		let access: Vec<_> = proto.instructions.iter().map(|instruction| {
+			let mut access = Access::of(&instruction.1, stack);
+			if may_call(&instruction.1) {
+				access.defs = access.defs.union(&captured);
+			}
+			access
+		}).collect();
+
+		let n = cfg.blocks.len();
+		let empty: State = vec![BTreeSet::new(); 256];
+		let mut block_in = vec![empty.clone(); n];
+		let mut block_out = vec![empty.clone(); n];
+		block_out[cfg.entry] = vec![BTreeSet::from([Site::Entry]); 256];
+		let order = cfg.reverse_postorder();
+		let mut changed = true;
+		while changed {
+			changed = false;
+			for &id in &order {
+				if id == cfg.entry {
+					continue;
+				}
+				let mut state = empty.clone();
+				for pred in cfg.predecessors(id) {
+					for (defs, from) in state.iter_mut().zip(&block_out[pred]) {
+						defs.extend(from);
+					}
+				}
+				block_in[id] = state.clone();
+				for pc in cfg.blocks[id].code.clone() {
+					step(&mut state, pc, &access[pc]);
+				}
+				if state != block_out[id] {
+					block_out[id] = state;
+					changed = true;
+				}
+			}
+		}
+
+		let mut use_def = BTreeMap::new();
+		let mut def_use: BTreeMap<Site, Vec<_>> = BTreeMap::new();
+		for (block, state) in cfg.blocks.iter().zip(&block_in) {
+			let mut state = state.clone();
+			for pc in block.code.clone() {
+				for reg in access[pc].uses.iter() {
+					let defs = state[reg as usize].clone();
+					for &site in &defs {
+						def_use.entry(site).or_default().push((pc, reg));
+					}
+					use_def.insert((pc, reg), defs);
+				}
+				step(&mut state, pc, &access[pc]);
+			}
+		}
+		let code = cfg.blocks.iter().map(|block| block.code.clone()).collect();
+		Self { block_in, access, use_def, def_use, code }
+	}
+
+	// the definitions of reg that can reach pc, before it runs. empty where pc can't be reached
+	pub fn reaching(&self, pc: InstructionPointer, reg: u8) -> BTreeSet<Site> {
+		let Some(id) = self.code.iter().position(|code| code.contains(&pc)) else {
+			return BTreeSet::new();
+		};
+		let mut state = self.block_in[id].clone();
+		for at in self.code[id].start..pc {
+			step(&mut state, at, &self.access[at]);
+		}
+		std::mem::take(&mut state[reg as usize])
+	}
+
+	// where the value pc reads from reg came from, None if pc doesn't read reg
+	pub fn use_def(&self, pc: InstructionPointer, reg: u8) -> Option<&BTreeSet<Site>> {
+		self.use_def.get(&(pc, reg))
+	}
+
+	// every (pc, reg) read that a definition can reach
+	pub fn def_use(&self, site: Site) -> &[(InstructionPointer, u8)] {
+		self.def_use.get(&site).map_or(&[], |uses| uses.as_slice())
+	}
+
+	pub fn defs(&self, pc: InstructionPointer) -> RegSet {
+		self.access[pc].defs
+	}
+}
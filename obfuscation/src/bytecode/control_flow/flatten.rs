@@ -1,4 +1,4 @@
-use bytecode::lua51::{Proto, Constants, instruction::{Opcode, Instr, Instruction, Reg, RegKst, Kst, Opmode, BinCondOp, BinOp}};
+use bytecode::lua51::{Proto, Constants, instruction::{Opcode, Instr, Instruction, Reg, RegKst, Kst, Capture, BinCondOp, BinOp}};
 use ir::{Context, control_flow::{self, Block}};
 use super::registers::Registers;
 use crate::{Debug, bytecode::Options};
@@ -29,83 +29,79 @@ pub fn get_block_from_jump(blocks: &Vec<Block>, ip: usize, offset: i32, current_
 	})
 }
 
-pub fn update_register_offsets(closure: &mut Proto, registers: &mut Registers) {
-	let mut instr_iter = closure.instructions.iter_mut();
-	while let Some(instr) = instr_iter.next() {
-		// update all registers to be unique to each closure
-		match &mut instr.2 {
-			Opmode::iABC(a, _b, _c) => {
-
-				match instr.1 {
-					Instr::BinCondOp(..) => {}
-					_ => {
-						*a = registers.get(*a as usize).try_into().expect("");
-						instr.1 = Instr::from_opmode(instr.0, instr.2);
-					}
-				}
+fn remap(registers: &mut Registers, reg: &mut Reg) {
+	reg.0 = registers.get(reg.0 as usize).try_into().expect("");
+}
 
-				match &mut instr.1 {
-					Instr::NewTable(_, b, c)
-					| Instr::Concat(_, b, c) => {
-						b.0 = registers.get(b.0 as usize).try_into().expect("");
-						c.0 = registers.get(c.0 as usize).try_into().expect("");
-					}
-					Instr::Move(_, b)
-					| Instr::LoadNil(_, b) => {
-						b.0 = registers.get(b.0 as usize).try_into().expect("");
-					}
-					Instr::SetTable(_, b, crk) => {
-						if let RegKst::R(rb) = b {
-							rb.0 = registers.get(rb.0 as usize).try_into().expect("");
-						}
-						if let RegKst::R(rb) = crk {
-							rb.0 = registers.get(rb.0 as usize).try_into().expect("");
-						}
-					}
-					Instr::GetTable(_, b, crk)
-					| Instr::Self_(_, b, crk) => {
-						b.0 = registers.get(b.0 as usize).try_into().expect("");
-						if let RegKst::R(rb) = crk {
-							rb.0 = registers.get(rb.0 as usize).try_into().expect("");
-						}
-					}
-					Instr::BinOp(_, brk, _, crk) => {
-						if let RegKst::R(rb) = brk {
-							rb.0 = registers.get(rb.0 as usize).try_into().expect("");
-						}
-						if let RegKst::R(rb) = crk {
-							rb.0 = registers.get(rb.0 as usize).try_into().expect("");
-						}
-					}
-					Instr::UnOp(_, _, b) => {
-						b.0 = registers.get(b.0 as usize).try_into().expect("");
-					}
-					Instr::BinCondOp(_, brk, _, crk) => {
-						if let RegKst::R(rb) = brk {
-							rb.0 = registers.get(rb.0 as usize).try_into().expect("");
-						}
-						if let RegKst::R(rb) = crk {
-							rb.0 = registers.get(rb.0 as usize).try_into().expect("");
-						}
-					}
-					Instr::TestSet(_, b, _) => {
-						b.0 = registers.get(b.0 as usize).try_into().expect("");
-					}
-					_ => {}
-				}
+fn remap_rk(registers: &mut Registers, rk: &mut RegKst) {
+	if let RegKst::R(reg) = rk {
+		remap(registers, reg);
+	}
+}
 
-				instr.2 = instr.1.get_opmode();
+pub fn update_register_offsets(closure: &mut Proto, registers: &mut Registers) {
+	for instr in closure.instructions.iter_mut() {
+		// update all registers to be unique to each closure. done on the instr itself so CLOSURE
+		// keeps its captures and SETLIST its count word
+		match &mut instr.1 {
+			Instr::Move(a, b)
+			| Instr::LoadNil(a, b)
+			| Instr::UnOp(a, _, b)
+			| Instr::TestSet(a, b, _) => {
+				remap(registers, a);
+				remap(registers, b);
+			}
+			Instr::Concat(a, b, c) => {
+				remap(registers, a);
+				remap(registers, b);
+				remap(registers, c);
+			}
+			Instr::GetTable(a, b, crk)
+			| Instr::Self_(a, b, crk) => {
+				remap(registers, a);
+				remap(registers, b);
+				remap_rk(registers, crk);
+			}
+			Instr::SetTable(a, brk, crk)
+			| Instr::BinOp(a, brk, _, crk) => {
+				remap(registers, a);
+				remap_rk(registers, brk);
+				remap_rk(registers, crk);
 			}
-			Opmode::iABx(a, _bx) => {
-				*a = registers.get(*a as usize).try_into().expect("");
-				instr.1 = Instr::from_opmode(instr.0, instr.2);
+			Instr::BinCondOp(_, brk, _, crk) => {
+				remap_rk(registers, brk);
+				remap_rk(registers, crk);
+			}
+			Instr::Closure(a, _, captures) => {
+				remap(registers, a);
+				for capture in captures {
+					if let Capture::Local(reg) = capture {
+						remap(registers, reg);
+					}
+				}
 			}
-			Opmode::iAsBx(a, _sbx) => {
-				*a = registers.get(*a as usize).try_into().expect("");
-				instr.1 = Instr::from_opmode(instr.0, instr.2);
-			},
-			Opmode::NOP => {}
+			// B and C of NEWTABLE are size hints, not registers
+			Instr::LoadK(a, _)
+			| Instr::LoadBool(a, ..)
+			| Instr::GetUpval(a, _)
+			| Instr::GetGlobal(a, _)
+			| Instr::SetGlobal(a, _)
+			| Instr::SetUpval(a, _)
+			| Instr::NewTable(a, ..)
+			| Instr::Jump(a, _)
+			| Instr::Test(a, _)
+			| Instr::Call(a, ..)
+			| Instr::TailCall(a, ..)
+			| Instr::Return(a, _)
+			| Instr::ForLoop(a, _)
+			| Instr::ForPrep(a, _)
+			| Instr::TForLoop(a, _)
+			| Instr::SetList(a, ..)
+			| Instr::Close(a)
+			| Instr::VarArg(a, _) => remap(registers, a),
+			Instr::NOP => {}
 		}
+		instr.2 = instr.1.get_opmode();
 	}
 }
 
@@ -213,7 +209,7 @@ pub fn flatten(ctx: &Context, c: &Proto, options: &Options) -> Context {
 
 						// 
 						let zero = flat_ctx.get_or_add_constant(Constants::Number(0f64));
-						add(Instruction::new(Opcode::Lt, Instr::BinCondOp(true, RegKst::R(Reg(a.0 + 2)), BinCondOp::Lt, RegKst::K(Kst(256 + zero)))));
+						add(Instruction::new(Opcode::Lt, Instr::BinCondOp(true, RegKst::R(Reg(a.0 + 2)), BinCondOp::Lt, RegKst::K(Kst(zero)))));
 						// jump else statement
 						add(Instruction::new(Opcode::Jump, Instr::Jump(state_reg, 7)));
 						// if Index <= Stk[A + 1]
@@ -358,7 +354,7 @@ fn finalize_closure(flat_ctx: &mut Context, state_reg: Reg, last_block: i32, blo
 		let block_pt_kst = flat_ctx.get_or_add_constant(Constants::Number(block_pt as f64));
 		// if statement
 		flat_ctx.add_instruction(flat_ctx.get_max_ip(), 
-		 Instruction::new(Opcode::Eq, Instr::BinCondOp(false, RegKst::R(state_reg), BinCondOp::Eq, RegKst::K(Kst(256 + block_pt_kst))))
+		 Instruction::new(Opcode::Eq, Instr::BinCondOp(false, RegKst::R(state_reg), BinCondOp::Eq, RegKst::K(Kst(block_pt_kst))))
 		);
 		// corresponding jump
 		flat_ctx.add_instruction(flat_ctx.get_max_ip(), 
@@ -373,7 +369,7 @@ fn finalize_closure(flat_ctx: &mut Context, state_reg: Reg, last_block: i32, blo
 	let ending_target = flat_ctx.get_or_add_constant(Constants::Number(-1f64));
 
 	flat_ctx.add_instruction(flat_ctx.get_max_ip(), 
-	 Instruction::new(Opcode::Eq, Instr::BinCondOp(false, RegKst::R(state_reg), BinCondOp::Eq, RegKst::K(Kst(256 + next_block))))
+	 Instruction::new(Opcode::Eq, Instr::BinCondOp(false, RegKst::R(state_reg), BinCondOp::Eq, RegKst::K(Kst(next_block))))
 	);
 	flat_ctx.add_instruction(flat_ctx.get_max_ip(), 
 	 Instruction::new(Opcode::Jump, Instr::Jump(state_reg, 1))
@@ -389,7 +385,7 @@ fn finalize_closure(flat_ctx: &mut Context, state_reg: Reg, last_block: i32, blo
 	);
 
 	let binop_pt = flat_ctx.add_instruction(1, // GE than 0
-	Instruction::new(Opcode::Le, Instr::BinCondOp(false, RegKst::K(Kst(256 + entry)), BinCondOp::Le, RegKst::R(state_reg)))
+	Instruction::new(Opcode::Le, Instr::BinCondOp(false, RegKst::K(Kst(entry)), BinCondOp::Le, RegKst::R(state_reg)))
 	);
 
 	let mut this = flat_ctx.get_max_ip();
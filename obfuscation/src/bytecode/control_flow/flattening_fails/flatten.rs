@@ -225,12 +225,12 @@ pub fn flatten(ctx: &Context, c: &Proto) -> Context {
 		let mut target_block = next_target;
 		let mut add_target = true;
 
-		debug.if_statement(RegKst::R(block_pointer_r), BinCondOp::Eq, RegKst::K(Kst(256 + ip_c)));
+		debug.if_statement(RegKst::R(block_pointer_r), BinCondOp::Eq, RegKst::K(Kst(ip_c)));
 
 		let mut this = flat_ctx.get_max_ip();
 		flat_ctx.add_instruction(this, 
 			// ip is located in block_pointer_r, and we're using this instruction pointer as an instruction pointer jump sorta
-		 Instruction::new(Opcode::Eq, Instr::BinCondOp(false, RegKst::R(block_pointer_r), BinCondOp::Eq, RegKst::K(Kst(256 + ip_c))))
+		 Instruction::new(Opcode::Eq, Instr::BinCondOp(false, RegKst::R(block_pointer_r), BinCondOp::Eq, RegKst::K(Kst(ip_c))))
 		);
 		this += 1; // may need to make this two or 0
 		let d1 = flat_ctx.get_max_ip();
@@ -298,7 +298,7 @@ pub fn flatten(ctx: &Context, c: &Proto) -> Context {
 
 						// 
 						let zero = flat_ctx.get_or_add_constant(Constants::Number(0f64));
-						flat_ctx.add_instruction(flat_ctx.get_max_ip(), Instruction::new(Opcode::Lt, Instr::BinCondOp(true, RegKst::R(Reg(a.0 + 2)), BinCondOp::Lt, RegKst::K(Kst(256 + zero)))));
+						flat_ctx.add_instruction(flat_ctx.get_max_ip(), Instruction::new(Opcode::Lt, Instr::BinCondOp(true, RegKst::R(Reg(a.0 + 2)), BinCondOp::Lt, RegKst::K(Kst(zero)))));
 						// jump else statement
 						flat_ctx.add_instruction(flat_ctx.get_max_ip(), Instruction::new(Opcode::Jump, Instr::Jump(block_pointer_r, 7))); // 7, was 5?
 						// if Index <= Stk[A + 1]
@@ -469,9 +469,9 @@ pub fn flatten(ctx: &Context, c: &Proto) -> Context {
 
 	let aaa = flat_ctx.get_or_add_constant(Constants::Number(last_block as f64 + 1f64));
 	let aaaa = flat_ctx.get_or_add_constant(Constants::Number(-1f64));
-	debug.if_statement(RegKst::R(block_pointer_r), BinCondOp::Eq, RegKst::K(Kst(256 + aaa)));
+	debug.if_statement(RegKst::R(block_pointer_r), BinCondOp::Eq, RegKst::K(Kst(aaa)));
 	flat_ctx.add_instruction(flat_ctx.get_max_ip(), 
-	 Instruction::new(Opcode::Eq, Instr::BinCondOp(false, RegKst::R(block_pointer_r), BinCondOp::Eq, RegKst::K(Kst(256 + aaa))))
+	 Instruction::new(Opcode::Eq, Instr::BinCondOp(false, RegKst::R(block_pointer_r), BinCondOp::Eq, RegKst::K(Kst(aaa))))
 	);
 	flat_ctx.add_instruction(flat_ctx.get_max_ip(), 
 	 Instruction::new(Opcode::Jump, Instr::Jump(block_pointer_r, 1))
@@ -490,7 +490,7 @@ pub fn flatten(ctx: &Context, c: &Proto) -> Context {
 	);
 
 	let binop_pt = flat_ctx.add_instruction(1, // GE than 0
-	Instruction::new(Opcode::Le, Instr::BinCondOp(false, RegKst::K(Kst(256 + entry)), BinCondOp::Le, RegKst::R(block_pointer_r)))
+	Instruction::new(Opcode::Le, Instr::BinCondOp(false, RegKst::K(Kst(entry)), BinCondOp::Le, RegKst::R(block_pointer_r)))
 	);
 
 	let mut this = flat_ctx.get_max_ip();
@@ -401,7 +401,7 @@ pub fn flatten(flat_ctx: &mut Context, c: &Proto) -> u32 {
 				| Instr::GetTable(_, _, c) => {
 					match c {
 						RegKst::K(kst) => {
-							let k = closure.constants[kst.0 as usize - 255].clone();
+							let k = closure.constants[kst.0 as usize].clone();
 							kst.0 = flat_ctx.get_or_add_constant(k) as u32;
 						}
 						_ => {}
@@ -417,14 +417,14 @@ pub fn flatten(flat_ctx: &mut Context, c: &Proto) -> u32 {
 				| Instr::BinCondOp(_, a, _, c) => {
 					match a {
 						RegKst::K(kst) => {
-							let k = closure.constants[kst.0 as usize - 256].clone();
+							let k = closure.constants[kst.0 as usize].clone();
 							kst.0 = flat_ctx.get_or_add_constant(k) as u32;
 						}
 						_ => {}
 					}
 					match c {
 						RegKst::K(kst) => {
-							let k = closure.constants[kst.0 as usize - 256].clone();
+							let k = closure.constants[kst.0 as usize].clone();
 							kst.0 = flat_ctx.get_or_add_constant(k) as u32;
 						}
 						_ => {}
@@ -450,12 +450,12 @@ pub fn flatten(flat_ctx: &mut Context, c: &Proto) -> u32 {
 			let mut target_block = next_target;
 			let mut add_target = true;
 
-			debug.if_statement(RegKst::R(block_pointer_r), BinCondOp::Eq, RegKst::K(Kst(256 + ip_c)));
+			debug.if_statement(RegKst::R(block_pointer_r), BinCondOp::Eq, RegKst::K(Kst(ip_c)));
 
 			let mut this = flat_ctx.get_max_ip();
 			flat_ctx.add_instruction(this, 
 				// ip is located in block_pointer_r, and we're using this instruction pointer as an instruction pointer jump sorta
-			 Instruction::new(Opcode::Eq, Instr::BinCondOp(false, RegKst::R(block_pointer_r), BinCondOp::Eq, RegKst::K(Kst(256 + ip_c))))
+			 Instruction::new(Opcode::Eq, Instr::BinCondOp(false, RegKst::R(block_pointer_r), BinCondOp::Eq, RegKst::K(Kst(ip_c))))
 			);
 			this += 1; // may need to make this two or 0
 			let mut code_size = block.code.len() + 1;
@@ -521,7 +521,7 @@ pub fn flatten(flat_ctx: &mut Context, c: &Proto) -> u32 {
 							let kst = Kst(flat_ctx.get_or_add_constant(Constants::Number( next_target )));
 							println!("--- return to kst {:?}", kst);
 							flat_ctx.add_instruction(flat_ctx.get_max_ip(), Instruction::new(Opcode::GetGlobal, Instr::GetGlobal(queue_1, table_kst)));
-							flat_ctx.add_instruction(flat_ctx.get_max_ip(), Instruction::new(Opcode::GetTable, Instr::GetTable(queue_1, queue_1, RegKst::K(Kst(256 + table_kst3.0)))));
+							flat_ctx.add_instruction(flat_ctx.get_max_ip(), Instruction::new(Opcode::GetTable, Instr::GetTable(queue_1, queue_1, RegKst::K(Kst(table_kst3.0)))));
 							flat_ctx.add_instruction(flat_ctx.get_max_ip(), Instruction::new(Opcode::Move, Instr::Move(queue_2, Reg(1))));
 							flat_ctx.add_instruction(flat_ctx.get_max_ip(), Instruction::new(Opcode::LoadK, Instr::LoadK(queue_3, kst)));
 							flat_ctx.add_instruction(flat_ctx.get_max_ip(), Instruction::new(Opcode::Call, Instr::Call(queue_1, 3, 1)));
@@ -589,7 +589,7 @@ pub fn flatten(flat_ctx: &mut Context, c: &Proto) -> u32 {
 
 							// 
 							let zero = flat_ctx.get_or_add_constant(Constants::Number(0f64));
-							flat_ctx.add_instruction(flat_ctx.get_max_ip(), Instruction::new(Opcode::Lt, Instr::BinCondOp(true, RegKst::R(Reg(a.0 + 2)), BinCondOp::Lt, RegKst::K(Kst(256 + zero)))));
+							flat_ctx.add_instruction(flat_ctx.get_max_ip(), Instruction::new(Opcode::Lt, Instr::BinCondOp(true, RegKst::R(Reg(a.0 + 2)), BinCondOp::Lt, RegKst::K(Kst(zero)))));
 							// jump else statement
 							flat_ctx.add_instruction(flat_ctx.get_max_ip(), Instruction::new(Opcode::Jump, Instr::Jump(block_pointer_r, 7))); // 7, was 5?
 							// if Index <= Stk[A + 1]
@@ -664,21 +664,21 @@ pub fn flatten(flat_ctx: &mut Context, c: &Proto) -> u32 {
 		let i1 = flat_ctx.get_or_add_constant(Constants::Number((this_id + (blocks.len() as i32)) as f64));
 		flat_ctx.add_instruction(flat_ctx.get_max_ip(), 
 			// ip is located in block_pointer_r, and we're using this instruction pointer as an instruction pointer jump sorta
-		 Instruction::new(Opcode::Eq, Instr::BinCondOp(false, RegKst::R(block_pointer_r), BinCondOp::Eq, RegKst::K(Kst(256 + i1))))
+		 Instruction::new(Opcode::Eq, Instr::BinCondOp(false, RegKst::R(block_pointer_r), BinCondOp::Eq, RegKst::K(Kst(i1))))
 		);
 		flat_ctx.add_instruction(flat_ctx.get_max_ip(), Instruction::new(Opcode::Jump, Instr::Jump(block_pointer_r, 9)));
 		
 		flat_ctx.add_instruction(flat_ctx.get_max_ip(), Instruction::new(Opcode::Len, Instr::UnOp(queue_4, UnOp::Len, Reg(1))));
 		let zero = flat_ctx.get_or_add_constant(Constants::Number(0f64));
 		flat_ctx.add_instruction(flat_ctx.get_max_ip(), 
-		 Instruction::new(Opcode::Lt, Instr::BinCondOp(false, RegKst::K(Kst(256 + zero)), BinCondOp::Lt, RegKst::R(queue_4)))
+		 Instruction::new(Opcode::Lt, Instr::BinCondOp(false, RegKst::K(Kst(zero)), BinCondOp::Lt, RegKst::R(queue_4)))
 		);
 		flat_ctx.add_instruction(flat_ctx.get_max_ip(), Instruction::new(Opcode::Jump, Instr::Jump(block_pointer_r, 6)));
 
 		//
-		flat_ctx.add_instruction(flat_ctx.get_max_ip(), Instruction::new(Opcode::GetTable, Instr::GetTable(block_pointer_r, Reg(1), RegKst::K(Kst(256 + table_kst4.0)))));
+		flat_ctx.add_instruction(flat_ctx.get_max_ip(), Instruction::new(Opcode::GetTable, Instr::GetTable(block_pointer_r, Reg(1), RegKst::K(Kst(table_kst4.0)))));
 		flat_ctx.add_instruction(flat_ctx.get_max_ip(), Instruction::new(Opcode::GetGlobal, Instr::GetGlobal(queue_1, table_kst)));
-		flat_ctx.add_instruction(flat_ctx.get_max_ip(), Instruction::new(Opcode::GetTable, Instr::GetTable(queue_1, queue_1, RegKst::K(Kst(256 + table_kst2.0)))));
+		flat_ctx.add_instruction(flat_ctx.get_max_ip(), Instruction::new(Opcode::GetTable, Instr::GetTable(queue_1, queue_1, RegKst::K(Kst(table_kst2.0)))));
 		flat_ctx.add_instruction(flat_ctx.get_max_ip(), Instruction::new(Opcode::Move, Instr::Move(queue_2, Reg(1))));
 		flat_ctx.add_instruction(flat_ctx.get_max_ip(), Instruction::new(Opcode::LoadK, Instr::LoadK(queue_3, table_kst4)));
 		flat_ctx.add_instruction(flat_ctx.get_max_ip(), Instruction::new(Opcode::Call, Instr::Call(queue_1, 3, 1)));
@@ -734,7 +734,7 @@ impl Flatten {
 		);
 
 		let binop_pt = ctx.add_instruction(2, // GE than 0
-		Instruction::new(Opcode::Le, Instr::BinCondOp(false, RegKst::K(Kst(256 + entry)), BinCondOp::Le, RegKst::R(block_pointer_r)))
+		Instruction::new(Opcode::Le, Instr::BinCondOp(false, RegKst::K(Kst(entry)), BinCondOp::Le, RegKst::R(block_pointer_r)))
 		);
 		self.pointers.insert("while_binop".to_string(), binop_pt);
  
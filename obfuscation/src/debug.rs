@@ -11,14 +11,7 @@ enum DebugValue {
 
 fn get_v_from_rk<'a>(rk: &RegKst, ksts: &'a Vec<Constants>) -> String {
 	match rk {
-		RegKst::K(kst) => {
-			let v = rk.get();
-			if v > 0xff {
-				format!("{:?}", &ksts[(v - 0x100) as usize])
-			} else {
-				format!("{:?}", &ksts[v as usize]) // should never happen
-			}
-		},
+		RegKst::K(kst) => format!("{:?}", &ksts[kst.0 as usize]),
 		RegKst::R(r) => {
 			format!("{:?}", r)
 		}
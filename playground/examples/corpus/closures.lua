@@ -0,0 +1,20 @@
+local function counter(start)
+	local n = start
+	return function(step)
+		n = n + (step or 1)
+		return n
+	end, function()
+		n = start
+	end
+end
+
+local inc, reset = counter(10)
+inc()
+inc(5)
+reset()
+
+for i = 1, 3 do
+	local captured = i * 2
+	local f = function() return captured + i end
+	print(f())
+end
@@ -0,0 +1,402 @@
+local t = {}
+t["key_0"] = 0.5 + t.x
+t["key_1"] = 1.5 + t.x
+t["key_2"] = 2.5 + t.x
+t["key_3"] = 3.5 + t.x
+t["key_4"] = 4.5 + t.x
+t["key_5"] = 5.5 + t.x
+t["key_6"] = 6.5 + t.x
+t["key_7"] = 7.5 + t.x
+t["key_8"] = 8.5 + t.x
+t["key_9"] = 9.5 + t.x
+t["key_10"] = 10.5 + t.x
+t["key_11"] = 11.5 + t.x
+t["key_12"] = 12.5 + t.x
+t["key_13"] = 13.5 + t.x
+t["key_14"] = 14.5 + t.x
+t["key_15"] = 15.5 + t.x
+t["key_16"] = 16.5 + t.x
+t["key_17"] = 17.5 + t.x
+t["key_18"] = 18.5 + t.x
+t["key_19"] = 19.5 + t.x
+t["key_20"] = 20.5 + t.x
+t["key_21"] = 21.5 + t.x
+t["key_22"] = 22.5 + t.x
+t["key_23"] = 23.5 + t.x
+t["key_24"] = 24.5 + t.x
+t["key_25"] = 25.5 + t.x
+t["key_26"] = 26.5 + t.x
+t["key_27"] = 27.5 + t.x
+t["key_28"] = 28.5 + t.x
+t["key_29"] = 29.5 + t.x
+t["key_30"] = 30.5 + t.x
+t["key_31"] = 31.5 + t.x
+t["key_32"] = 32.5 + t.x
+t["key_33"] = 33.5 + t.x
+t["key_34"] = 34.5 + t.x
+t["key_35"] = 35.5 + t.x
+t["key_36"] = 36.5 + t.x
+t["key_37"] = 37.5 + t.x
+t["key_38"] = 38.5 + t.x
+t["key_39"] = 39.5 + t.x
+t["key_40"] = 40.5 + t.x
+t["key_41"] = 41.5 + t.x
+t["key_42"] = 42.5 + t.x
+t["key_43"] = 43.5 + t.x
+t["key_44"] = 44.5 + t.x
+t["key_45"] = 45.5 + t.x
+t["key_46"] = 46.5 + t.x
+t["key_47"] = 47.5 + t.x
+t["key_48"] = 48.5 + t.x
+t["key_49"] = 49.5 + t.x
+t["key_50"] = 50.5 + t.x
+t["key_51"] = 51.5 + t.x
+t["key_52"] = 52.5 + t.x
+t["key_53"] = 53.5 + t.x
+t["key_54"] = 54.5 + t.x
+t["key_55"] = 55.5 + t.x
+t["key_56"] = 56.5 + t.x
+t["key_57"] = 57.5 + t.x
+t["key_58"] = 58.5 + t.x
+t["key_59"] = 59.5 + t.x
+t["key_60"] = 60.5 + t.x
+t["key_61"] = 61.5 + t.x
+t["key_62"] = 62.5 + t.x
+t["key_63"] = 63.5 + t.x
+t["key_64"] = 64.5 + t.x
+t["key_65"] = 65.5 + t.x
+t["key_66"] = 66.5 + t.x
+t["key_67"] = 67.5 + t.x
+t["key_68"] = 68.5 + t.x
+t["key_69"] = 69.5 + t.x
+t["key_70"] = 70.5 + t.x
+t["key_71"] = 71.5 + t.x
+t["key_72"] = 72.5 + t.x
+t["key_73"] = 73.5 + t.x
+t["key_74"] = 74.5 + t.x
+t["key_75"] = 75.5 + t.x
+t["key_76"] = 76.5 + t.x
+t["key_77"] = 77.5 + t.x
+t["key_78"] = 78.5 + t.x
+t["key_79"] = 79.5 + t.x
+t["key_80"] = 80.5 + t.x
+t["key_81"] = 81.5 + t.x
+t["key_82"] = 82.5 + t.x
+t["key_83"] = 83.5 + t.x
+t["key_84"] = 84.5 + t.x
+t["key_85"] = 85.5 + t.x
+t["key_86"] = 86.5 + t.x
+t["key_87"] = 87.5 + t.x
+t["key_88"] = 88.5 + t.x
+t["key_89"] = 89.5 + t.x
+t["key_90"] = 90.5 + t.x
+t["key_91"] = 91.5 + t.x
+t["key_92"] = 92.5 + t.x
+t["key_93"] = 93.5 + t.x
+t["key_94"] = 94.5 + t.x
+t["key_95"] = 95.5 + t.x
+t["key_96"] = 96.5 + t.x
+t["key_97"] = 97.5 + t.x
+t["key_98"] = 98.5 + t.x
+t["key_99"] = 99.5 + t.x
+t["key_100"] = 100.5 + t.x
+t["key_101"] = 101.5 + t.x
+t["key_102"] = 102.5 + t.x
+t["key_103"] = 103.5 + t.x
+t["key_104"] = 104.5 + t.x
+t["key_105"] = 105.5 + t.x
+t["key_106"] = 106.5 + t.x
+t["key_107"] = 107.5 + t.x
+t["key_108"] = 108.5 + t.x
+t["key_109"] = 109.5 + t.x
+t["key_110"] = 110.5 + t.x
+t["key_111"] = 111.5 + t.x
+t["key_112"] = 112.5 + t.x
+t["key_113"] = 113.5 + t.x
+t["key_114"] = 114.5 + t.x
+t["key_115"] = 115.5 + t.x
+t["key_116"] = 116.5 + t.x
+t["key_117"] = 117.5 + t.x
+t["key_118"] = 118.5 + t.x
+t["key_119"] = 119.5 + t.x
+t["key_120"] = 120.5 + t.x
+t["key_121"] = 121.5 + t.x
+t["key_122"] = 122.5 + t.x
+t["key_123"] = 123.5 + t.x
+t["key_124"] = 124.5 + t.x
+t["key_125"] = 125.5 + t.x
+t["key_126"] = 126.5 + t.x
+t["key_127"] = 127.5 + t.x
+t["key_128"] = 128.5 + t.x
+t["key_129"] = 129.5 + t.x
+t["key_130"] = 130.5 + t.x
+t["key_131"] = 131.5 + t.x
+t["key_132"] = 132.5 + t.x
+t["key_133"] = 133.5 + t.x
+t["key_134"] = 134.5 + t.x
+t["key_135"] = 135.5 + t.x
+t["key_136"] = 136.5 + t.x
+t["key_137"] = 137.5 + t.x
+t["key_138"] = 138.5 + t.x
+t["key_139"] = 139.5 + t.x
+t["key_140"] = 140.5 + t.x
+t["key_141"] = 141.5 + t.x
+t["key_142"] = 142.5 + t.x
+t["key_143"] = 143.5 + t.x
+t["key_144"] = 144.5 + t.x
+t["key_145"] = 145.5 + t.x
+t["key_146"] = 146.5 + t.x
+t["key_147"] = 147.5 + t.x
+t["key_148"] = 148.5 + t.x
+t["key_149"] = 149.5 + t.x
+t["key_150"] = 150.5 + t.x
+t["key_151"] = 151.5 + t.x
+t["key_152"] = 152.5 + t.x
+t["key_153"] = 153.5 + t.x
+t["key_154"] = 154.5 + t.x
+t["key_155"] = 155.5 + t.x
+t["key_156"] = 156.5 + t.x
+t["key_157"] = 157.5 + t.x
+t["key_158"] = 158.5 + t.x
+t["key_159"] = 159.5 + t.x
+t["key_160"] = 160.5 + t.x
+t["key_161"] = 161.5 + t.x
+t["key_162"] = 162.5 + t.x
+t["key_163"] = 163.5 + t.x
+t["key_164"] = 164.5 + t.x
+t["key_165"] = 165.5 + t.x
+t["key_166"] = 166.5 + t.x
+t["key_167"] = 167.5 + t.x
+t["key_168"] = 168.5 + t.x
+t["key_169"] = 169.5 + t.x
+t["key_170"] = 170.5 + t.x
+t["key_171"] = 171.5 + t.x
+t["key_172"] = 172.5 + t.x
+t["key_173"] = 173.5 + t.x
+t["key_174"] = 174.5 + t.x
+t["key_175"] = 175.5 + t.x
+t["key_176"] = 176.5 + t.x
+t["key_177"] = 177.5 + t.x
+t["key_178"] = 178.5 + t.x
+t["key_179"] = 179.5 + t.x
+t["key_180"] = 180.5 + t.x
+t["key_181"] = 181.5 + t.x
+t["key_182"] = 182.5 + t.x
+t["key_183"] = 183.5 + t.x
+t["key_184"] = 184.5 + t.x
+t["key_185"] = 185.5 + t.x
+t["key_186"] = 186.5 + t.x
+t["key_187"] = 187.5 + t.x
+t["key_188"] = 188.5 + t.x
+t["key_189"] = 189.5 + t.x
+t["key_190"] = 190.5 + t.x
+t["key_191"] = 191.5 + t.x
+t["key_192"] = 192.5 + t.x
+t["key_193"] = 193.5 + t.x
+t["key_194"] = 194.5 + t.x
+t["key_195"] = 195.5 + t.x
+t["key_196"] = 196.5 + t.x
+t["key_197"] = 197.5 + t.x
+t["key_198"] = 198.5 + t.x
+t["key_199"] = 199.5 + t.x
+t["key_200"] = 200.5 + t.x
+t["key_201"] = 201.5 + t.x
+t["key_202"] = 202.5 + t.x
+t["key_203"] = 203.5 + t.x
+t["key_204"] = 204.5 + t.x
+t["key_205"] = 205.5 + t.x
+t["key_206"] = 206.5 + t.x
+t["key_207"] = 207.5 + t.x
+t["key_208"] = 208.5 + t.x
+t["key_209"] = 209.5 + t.x
+t["key_210"] = 210.5 + t.x
+t["key_211"] = 211.5 + t.x
+t["key_212"] = 212.5 + t.x
+t["key_213"] = 213.5 + t.x
+t["key_214"] = 214.5 + t.x
+t["key_215"] = 215.5 + t.x
+t["key_216"] = 216.5 + t.x
+t["key_217"] = 217.5 + t.x
+t["key_218"] = 218.5 + t.x
+t["key_219"] = 219.5 + t.x
+t["key_220"] = 220.5 + t.x
+t["key_221"] = 221.5 + t.x
+t["key_222"] = 222.5 + t.x
+t["key_223"] = 223.5 + t.x
+t["key_224"] = 224.5 + t.x
+t["key_225"] = 225.5 + t.x
+t["key_226"] = 226.5 + t.x
+t["key_227"] = 227.5 + t.x
+t["key_228"] = 228.5 + t.x
+t["key_229"] = 229.5 + t.x
+t["key_230"] = 230.5 + t.x
+t["key_231"] = 231.5 + t.x
+t["key_232"] = 232.5 + t.x
+t["key_233"] = 233.5 + t.x
+t["key_234"] = 234.5 + t.x
+t["key_235"] = 235.5 + t.x
+t["key_236"] = 236.5 + t.x
+t["key_237"] = 237.5 + t.x
+t["key_238"] = 238.5 + t.x
+t["key_239"] = 239.5 + t.x
+t["key_240"] = 240.5 + t.x
+t["key_241"] = 241.5 + t.x
+t["key_242"] = 242.5 + t.x
+t["key_243"] = 243.5 + t.x
+t["key_244"] = 244.5 + t.x
+t["key_245"] = 245.5 + t.x
+t["key_246"] = 246.5 + t.x
+t["key_247"] = 247.5 + t.x
+t["key_248"] = 248.5 + t.x
+t["key_249"] = 249.5 + t.x
+t["key_250"] = 250.5 + t.x
+t["key_251"] = 251.5 + t.x
+t["key_252"] = 252.5 + t.x
+t["key_253"] = 253.5 + t.x
+t["key_254"] = 254.5 + t.x
+t["key_255"] = 255.5 + t.x
+t["key_256"] = 256.5 + t.x
+t["key_257"] = 257.5 + t.x
+t["key_258"] = 258.5 + t.x
+t["key_259"] = 259.5 + t.x
+t["key_260"] = 260.5 + t.x
+t["key_261"] = 261.5 + t.x
+t["key_262"] = 262.5 + t.x
+t["key_263"] = 263.5 + t.x
+t["key_264"] = 264.5 + t.x
+t["key_265"] = 265.5 + t.x
+t["key_266"] = 266.5 + t.x
+t["key_267"] = 267.5 + t.x
+t["key_268"] = 268.5 + t.x
+t["key_269"] = 269.5 + t.x
+t["key_270"] = 270.5 + t.x
+t["key_271"] = 271.5 + t.x
+t["key_272"] = 272.5 + t.x
+t["key_273"] = 273.5 + t.x
+t["key_274"] = 274.5 + t.x
+t["key_275"] = 275.5 + t.x
+t["key_276"] = 276.5 + t.x
+t["key_277"] = 277.5 + t.x
+t["key_278"] = 278.5 + t.x
+t["key_279"] = 279.5 + t.x
+t["key_280"] = 280.5 + t.x
+t["key_281"] = 281.5 + t.x
+t["key_282"] = 282.5 + t.x
+t["key_283"] = 283.5 + t.x
+t["key_284"] = 284.5 + t.x
+t["key_285"] = 285.5 + t.x
+t["key_286"] = 286.5 + t.x
+t["key_287"] = 287.5 + t.x
+t["key_288"] = 288.5 + t.x
+t["key_289"] = 289.5 + t.x
+t["key_290"] = 290.5 + t.x
+t["key_291"] = 291.5 + t.x
+t["key_292"] = 292.5 + t.x
+t["key_293"] = 293.5 + t.x
+t["key_294"] = 294.5 + t.x
+t["key_295"] = 295.5 + t.x
+t["key_296"] = 296.5 + t.x
+t["key_297"] = 297.5 + t.x
+t["key_298"] = 298.5 + t.x
+t["key_299"] = 299.5 + t.x
+t["key_300"] = 300.5 + t.x
+t["key_301"] = 301.5 + t.x
+t["key_302"] = 302.5 + t.x
+t["key_303"] = 303.5 + t.x
+t["key_304"] = 304.5 + t.x
+t["key_305"] = 305.5 + t.x
+t["key_306"] = 306.5 + t.x
+t["key_307"] = 307.5 + t.x
+t["key_308"] = 308.5 + t.x
+t["key_309"] = 309.5 + t.x
+t["key_310"] = 310.5 + t.x
+t["key_311"] = 311.5 + t.x
+t["key_312"] = 312.5 + t.x
+t["key_313"] = 313.5 + t.x
+t["key_314"] = 314.5 + t.x
+t["key_315"] = 315.5 + t.x
+t["key_316"] = 316.5 + t.x
+t["key_317"] = 317.5 + t.x
+t["key_318"] = 318.5 + t.x
+t["key_319"] = 319.5 + t.x
+t["key_320"] = 320.5 + t.x
+t["key_321"] = 321.5 + t.x
+t["key_322"] = 322.5 + t.x
+t["key_323"] = 323.5 + t.x
+t["key_324"] = 324.5 + t.x
+t["key_325"] = 325.5 + t.x
+t["key_326"] = 326.5 + t.x
+t["key_327"] = 327.5 + t.x
+t["key_328"] = 328.5 + t.x
+t["key_329"] = 329.5 + t.x
+t["key_330"] = 330.5 + t.x
+t["key_331"] = 331.5 + t.x
+t["key_332"] = 332.5 + t.x
+t["key_333"] = 333.5 + t.x
+t["key_334"] = 334.5 + t.x
+t["key_335"] = 335.5 + t.x
+t["key_336"] = 336.5 + t.x
+t["key_337"] = 337.5 + t.x
+t["key_338"] = 338.5 + t.x
+t["key_339"] = 339.5 + t.x
+t["key_340"] = 340.5 + t.x
+t["key_341"] = 341.5 + t.x
+t["key_342"] = 342.5 + t.x
+t["key_343"] = 343.5 + t.x
+t["key_344"] = 344.5 + t.x
+t["key_345"] = 345.5 + t.x
+t["key_346"] = 346.5 + t.x
+t["key_347"] = 347.5 + t.x
+t["key_348"] = 348.5 + t.x
+t["key_349"] = 349.5 + t.x
+t["key_350"] = 350.5 + t.x
+t["key_351"] = 351.5 + t.x
+t["key_352"] = 352.5 + t.x
+t["key_353"] = 353.5 + t.x
+t["key_354"] = 354.5 + t.x
+t["key_355"] = 355.5 + t.x
+t["key_356"] = 356.5 + t.x
+t["key_357"] = 357.5 + t.x
+t["key_358"] = 358.5 + t.x
+t["key_359"] = 359.5 + t.x
+t["key_360"] = 360.5 + t.x
+t["key_361"] = 361.5 + t.x
+t["key_362"] = 362.5 + t.x
+t["key_363"] = 363.5 + t.x
+t["key_364"] = 364.5 + t.x
+t["key_365"] = 365.5 + t.x
+t["key_366"] = 366.5 + t.x
+t["key_367"] = 367.5 + t.x
+t["key_368"] = 368.5 + t.x
+t["key_369"] = 369.5 + t.x
+t["key_370"] = 370.5 + t.x
+t["key_371"] = 371.5 + t.x
+t["key_372"] = 372.5 + t.x
+t["key_373"] = 373.5 + t.x
+t["key_374"] = 374.5 + t.x
+t["key_375"] = 375.5 + t.x
+t["key_376"] = 376.5 + t.x
+t["key_377"] = 377.5 + t.x
+t["key_378"] = 378.5 + t.x
+t["key_379"] = 379.5 + t.x
+t["key_380"] = 380.5 + t.x
+t["key_381"] = 381.5 + t.x
+t["key_382"] = 382.5 + t.x
+t["key_383"] = 383.5 + t.x
+t["key_384"] = 384.5 + t.x
+t["key_385"] = 385.5 + t.x
+t["key_386"] = 386.5 + t.x
+t["key_387"] = 387.5 + t.x
+t["key_388"] = 388.5 + t.x
+t["key_389"] = 389.5 + t.x
+t["key_390"] = 390.5 + t.x
+t["key_391"] = 391.5 + t.x
+t["key_392"] = 392.5 + t.x
+t["key_393"] = 393.5 + t.x
+t["key_394"] = 394.5 + t.x
+t["key_395"] = 395.5 + t.x
+t["key_396"] = 396.5 + t.x
+t["key_397"] = 397.5 + t.x
+t["key_398"] = 398.5 + t.x
+t["key_399"] = 399.5 + t.x
+return t
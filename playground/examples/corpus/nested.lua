@@ -0,0 +1,15 @@
+local a = 1
+local function outer(x)
+	local b = x + a
+	local function middle(y)
+		local c = y + b
+		local function inner(z)
+			a = a + z + b + c
+			return function() return a, b, c, x, y, z end
+		end
+		return inner
+	end
+	return middle
+end
+
+return outer(1)(2)(3)()
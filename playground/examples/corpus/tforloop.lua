@@ -0,0 +1,14 @@
+local t = { a = 1, b = 2, "x", "y" }
+for k, v in pairs(t) do
+	print(k, v)
+end
+for i, v in ipairs(t) do
+	if v == "y" then break end
+	print(i, v)
+end
+local function iter(s, i)
+	if i < s then return i + 1 end
+end
+for i in iter, 3, 0 do
+	print(i)
+end
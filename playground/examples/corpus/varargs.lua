@@ -0,0 +1,15 @@
+local function pack(...)
+	return { n = select("#", ...), ... }
+end
+
+local function first(a, ...)
+	local rest = { ... }
+	return a, #rest, arg
+end
+
+local function forward(...)
+	return pack(...), first(...)
+end
+
+print(forward(1, nil, "three"))
+print(select(2, ...))
@@ -6,17 +6,20 @@ fn reader_writer() {
 	writer.byte(0x01);
 	writer.bytes(vec![0x02, 0x03]);
 	writer.int(0x4, 4);
-	writer.string("test", 4);
-	writer.number(5f64, 4);
+	writer.string(b"test", 4);
+	writer.number(5f64, 8, false);
+	writer.integer(-2, 4);
 
 	let slice = writer.as_bytes();
 	let mut reader = Reader::new(slice);
 	
-	assert_eq!(*reader.byte(), 0x1);
-	assert_eq!(reader.bytes(2), &[0x02, 0x03]);
-	assert_eq!(reader.int(4), 0x04);
-	assert_eq!(reader.string(4), "test");
-	assert_eq!(reader.number(4), 5f64);
+	assert_eq!(reader.byte().unwrap(), 0x1);
+	assert_eq!(reader.bytes(2).unwrap(), &[0x02, 0x03]);
+	assert_eq!(reader.int(4).unwrap(), 0x04);
+	assert_eq!(reader.string(4).unwrap(), "test");
+	assert_eq!(reader.number(8, false).unwrap(), 5f64);
+	assert_eq!(reader.integer(4).unwrap(), -2);
+	assert!(reader.byte().is_err());
 }
 
 #[test]
@@ -42,4 +45,1118 @@ fn basic_read_and_dump() {
 	let bytes = bytecode::lua51::serialize_bytecode(&header, &proto);
 	assert_eq!(bytes, test_out);
 
-}
\ No newline at end of file
+}
+
+// walks two protos side by side and lists every field that differs
+#[cfg(test)]
+fn diff_proto(path: &str, a: &bytecode::lua51::Proto, b: &bytecode::lua51::Proto, out: &mut Vec<String>) {
+	let mut field = |name: &str, x: String, y: String| {
+		if x != y {
+			out.push(format!("{}.{}: {} != {}", path, name, x, y));
+		}
+	};
+	field("source", format!("{:?}", a.source), format!("{:?}", b.source));
+	field("line_defined", a.line_defined.to_string(), b.line_defined.to_string());
+	field("last_line_defined", a.last_line_defined.to_string(), b.last_line_defined.to_string());
+	field("nupvals", a.nupvals.to_string(), b.nupvals.to_string());
+	field("nparams", a.nparams.to_string(), b.nparams.to_string());
+	field("is_vararg_flag", a.is_vararg_flag.to_string(), b.is_vararg_flag.to_string());
+	field("max_stack_size", a.max_stack_size.to_string(), b.max_stack_size.to_string());
+	field("instructions.len()", a.instructions.len().to_string(), b.instructions.len().to_string());
+	for (i, (x, y)) in a.instructions.iter().zip(&b.instructions).enumerate() {
+		field(&format!("instructions[{}]", i), format!("{:08x} {:?}", x.serialize(), x.1), format!("{:08x} {:?}", y.serialize(), y.1));
+	}
+	field("constants.len()", a.constants.len().to_string(), b.constants.len().to_string());
+	for (i, (x, y)) in a.constants.iter().zip(&b.constants).enumerate() {
+		field(&format!("constants[{}]", i), format!("{:?}", x), format!("{:?}", y));
+	}
+	field("source_lines", format!("{:?}", a.source_lines), format!("{:?}", b.source_lines));
+	field("locals", format!("{:?}", a.locals), format!("{:?}", b.locals));
+	field("upvals", format!("{:?}", a.upvals), format!("{:?}", b.upvals));
+	field("prototypes.len()", a.prototypes.len().to_string(), b.prototypes.len().to_string());
+	for (i, (x, y)) in a.prototypes.iter().zip(&b.prototypes).enumerate() {
+		diff_proto(&format!("{}.prototypes[{}]", path, i), x, y, out);
+	}
+}
+
+#[test]
+fn round_trip_corpus() {
+	use bytecode::{Hex, lua51::{deserialize_bytecode, serialize_bytecode, try_deserialize_bytecode}};
+
+	// every fixture in examples/corpus has its .out from a stock 5.1 luac next to it,
+	// setlist.out is stripped to keep it small
+	let mut fixtures: Vec<_> = std::fs::read_dir("examples/corpus").unwrap()
+		.map(|entry| entry.unwrap().path())
+		.filter(|path| path.extension().is_some_and(|ext| ext == "lua"))
+		.collect();
+	fixtures.sort();
+	assert!(!fixtures.is_empty());
+
+	let mut failures = vec![];
+	for fixture in fixtures {
+		let chunk = std::fs::read(fixture.with_extension("out")).unwrap_or_else(|_| panic!("{:?} has no .out", fixture));
+		let (header, proto) = deserialize_bytecode(&chunk);
+		let bytes = serialize_bytecode(&header, &proto);
+		if bytes == chunk {
+			continue;
+		}
+
+		let at = chunk.iter().zip(&bytes).position(|(x, y)| x != y).unwrap_or(chunk.len().min(bytes.len()));
+		let from = at.saturating_sub(16);
+		let mut report = format!("{:?}: {} bytes dumped as {}, first difference at {:#x}\n", fixture, chunk.len(), bytes.len(), at);
+		report += &chunk[from..chunk.len().min(at + 16)].hex(Some(at - from));
+		report += &bytes[from..bytes.len().min(at + 16)].hex(Some(at - from));
+		match try_deserialize_bytecode(&bytes) {
+			Ok((_, read)) => {
+				let mut diff = vec![];
+				diff_proto("main", &proto, &read, &mut diff);
+				report += &diff.join("\n");
+			},
+			Err(err) => report += &format!("dump does not load: {}", err)
+		}
+		failures.push(report);
+	}
+	assert!(failures.is_empty(), "\n{}", failures.join("\n\n"));
+}
+
+#[test]
+fn grouped_instructions() {
+	use bytecode::lua51::{Header, deserialize_bytecode, disassemble, serialize_bytecode};
+	use bytecode::lua51::instruction::{Capture, Instr, Instruction, Opcode, Reg};
+
+	// CLOSURE comes with its captures, and jumps count instructions rather than words
+	let chunk = std::fs::read("examples/corpus/closures.out").unwrap();
+	let (header, mut proto) = deserialize_bytecode(&chunk);
+	assert_eq!(proto.instructions.len(), 25);
+	assert_eq!(proto.source_lines.as_ref().unwrap().len(), 25);
+	assert!(matches!(&proto.instructions[16].1, Instr::Closure(Reg(8), 1, captures)
+		if matches!(captures[..], [Capture::Local(Reg(7)), Capture::Local(Reg(6))])));
+	assert!(matches!(proto.instructions[14].1, Instr::ForPrep(_, 8)));
+	assert!(matches!(proto.instructions[23].1, Instr::ForLoop(_, -9)));
+
+	// inserting right after a CLOSURE leaves the captures in front of the new instruction
+	proto.instructions.insert(17, Instruction::new(Opcode::LoadNil, Instr::LoadNil(Reg(0), Reg(0))));
+	proto.source_lines.as_mut().unwrap().insert(17, 18);
+	let Instr::ForPrep(_, sbx) = &mut proto.instructions[14].1 else { panic!() };
+	*sbx += 1;
+	let Instr::ForLoop(_, sbx) = &mut proto.instructions[24].1 else { panic!() };
+	*sbx -= 1;
+	proto.instructions[14].2 = proto.instructions[14].1.get_opmode();
+	proto.instructions[24].2 = proto.instructions[24].1.get_opmode();
+
+	let bytes = serialize_bytecode(&header, &proto);
+	let (header, read) = deserialize_bytecode(&bytes);
+	let listing = disassemble(&header, &read, false);
+	assert!(listing.contains("\t17\t[18]\tCLOSURE  \t8 1\t; 0x00000005\n\t18\t[18]\tMOVE     \t0 7\n\t19\t[18]\tMOVE     \t0 6\n\t20\t[18]\tLOADNIL  \t0 0\n"));
+	assert!(listing.contains("\tFORPREP  \t3 11\t; to 27\n"));
+	assert!(listing.contains("\tFORLOOP  \t3 -12\t; to 16\n"));
+
+	// a SETLIST count is never decoded as an instruction of its own, whatever it looks like.
+	// a count of 0 can't go in C either, since C = 0 means the next word holds it
+	for count in [552, 0] {
+		let mut proto = bytecode::lua51::Proto::default();
+		proto.max_stack_size = 3;
+		proto.instructions.push(Instruction::new(Opcode::SetList, Instr::SetList(Reg(0), 1, count)));
+		proto.instructions.push(Instruction::new(Opcode::Return, Instr::Return(Reg(0), 1)));
+		let bytes = serialize_bytecode(&Header::default(), &proto);
+		let (_, read) = deserialize_bytecode(&bytes);
+		assert_eq!(read.instructions.len(), 2);
+		assert!(matches!(read.instructions[0].1, Instr::SetList(Reg(0), 1, c) if c == count));
+		assert_eq!(serialize_bytecode(&Header::default(), &read), bytes);
+	}
+}
+
+#[test]
+fn disassemble_listing() {
+	use bytecode::lua51::{disassemble, deserialize_bytecode};
+
+	// the .lst files are luac -l -l output, pointers differ so only compare what's around them.
+	// luac doesn't print the .vararg lines either
+	let strip_pointers = |s: &str| s.split("0x").map(|part| part.trim_start_matches(|c: char| c.is_ascii_hexdigit())).collect::<Vec<_>>().join("0x");
+	for name in ["closures", "nested", "tforloop", "varargs"] {
+		let chunk = std::fs::read(format!("examples/corpus/{}.out", name)).unwrap();
+		let expected = std::fs::read_to_string(format!("examples/corpus/{}.lst", name)).unwrap();
+		let (header, proto) = deserialize_bytecode(&chunk);
+		let listing = disassemble(&header, &proto, true);
+		assert_eq!(listing.matches("\n.vararg 3\n").count(), if name == "varargs" { 3 } else { 0 }, "{}", name);
+		let listing: String = listing.split_inclusive('\n').filter(|line| !line.starts_with(".vararg")).collect();
+		assert_eq!(strip_pointers(&listing), strip_pointers(&expected), "{}", name);
+	}
+
+	// without -l -l there are no constant, local and upvalue listings
+	let chunk = std::fs::read("examples/corpus/setlist.out").unwrap();
+	let (header, proto) = deserialize_bytecode(&chunk);
+	let listing = disassemble(&header, &proto, false);
+	assert!(listing.starts_with("\nmain <?:0,0> (26543 instructions, 106172 bytes at 0x00000001)\n0+ params, 51 slots, 0 upvalues, 0 locals, 4 constants, 0 functions\n"));
+	assert!(listing.contains("\n\t26113\t[-]\tSETLIST  \t0 50 0\t; 512\n\t26115\t"));
+	assert!(!listing.contains("constants ("));
+
+	// the byte count follows the header's instruction size
+	let wide = bytecode::lua51::Header { instruction_size: 8, ..header };
+	assert!(disassemble(&wide, &proto, false).starts_with("\nmain <?:0,0> (26543 instructions, 212344 bytes at 0x00000001)\n"));
+}
+
+#[test]
+fn assemble_source() {
+	use bytecode::lua51::{Constants, Header, assemble, disassemble, deserialize_bytecode, serialize_bytecode};
+	use bytecode::lua51::instruction::Instr;
+
+	// listings assemble back into the same listing and into the same bytes, vararg flags included
+	for name in ["closures", "constants", "nested", "setlist", "test_file", "tforloop", "varargs"] {
+		let chunk = std::fs::read(format!("examples/corpus/{}.out", name)).unwrap();
+		let (header, proto) = deserialize_bytecode(&chunk);
+		let listing = disassemble(&header, &proto, true);
+		let assembled = assemble(&listing).unwrap();
+		assert_eq!(disassemble(&header, &assembled, true), listing, "{}", name);
+		assert_eq!(serialize_bytecode(&header, &assembled), chunk, "{}", name);
+	}
+
+	let proto = assemble(r#"
+		.source "@patch.lua"
+		.const print "print"
+		.upval counter
+		.line 3
+		loop:	GETGLOBAL 0 print
+			GETUPVAL 1 counter
+			ADD 1 1 #1
+			EQ 0 1 #10
+			JMP done
+			CLOSURE 2 callback
+			LOADK 3 "tick\n"
+			CALL 0 2 1
+			JMP loop
+		.local i loop done
+		done:	RETURN 0 1
+		.function callback 4 6
+			.params 1
+			RETURN 0 1
+		.end
+	"#).unwrap();
+	assert_eq!(proto.constants, vec![Constants::String("print".into()), Constants::Number(1.0), Constants::Number(10.0), Constants::String("tick\n".into())]);
+	assert!(matches!(proto.instructions[4].1, Instr::Jump(_, 4)));
+	assert!(matches!(proto.instructions[8].1, Instr::Jump(_, -9)));
+	assert!(matches!(&proto.instructions[5].1, Instr::Closure(_, 0, captures) if captures.is_empty()));
+	assert_eq!(proto.source_lines.as_deref(), Some(&[3; 10][..]));
+	assert_eq!((proto.max_stack_size, proto.nupvals), (4, 1));
+	assert_eq!((proto.prototypes[0].nparams, proto.prototypes[0].line_defined, proto.prototypes[0].source.is_none()), (1, 4, true));
+	assert!(disassemble(&Header::default(), &proto, true).contains("locals (1) for 0x00000001:\n\t0\ti\t1\t10\n"));
+
+	let bytes = serialize_bytecode(&Header::default(), &proto);
+	let (header, read) = deserialize_bytecode(&bytes);
+	assert_eq!(disassemble(&header, &read, true), disassemble(&header, &proto, true));
+
+	// errors point at the line
+	let err = assemble("LOADK 0 \"x\"\nJMP nowhere").unwrap_err();
+	assert_eq!((err.line, err.message.as_str()), (2, "unknown label `nowhere`"));
+	assert_eq!(assemble(".function f\nRETURN 0 1").unwrap_err().to_string(), "line 1: .function without .end");
+}
+
+#[test]
+fn verify_code() {
+	use bytecode::lua51::{Constants, assemble, deserialize_bytecode, verify, VerifyErrorKind};
+	use bytecode::lua51::instruction::{Instr, Kst, Reg, RegKst};
+
+	// everything luac produced passes
+	for entry in std::fs::read_dir("examples/corpus").unwrap() {
+		let path = entry.unwrap().path();
+		if path.extension().is_some_and(|ext| ext == "out") {
+			let (_, proto) = deserialize_bytecode(&std::fs::read(&path).unwrap());
+			assert_eq!(verify(&proto), Ok(()), "{}", path.display());
+		}
+	}
+
+	let check = |text: &str| verify(&assemble(text).unwrap()).map_err(|err| (err.path, err.pc, err.kind));
+	assert_eq!(check(".stack 2\nMOVE 0 1\nRETURN 0 1"), Ok(()));
+	assert_eq!(check(".stack 2\nMOVE 0 2\nRETURN 0 1"), Err((vec![], Some(0), VerifyErrorKind::RegisterOutOfRange(2))));
+	assert_eq!(check(".stack 2\nMOVE 0 1"), Err((vec![], None, VerifyErrorKind::MissingReturn)));
+	assert_eq!(check("LOADK 0 -2\nRETURN 0 1"), Err((vec![], Some(0), VerifyErrorKind::ConstantOutOfRange(1))));
+	assert_eq!(check("JMP 5\nRETURN 0 1"), Err((vec![], Some(0), VerifyErrorKind::JumpOutOfRange(6))));
+	assert_eq!(check("EQ 0 0 0\nRETURN 0 1\nRETURN 0 1"), Err((vec![], Some(0), VerifyErrorKind::MissingJump)));
+	assert_eq!(check(".vararg 0\nVARARG 0 2\nRETURN 0 1"), Err((vec![], Some(0), VerifyErrorKind::NotVararg)));
+	assert_eq!(check("CALL 0 1 0\nMOVE 0 0\nRETURN 0 1"), Err((vec![], Some(0), VerifyErrorKind::OpenResultNotConsumed)));
+
+	// jumps can't land on the count of a SETLIST or the captures of a CLOSURE
+	assert_eq!(check(".stack 2\nLOADBOOL 0 0 1\nSETLIST 0 1 600\nRETURN 0 1"), Err((vec![], Some(0), VerifyErrorKind::JumpIntoPseudo(2))));
+	let closure = ".stack 2\nJMP 1\nCLOSURE 0 f\nMOVE 0 1\nRETURN 0 1\n.function f\n.upval x\nRETURN 0 1\n.end";
+	assert_eq!(assemble(closure).unwrap_err().message, "jump lands inside a CLOSURE or SETLIST sequence");
+	let closure = closure.replace("JMP 1", "JMP 2");
+	assert_eq!(check(&closure), Ok(()));
+	let mut proto = assemble(&closure).unwrap();
+	let Instr::Closure(_, _, captures) = &mut proto.instructions[1].1 else { panic!() };
+	captures.clear();
+	assert_eq!(verify(&proto).unwrap_err().kind, VerifyErrorKind::BadCapture);
+
+	// constants in RK operands are real indices, past 255 they have to go through a register
+	let mut proto = assemble(".stack 2\nGETTABLE 0 0 -1\nRETURN 0 1").unwrap();
+	proto.constants.resize(300, Constants::Nil);
+	let Instr::GetTable(_, _, c) = &mut proto.instructions[0].1 else { panic!() };
+	assert_eq!((c.get(), c.field()), (0, 0x100));
+	*c = RegKst::K(Kst(299));
+	assert!(!c.fits() && RegKst::constant(299).is_none());
+	// encoding one refuses to quietly pick another constant, verify reports it instead
+	let big = *c;
+	assert!(std::panic::catch_unwind(|| big.field()).is_err());
+	assert_eq!(verify(&proto).unwrap_err().kind, VerifyErrorKind::RkOutOfRange(299));
+	proto.instructions[0].1 = Instr::GetTable(Reg(0), Reg(0), RegKst::constant(255).unwrap());
+	assert_eq!(verify(&proto), Ok(()));
+
+	// children are checked too
+	let child = "CLOSURE 0 f\nRETURN 0 1\n.function f\nGETUPVAL 0 0\nRETURN 0 1\n.end";
+	assert_eq!(check(child), Err((vec![0], Some(0), VerifyErrorKind::UpvalueOutOfRange(0))));
+	assert_eq!(verify(&assemble(child).unwrap()).unwrap_err().to_string(), "upvalue 0 out of range in proto main/0 at pc 0");
+}
+
+#[test]
+fn constant_refs() {
+	use bytecode::lua51::{Constants, Header, Proto};
+	use bytecode::lua51::instruction::{BinCondOp, BinOp, Instr, Instruction, Kst, Opcode, Reg, RegKst};
+
+	// only K operands follow the constants around, registers keep their number
+	let mut proto = Proto::default();
+	proto.constants.push(Constants::Number(1.0));
+	proto.instructions.push(Instruction::new(Opcode::Add, Instr::BinOp(Reg(1), RegKst::R(Reg(0)), BinOp::Add, RegKst::K(Kst(0)))));
+	proto.instructions.push(Instruction::new(Opcode::Lt, Instr::BinCondOp(true, RegKst::K(Kst(0)), BinCondOp::Lt, RegKst::R(Reg(0)))));
+	proto.instructions.push(Instruction::new(Opcode::Return, Instr::Return(Reg(0), 1)));
+	let mut ctx = ir::Context::new(Header::default(), proto);
+	ctx.map();
+	ctx.add_constant(0, Constants::Nil);
+	assert!(matches!(ctx.chunk.instructions[0].1, Instr::BinOp(Reg(1), RegKst::R(Reg(0)), BinOp::Add, RegKst::K(Kst(1)))));
+	assert!(matches!(ctx.chunk.instructions[1].1, Instr::BinCondOp(true, RegKst::K(Kst(1)), BinCondOp::Lt, RegKst::R(Reg(0)))));
+}
+
+#[test]
+fn debug_info_options() {
+	use bytecode::LuaString;
+	use bytecode::lua51::{DebugInfo, Proto, SerializeOptions, deserialize_bytecode, serialize_bytecode, serialize_bytecode_with, verify};
+
+	let chunk = std::fs::read("examples/corpus/nested.out").unwrap();
+	let (header, proto) = deserialize_bytecode(&chunk);
+	assert_eq!(serialize_bytecode_with(&header, &proto, &SerializeOptions::default()), chunk);
+
+	// stripped like luac -s, all the way down
+	let stripped = serialize_bytecode_with(&header, &proto, &SerializeOptions { debug_info: DebugInfo::Strip });
+	assert!(stripped.len() < chunk.len());
+	let (_, read) = deserialize_bytecode(&stripped);
+	fn check_stripped(proto: &Proto) {
+		assert_eq!(proto.source, None);
+		assert!(proto.source_lines.as_ref().unwrap().is_empty() && proto.locals.as_ref().unwrap().is_empty());
+		assert!(proto.upvals.as_ref().unwrap().is_empty());
+		proto.prototypes.iter().for_each(check_stripped);
+	}
+	check_stripped(&read);
+	assert!(!read.prototypes.is_empty());
+	assert_eq!(verify(&read), Ok(()));
+	assert_eq!(serialize_bytecode(&header, &read), stripped);
+
+	// new source name and lines, the locals stay
+	let source = LuaString::from("=release");
+	let lines = |path: &[usize], i: usize| (path.len() * 1000 + i) as u32;
+	let options = SerializeOptions { debug_info: DebugInfo::Replace { source: Some(&source), lines: Some(&lines) } };
+	let (_, read) = deserialize_bytecode(&serialize_bytecode_with(&header, &proto, &options));
+	assert_eq!(read.source.as_deref(), Some(&b"=release"[..]));
+	assert_eq!(read.prototypes[0].source, None);
+	assert_eq!(read.source_lines, Some((0..read.instructions.len() as u32).collect()));
+	let child = &read.prototypes[0];
+	assert_eq!(child.source_lines.as_ref().unwrap()[..2], [1000, 1001]);
+	assert_eq!(format!("{:?}", read.locals), format!("{:?}", proto.locals));
+}
+
+#[test]
+fn debug_info_model() {
+	use bytecode::lua51::{deserialize_bytecode, serialize_bytecode, verify};
+	use bytecode::lua51::instruction::{Instr, Instruction, Opcode, Reg};
+
+	// counter from closures.lua, where the CLOSUREs and their captures are one instruction each
+	let chunk = std::fs::read("examples/corpus/closures.out").unwrap();
+	let (header, mut main) = deserialize_bytecode(&chunk);
+	let proto = &mut main.prototypes[0];
+	assert_eq!((0..6).map(|pc| proto.line(pc)).collect::<Vec<_>>(), [Some(2), Some(6), Some(8), Some(8), Some(9), None]);
+	let names = |proto: &bytecode::lua51::Proto, pc| proto.live_locals(pc).map(|local| local.name.to_string()).collect::<Vec<_>>();
+	assert_eq!(names(proto, 0), ["start"]);
+	assert_eq!(names(proto, 3), ["start", "n"]);
+	assert!(names(proto, 4).is_empty());
+	assert_eq!(proto.local_name(Reg(1), 1).map(|name| name.to_string()), Some("n".to_string()));
+	assert_eq!(proto.local_name(Reg(1), 0), None);
+
+	// an inserted instruction takes the line and scope of the one in front of it
+	let (lines, locals) = (proto.source_lines.clone(), proto.locals.clone());
+	proto.insert_instruction(1, Instruction::new(Opcode::LoadNil, Instr::LoadNil(Reg(2), Reg(2))));
+	assert_eq!(proto.source_lines.as_deref(), Some(&[2, 2, 6, 8, 8, 9][..]));
+	assert_eq!(names(proto, 1), ["start"]);
+	assert_eq!(proto.local_name(Reg(1), 2).map(|name| name.to_string()), Some("n".to_string()));
+	proto.insert_instruction(0, Instruction::new(Opcode::LoadNil, Instr::LoadNil(Reg(2), Reg(2))));
+	assert_eq!((proto.line(0), names(proto, 0)), (Some(2), vec!["start".to_string()]));
+	proto.set_line(0, 1);
+	assert_eq!(proto.line(0), Some(1));
+	let (_, read) = deserialize_bytecode(&serialize_bytecode(&header, &main));
+	assert_eq!(verify(&read), Ok(()));
+	assert_eq!(read.prototypes[0].source_lines.as_deref(), Some(&[1, 2, 2, 6, 8, 8, 9][..]));
+
+	// and removing them puts everything back
+	let proto = &mut main.prototypes[0];
+	assert!(matches!(proto.remove_instruction(0).1, Instr::LoadNil(..)));
+	assert!(matches!(proto.remove_instruction(1).1, Instr::LoadNil(..)));
+	assert_eq!((proto.source_lines.clone(), proto.locals.clone()), (lines, locals));
+	assert_eq!(serialize_bytecode(&header, &main), chunk);
+
+	// no line info stays no line info
+	let mut proto = bytecode::lua51::Proto::default();
+	proto.insert_instruction(0, Instruction::new(Opcode::Return, Instr::Return(Reg(0), 1)));
+	assert_eq!(proto.source_lines.as_deref(), Some(&[][..]));
+}
+
+#[test]
+fn serde_round_trip() {
+	use bytecode::LuaString;
+	use bytecode::lua51::{Constants, Header, Proto, deserialize_bytecode, serialize_bytecode};
+	use bytecode::lua51::instruction::{Instr, Instruction, Opcode, Reg};
+
+	for name in ["closures", "nested", "setlist", "constants"] {
+		let chunk = std::fs::read(format!("examples/corpus/{}.out", name)).unwrap();
+		let (header, proto) = deserialize_bytecode(&chunk);
+		let json = serde_json::to_string(&(header, &proto)).unwrap();
+		let (header, proto): (Header, Proto) = serde_json::from_str(&json).unwrap();
+		assert_eq!(serialize_bytecode(&header, &proto), chunk, "{}", name);
+	}
+
+	// what it looks like from the outside
+	let instruction = Instruction::new(Opcode::Move, Instr::Move(Reg(0), Reg(1)));
+	assert_eq!(serde_json::to_string(&instruction).unwrap(), r#"["Move",{"Move":[0,1]}]"#);
+	assert!(serde_json::from_str::<Instruction>(r#"["LoadK",{"Move":[0,1]}]"#).is_err());
+	let constants = [Constants::String("print".into()), Constants::String(LuaString(vec![0xff, 0]))];
+	let json = serde_json::to_string(&constants).unwrap();
+	assert_eq!(json, r#"[{"String":"print"},{"String":[255,0]}]"#);
+	let read: Vec<Constants> = serde_json::from_str(&json).unwrap();
+	assert_eq!(read, constants);
+}
+
+#[test]
+fn annotated_dump() {
+	use bytecode::lua51::annotate;
+
+	let chunk = std::fs::read("examples/corpus/closures.out").unwrap();
+	let dump = annotate(&chunk);
+	assert!(dump.starts_with("00000000  1b 4c 75 61 51 00 01 04 08 04 08 00              header\nproto main\n"));
+	assert!(dump.contains("\n00000032  24 00 00 00                                      instruction #0  CLOSURE\n"));
+	assert!(dump.contains("\n  proto main/0\n  000000e2  00 00 00 00 00 00 00 00                          source name\n"));
+	assert!(dump.contains("\n    proto main/0/0\n"));
+	assert!(!dump.contains("error") && !dump.contains("not read"));
+
+	// stops where reading did, with what was left over
+	let dump = annotate(&chunk[..300]);
+	assert!(dump.ends_with(concat!(
+		"    0000012a  error: unexpected end of chunk (needed 4 bytes, 2 left) while reading line defined of proto main/0/0 at offset 0x12a\n",
+		"    0000012a  03 00                                            not read\n"
+	)));
+	let mut long = chunk.clone();
+	long.extend([1, 2]);
+	assert!(annotate(&long).ends_with(&format!("{:08x}  01 02                                            trailing data\n", chunk.len())));
+}
+
+#[test]
+fn control_flow_graph() {
+	use bytecode::lua51::{assemble, deserialize_bytecode};
+	use ir::{Cfg, EdgeKind::*};
+
+	let proto = assemble(concat!(
+		".stack 5\n",
+		"EQ 0 0 1\nJMP 2\n", // 0
+		"LOADNIL 2 2\nJMP 1\n", // 2
+		"LOADNIL 2 2\n", // 4
+		"FORPREP 0 1\n", // 5
+		"LOADNIL 4 4\n", // 6
+		"FORLOOP 0 -2\n", // 7
+		"RETURN 0 1" // 8
+	)).unwrap();
+	let cfg = Cfg::new(&proto);
+	let code: Vec<_> = cfg.blocks.iter().map(|block| block.code.clone()).collect();
+	assert_eq!(code, [0..0, 0..2, 2..4, 4..5, 5..6, 6..7, 7..8, 8..9, 9..9]);
+	let succs: Vec<_> = cfg.blocks.iter().map(|block| block.succs.clone()).collect();
+	assert_eq!(succs, [
+		vec![(1, Fallthrough)],
+		vec![(3, CondTrue), (2, CondFalse)],
+		vec![(4, Jump)],
+		vec![(4, Fallthrough)],
+		vec![(6, ForPrep)],
+		vec![(6, Fallthrough)],
+		vec![(5, LoopBack), (7, LoopExit)],
+		vec![(8, Return)],
+		vec![]
+	]);
+	assert_eq!((cfg.entry, cfg.exit), (0, 8));
+	assert_eq!(cfg.blocks[6].preds, [(4, ForPrep), (5, Fallthrough)]);
+	assert_eq!(cfg.predecessors(8).collect::<Vec<_>>(), [7]);
+	assert_eq!((cfg.block_of(3), cfg.block_of(9)), (Some(2), None));
+
+	// TFORLOOP and its JMP back, LOADBOOL skipping over an instruction
+	let chunk = std::fs::read("examples/corpus/tforloop.out").unwrap();
+	let (_, proto) = deserialize_bytecode(&chunk);
+	let cfg = Cfg::new(&proto);
+	let tforloop = cfg.blocks.iter().position(|block| matches!(proto.instructions[block.code.start].1, bytecode::lua51::instruction::Instr::TForLoop(..))).unwrap();
+	assert!(matches!(cfg.blocks[tforloop].succs[..], [(_, LoopBack), (_, LoopExit)]));
+	for (id, block) in cfg.blocks.iter().enumerate() {
+		assert!(block.succs.iter().all(|&(to, kind)| cfg.blocks[to].preds.contains(&(id, kind))));
+	}
+	let proto = assemble(".stack 2\nLOADBOOL 0 0 1\nLOADBOOL 0 1 0\nRETURN 0 2").unwrap();
+	assert_eq!(Cfg::new(&proto).blocks[1].succs, [(3, Jump)]);
+}
+
+#[test]
+fn dominator_trees() {
+	use bytecode::lua51::assemble;
+	use ir::{Cfg, DomTree};
+
+	// the same if/else and numeric for as in control_flow_graph
+	let proto = assemble(".stack 5\nEQ 0 0 1\nJMP 2\nLOADNIL 2 2\nJMP 1\nLOADNIL 2 2\nFORPREP 0 1\nLOADNIL 4 4\nFORLOOP 0 -2\nRETURN 0 1").unwrap();
+	let cfg = Cfg::new(&proto);
+	assert_eq!(cfg.reverse_postorder()[0], cfg.entry);
+
+	let dom = DomTree::dominators(&cfg);
+	let idoms: Vec<_> = (0..cfg.blocks.len()).map(|id| dom.immediate_dominator(id)).collect();
+	assert_eq!(idoms, [None, Some(0), Some(1), Some(1), Some(1), Some(6), Some(4), Some(6), Some(7)]);
+	assert_eq!(dom.children(1), [2, 3, 4]);
+	assert!(dom.dominates(4, 5) && dom.dominates(5, 5) && !dom.dominates(2, 4));
+	assert_eq!((dom.frontier(2), dom.frontier(3), dom.frontier(5), dom.frontier(6)), (&[4][..], &[4][..], &[6][..], &[6][..]));
+	assert!(dom.frontier(1).is_empty());
+
+	let post = DomTree::post_dominators(&cfg);
+	let ipdoms: Vec<_> = (0..cfg.blocks.len()).map(|id| post.immediate_dominator(id)).collect();
+	assert_eq!(ipdoms, [Some(1), Some(4), Some(4), Some(4), Some(6), Some(6), Some(7), Some(8), None]);
+	assert_eq!((post.frontier(2), post.frontier(3), post.frontier(5)), (&[1][..], &[1][..], &[6][..]));
+
+	// nothing after an endless loop is reached, and the loop never reaches the exit
+	let proto = assemble(".stack 2\nLOADNIL 0 0\nJMP -1\nRETURN 0 1").unwrap();
+	let cfg = Cfg::new(&proto);
+	let (dom, post) = (DomTree::dominators(&cfg), DomTree::post_dominators(&cfg));
+	assert!(dom.reachable(2) && !dom.reachable(3) && !dom.dominates(0, 3));
+	assert!(!post.reachable(2) && post.reachable(3));
+	assert_eq!(post.immediate_dominator(1), None);
+}
+
+#[test]
+fn loop_forest() {
+	use bytecode::lua51::assemble;
+	use ir::{Cfg, DomTree, LoopKind, Loops};
+
+	// while r0 < r1 do repeat until r2; for r3 = .. do end end
+	let proto = assemble(".stack 7\nLT 0 0 1\nJMP 7\nLOADNIL 2 2\nTEST 2 0 0\nJMP -3\nFORPREP 3 1\nLOADNIL 6 6\nFORLOOP 3 -2\nJMP -9\nRETURN 0 1").unwrap();
+	let cfg = Cfg::new(&proto);
+	let loops = Loops::new(&cfg, &DomTree::dominators(&cfg), &proto);
+	assert!(loops.is_reducible());
+	let summary: Vec<_> = loops.loops.iter().map(|l| (l.header, l.kind, l.body.clone(), l.exits.clone(), l.parent, l.depth)).collect();
+	assert_eq!(summary, [
+		(1, LoopKind::While, vec![1, 2, 3, 4, 5, 6], vec![(1, 7)], None, 1),
+		(2, LoopKind::Repeat, vec![2], vec![(2, 3)], Some(0), 2),
+		(5, LoopKind::NumericFor, vec![4, 5], vec![(5, 6)], Some(0), 2)
+	]);
+	assert_eq!((loops.loops[0].latches.as_slice(), loops.loops[2].latches.as_slice()), (&[6][..], &[4][..]));
+	assert_eq!(loops.roots().collect::<Vec<_>>(), [0]);
+	assert_eq!(loops.loops[0].children, [1, 2]);
+	assert_eq!((loops.innermost(4), loops.innermost(3), loops.innermost(7)), (Some(2), Some(0), None));
+
+	// the generic for is entered at its TFORLOOP
+	let proto = assemble(".stack 6\nJMP 1\nLOADNIL 5 5\nTFORLOOP 0 1\nJMP -3\nRETURN 0 1").unwrap();
+	let cfg = Cfg::new(&proto);
+	let loops = Loops::new(&cfg, &DomTree::dominators(&cfg), &proto);
+	assert_eq!(loops.loops.iter().map(|l| (l.header, l.kind)).collect::<Vec<_>>(), [(3, LoopKind::GenericFor)]);
+
+	// a cycle that can be entered in two places has no header
+	let proto = assemble(".stack 2\nTEST 0 0 0\nJMP 1\nLOADNIL 1 1\nLOADNIL 1 1\nTEST 0 0 1\nJMP -4\nRETURN 0 1").unwrap();
+	let cfg = Cfg::new(&proto);
+	let loops = Loops::new(&cfg, &DomTree::dominators(&cfg), &proto);
+	assert!(loops.loops.is_empty() && !loops.is_reducible());
+	assert_eq!(loops.irreducible.len(), 1);
+}
+
+#[test]
+fn register_liveness() {
+	use bytecode::lua51::assemble;
+	use bytecode::lua51::instruction::{Instr, Reg};
+	use ir::{Access, Cfg, Liveness, RegSet};
+
+	let set = |regs: &[u8]| regs.iter().fold(RegSet::default(), |mut set, &reg| {
+		set.insert(reg);
+		set
+	});
+
+	// r0 = r0 or (r0 + r1) sort of thing, with r3 only needed on one path
+	let proto = assemble(".stack 4\nLOADNIL 0 1\nMOVE 2 0\nADD 3 2 1\nTEST 3 0 0\nJMP 1\nMOVE 0 3\nRETURN 0 2").unwrap();
+	let cfg = Cfg::new(&proto);
+	let live = Liveness::new(&cfg, &proto);
+	let before: Vec<_> = (0..7).map(|pc| live.live_before(pc)).collect();
+	assert_eq!(before, [set(&[]), set(&[0, 1]), set(&[0, 1, 2]), set(&[0, 3]), set(&[0, 3]), set(&[3]), set(&[0])]);
+	assert_eq!(live.live_after(3), set(&[0, 3]));
+	assert_eq!(live.live_in[cfg.block_of(5).unwrap()], set(&[3]));
+	assert_eq!(live.live_out[cfg.entry], RegSet::default());
+
+	// an open call reads up to the top and writes everything from A up
+	let proto = assemble(".stack 4\nMOVE 1 0\nCALL 0 2 0\nRETURN 0 0").unwrap();
+	let cfg = Cfg::new(&proto);
+	let live = Liveness::new(&cfg, &proto);
+	assert_eq!((live.live_before(0), live.live_before(1), live.live_before(2)), (set(&[0]), set(&[0, 1]), set(&[0, 1, 2, 3])));
+	assert_eq!(live.access(1).kills, set(&[0, 1, 2, 3]));
+	assert_eq!(live.dead_across(0..1, 4), set(&[2, 3]));
+
+	// with a count only the results are sure to be written, but everything above A is clobbered,
+	// so nothing from A up is free across the call while r0 below it stays live
+	let proto = assemble(".stack 5\nMOVE 2 1\nCALL 1 2 2\nRETURN 0 3").unwrap();
+	let cfg = Cfg::new(&proto);
+	let live = Liveness::new(&cfg, &proto);
+	assert_eq!((live.access(1).defs, live.access(1).kills), (set(&[1, 2, 3, 4]), set(&[1])));
+	assert_eq!((live.live_before(1), live.live_after(1)), (set(&[0, 1, 2]), set(&[0, 1])));
+	assert_eq!(live.dead_across(0..1, 5), set(&[3, 4]));
+	assert_eq!(live.dead_across(1..2, 5), RegSet::default());
+	let tforloop = Access::of(&Instr::TForLoop(Reg(0), 2), 8);
+	assert_eq!((tforloop.uses, tforloop.defs, tforloop.kills), (set(&[0, 1, 2]), set(&[2, 3, 4, 5, 6, 7]), set(&[3, 4])));
+
+	// the for loop's hidden registers stay live round the loop
+	let proto = assemble(".stack 5\nFORPREP 0 1\nMOVE 4 3\nFORLOOP 0 -2\nRETURN 0 1").unwrap();
+	let cfg = Cfg::new(&proto);
+	let live = Liveness::new(&cfg, &proto);
+	assert_eq!((live.live_before(0), live.live_before(1), live.live_after(2)), (set(&[0, 1, 2]), set(&[0, 1, 2, 3]), set(&[0, 1, 2, 3])));
+}
+
+#[test]
+fn reaching_definitions() {
+	use std::collections::BTreeSet;
+	use bytecode::lua51::{assemble, Proto};
+	use bytecode::lua51::instruction::{Capture, Instr, Instruction, Opcode, Reg, RegKst, UnOp};
+	use ir::{Cfg, ReachingDefs, Site};
+
+	// r0 comes from either the LOADNIL or the MOVE, r1 is a parameter
+	let proto = assemble(".stack 3\nLOADNIL 0 0\nTEST 1 0 0\nJMP 1\nMOVE 0 1\nMOVE 2 0\nCALL 2 1 2\nRETURN 2 2").unwrap();
+	let cfg = Cfg::new(&proto);
+	let defs = ReachingDefs::new(&cfg, &proto);
+	assert_eq!(defs.use_def(4, 0), Some(&BTreeSet::from([Site::Pc(0), Site::Pc(3)])));
+	assert_eq!(defs.use_def(1, 1), Some(&BTreeSet::from([Site::Entry])));
+	assert_eq!(defs.use_def(4, 1), None);
+	assert_eq!(defs.use_def(6, 2), Some(&BTreeSet::from([Site::Pc(5)])));
+	assert_eq!(defs.def_use(Site::Pc(0)), [(4, 0)]);
+	assert_eq!(defs.def_use(Site::Entry), [(1, 1), (3, 1)]);
+	assert!(defs.def_use(Site::Pc(6)).is_empty());
+	assert_eq!(defs.reaching(6, 0), BTreeSet::from([Site::Pc(0), Site::Pc(3)]));
+	assert_eq!(defs.reaching(5, 2), BTreeSet::from([Site::Pc(4)]));
+
+	// TESTSET might not write r0, so the old value can still get through
+	let proto = assemble(".stack 2\nTESTSET 0 1 1\nJMP 0\nRETURN 0 2").unwrap();
+	let cfg = Cfg::new(&proto);
+	let defs = ReachingDefs::new(&cfg, &proto);
+	assert_eq!(defs.use_def(2, 0), Some(&BTreeSet::from([Site::Entry, Site::Pc(0)])));
+
+	// a call with one result replaces r0, and may have clobbered everything above it
+	let proto = assemble(".stack 4\nLOADNIL 0 3\nCALL 0 1 2\nRETURN 0 4").unwrap();
+	let cfg = Cfg::new(&proto);
+	let defs = ReachingDefs::new(&cfg, &proto);
+	assert_eq!(defs.reaching(2, 0), BTreeSet::from([Site::Pc(1)]));
+	assert_eq!(defs.reaching(2, 3), BTreeSet::from([Site::Pc(0), Site::Pc(1)]));
+	assert_eq!(defs.use_def(2, 1), Some(&BTreeSet::from([Site::Pc(0), Site::Pc(1)])));
+
+	// a captured r0 can be changed by a metamethod behind GETTABLE, but NOT never runs one
+	let mut proto = Proto::default();
+	proto.max_stack_size = 3;
+	proto.prototypes.push(Proto { nupvals: 1, ..Proto::default() });
+	proto.instructions = vec![
+		Instruction::new(Opcode::LoadNil, Instr::LoadNil(Reg(0), Reg(0))),
+		Instruction::new(Opcode::Closure, Instr::Closure(Reg(1), 0, vec![Capture::Local(Reg(0))])),
+		Instruction::new(Opcode::GetTable, Instr::GetTable(Reg(2), Reg(1), RegKst::R(Reg(1)))),
+		Instruction::new(Opcode::Not, Instr::UnOp(Reg(2), UnOp::Not, Reg(2))),
+		Instruction::new(Opcode::Return, Instr::Return(Reg(0), 2))
+	];
+	let cfg = Cfg::new(&proto);
+	let defs = ReachingDefs::new(&cfg, &proto);
+	assert_eq!(defs.use_def(4, 0), Some(&BTreeSet::from([Site::Pc(0), Site::Pc(2)])));
+}
+
+#[test]
+fn flatten_closures() {
+	use bytecode::lua51::{deserialize_bytecode, try_deserialize_bytecode, Header, Proto};
+	use bytecode::lua51::instruction::{Instr, Instruction, Opcode, Reg};
+	use obfuscation::bytecode::{Obfuscate, Options, VM};
+
+	// every CLOSURE still carries one capture per upvalue of its child
+	fn captures_match(proto: &Proto) -> bool {
+		proto.instructions.iter().all(|instruction| match &instruction.1 {
+			Instr::Closure(_, b, captures) => proto.prototypes[*b as usize].nupvals as usize == captures.len(),
+			_ => true
+		}) && proto.prototypes.iter().all(captures_match)
+	}
+
+	let flatten = |header: Header, proto: Proto| {
+		let mut ctx = ir::Context::new(header, proto);
+		ctx.map();
+		let mut obfuscate = Obfuscate::new(Options { flatten_control_flow: true, scramble_opcodes: false, target_vm: VM::Lua51 });
+		obfuscate.obfuscate(ctx);
+		try_deserialize_bytecode(&obfuscate.get().unwrap().assemble()).unwrap().1
+	};
+
+	// closures go through the register remap with their captures and load again
+	let chunk = std::fs::read("examples/corpus/closures.out").unwrap();
+	let (header, proto) = deserialize_bytecode(&chunk);
+	assert!(captures_match(&flatten(header, proto)));
+
+	// so does a SETLIST count too big for C
+	let mut proto = Proto::default();
+	proto.max_stack_size = 3;
+	proto.instructions.push(Instruction::new(Opcode::SetList, Instr::SetList(Reg(0), 1, 552)));
+	proto.instructions.push(Instruction::new(Opcode::Return, Instr::Return(Reg(0), 1)));
+	let flattened = flatten(Header::default(), proto);
+	assert!(flattened.instructions.iter().any(|instruction| matches!(instruction.1, Instr::SetList(_, 1, 552))));
+}
+
+#[test]
+fn malformed_chunks() {
+	use bytecode::{lua51::{try_deserialize_bytecode, serialize_bytecode, Constants, Proto}, DecodeErrorKind, Field};
+
+	let test_out = std::fs::read("examples/test_file.out").unwrap();
+
+	// truncated inside the main proto
+	let err = try_deserialize_bytecode(&test_out[..40]).unwrap_err();
+	assert!(matches!(err.kind, DecodeErrorKind::UnexpectedEof { needed: 1, remaining: 0 }));
+	assert_eq!((err.field, err.offset), (Field::ProtoInfo, 40));
+	assert!(err.path.is_empty());
+
+	// bad signature
+	let mut bad = test_out.clone();
+	bad[1] = b'l';
+	let err = try_deserialize_bytecode(&bad).unwrap_err();
+	assert_eq!((err.kind, err.field, err.offset), (DecodeErrorKind::BadSignature, Field::Header, 0));
+
+	// unknown constant tag in a nested proto
+	let (header, mut proto) = try_deserialize_bytecode(&test_out).unwrap();
+	let mut child = Proto::default();
+	child.constants.push(Constants::Boolean(true));
+	proto.prototypes.push(child);
+
+	let mut bytes = serialize_bytecode(&header, &proto);
+	let tag = bytes.windows(6).position(|w| w == [1, 0, 0, 0, 1, 1]).unwrap() + 4;
+	bytes[tag] = 7;
+	let err = try_deserialize_bytecode(&bytes).unwrap_err();
+	assert_eq!(err.kind, DecodeErrorKind::UnknownConstantTag(7));
+	assert_eq!((err.field, err.offset), (Field::ConstantTag(0), tag));
+	assert_eq!(err.path, vec![0]);
+}
+
+#[test]
+fn streamed_chunks() {
+	use std::io::{self, Cursor, Write};
+	use bytecode::{lua51, lua53, DecodeErrorKind};
+
+	let mut files = vec![];
+	for entry in std::fs::read_dir("examples/corpus").unwrap() {
+		let path = entry.unwrap().path();
+		if path.extension().is_some_and(|ext| ext == "out") {
+			files.push(std::fs::read(path).unwrap());
+		}
+	}
+	files.sort();
+
+	// every chunk written back-to-back into one stream
+	let mut stream = vec![];
+	let mut writer = lua51::Writer::from_write(&mut stream);
+	for bytes in &files {
+		let (header, proto) = lua51::deserialize_bytecode(bytes);
+		assert_eq!(lua51::serialize_to(&mut writer, &header, &proto), bytes.len());
+	}
+	assert_eq!(writer.flush().unwrap(), files.iter().map(Vec::len).sum::<usize>());
+	drop(writer);
+	assert_eq!(stream, files.concat());
+
+	// and read back one at a time
+	let mut reader = lua51::Reader::from_read(Cursor::new(&stream));
+	for bytes in &files {
+		let (header, proto, consumed) = lua51::deserialize_from(&mut reader).unwrap();
+		assert_eq!(consumed, bytes.len());
+		assert_eq!(&lua51::serialize_bytecode(&header, &proto), bytes);
+	}
+	assert!(reader.at_end().unwrap());
+
+	// slices work the same way, and chunks of different versions can share a stream
+	let test_53 = std::fs::read("examples/test_file_53.out").unwrap();
+	let mixed = [test_53.as_slice(), &files[0]].concat();
+	let mut reader = lua51::Reader::new(&mixed);
+	assert_eq!(lua53::deserialize_from(&mut reader).unwrap().2, test_53.len());
+	assert_eq!(lua51::deserialize_from(&mut reader).unwrap().2, files[0].len());
+	assert!(reader.at_end().unwrap());
+
+	// a stream cut short reports where in the stream it ended
+	let cut = &stream[..files[0].len() + 30];
+	let mut reader = lua51::Reader::from_read(cut);
+	lua51::deserialize_from(&mut reader).unwrap();
+	let err = lua51::deserialize_from(&mut reader).unwrap_err();
+	assert!(matches!(err.kind, DecodeErrorKind::UnexpectedEof { .. }));
+	assert!(err.offset >= files[0].len() && err.offset < cut.len());
+
+	// errors from the sink surface on flush
+	struct Broken;
+	impl Write for Broken {
+		fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+			Err(io::ErrorKind::BrokenPipe.into())
+		}
+		fn flush(&mut self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+	let (header, proto) = lua51::deserialize_bytecode(&files[0]);
+	let mut writer = lua51::Writer::from_write(Broken);
+	lua51::serialize_to(&mut writer, &header, &proto);
+	assert_eq!(writer.flush().unwrap_err().kind(), io::ErrorKind::BrokenPipe);
+}
+
+#[test]
+fn header_layouts() {
+	use bytecode::{Endianness, lua51::{Header, Reader, Writer, deserialize_bytecode, serialize_bytecode}};
+
+	// numbers and ints follow the writer's byte order and sizes
+	let mut writer = Writer::new();
+	writer.set_endianness(Endianness::Big);
+	writer.int(0x01020304, 8);
+	writer.number(-3f64, 4, true);
+	writer.number(0.5f64, 4, false);
+	assert_eq!(writer.as_bytes(), &[0, 0, 0, 0, 1, 2, 3, 4, 0xff, 0xff, 0xff, 0xfd, 0x3f, 0, 0, 0]);
+
+	let mut reader = Reader::new(writer.as_bytes());
+	reader.set_endianness(Endianness::Big);
+	assert_eq!(reader.int(8).unwrap(), 0x01020304);
+	assert_eq!(reader.number(4, true).unwrap(), -3f64);
+	assert_eq!(reader.number(4, false).unwrap(), 0.5f64);
+
+	// chunk from a 64-bit luac (size_t = 8)
+	let test_out = std::fs::read("examples/test_file_64.out").unwrap();
+	let (header, proto) = deserialize_bytecode(&test_out);
+	assert_eq!(header.size_t_size, 8);
+	assert_eq!(proto.instructions.len(), 95); // 96 words, one of them a CLOSURE capture
+	assert_eq!(proto.prototypes.len(), 1);
+
+	// and re-emitted for other layouts
+	let layouts = [
+		Header { endianness: Endianness::Big, ..header },
+		Header { int_size: 8, instruction_size: 8, ..Header::default() },
+		Header { number_size: 4, integral: true, ..Header::default() }
+	];
+	for layout in layouts {
+		let bytes = serialize_bytecode(&layout, &proto);
+		let (read_header, read_proto) = deserialize_bytecode(&bytes);
+		assert_eq!(read_header, layout);
+		assert_eq!(serialize_bytecode(&layout, &read_proto), bytes);
+		assert_eq!(read_proto.constants, proto.constants);
+	}
+}
+
+#[test]
+fn retarget_layouts() {
+	use bytecode::{Endianness, lua51::{Constants, Header, LossKind, retarget, retarget_bytecode, deserialize_bytecode}};
+
+	// 64-bit little endian down to the 32-bit default and to big endian
+	let test_out = std::fs::read("examples/test_file_64.out").unwrap();
+	let (_, proto) = deserialize_bytecode(&test_out);
+	for target in [Header::default(), Header { endianness: Endianness::Big, ..Header::default() }] {
+		let (bytes, losses) = retarget_bytecode(&test_out, &target).unwrap();
+		assert!(losses.is_empty());
+
+		let (header, converted) = deserialize_bytecode(&bytes);
+		assert_eq!(header, target);
+		assert_eq!(converted.constants, proto.constants);
+		assert_eq!(converted.prototypes[0].constants, proto.prototypes[0].constants);
+	}
+
+	// numbers the target can't hold exactly are reported and replaced
+	let mut proto = proto;
+	proto.prototypes[0].constants.push(Constants::Number(0.1));
+	proto.prototypes[0].constants.push(Constants::Number(2.5));
+	let float = Header { number_size: 4, ..Header::default() };
+	let (converted, losses) = retarget(&proto, &float);
+	assert_eq!(losses.len(), 1);
+	assert_eq!(losses[0].path, vec![0]);
+	assert!(matches!(losses[0].kind, LossKind::NumberRounded { index: 1, from, to } if from == 0.1 && to == 0.1f32 as f64));
+	assert_eq!(converted.prototypes[0].constants[2], Constants::Number(2.5));
+
+	let integral = Header { number_size: 4, integral: true, ..Header::default() };
+	let (converted, losses) = retarget(&proto, &integral);
+	assert_eq!(losses.len(), 2);
+	assert_eq!(converted.prototypes[0].constants[1..], [Constants::Number(0.0), Constants::Number(2.0)]);
+
+	// line info past a 16-bit int
+	let long = Header { int_size: 2, ..Header::default() };
+	proto.line_defined = 40000;
+	let (_, losses) = retarget(&proto, &long);
+	assert!(losses.iter().any(|loss| loss.kind == LossKind::IntOutOfRange { what: "line defined", value: 40000 }));
+
+	// a c int is signed, so a 4-byte one stops at i32::MAX too
+	proto.line_defined = 3_000_000_000;
+	let (_, losses) = retarget(&proto, &Header::default());
+	assert!(losses.iter().any(|loss| loss.kind == LossKind::IntOutOfRange { what: "line defined", value: 3_000_000_000 }));
+}
+
+#[test]
+fn binary_strings() {
+	use bytecode::{LuaString, lua51, lua53};
+
+	// strings are bytes, not utf-8
+	let binary = LuaString(vec![b'a', 0, 0xe9, b'\n', b'"']);
+	assert_eq!(binary.to_str(), None);
+	assert_eq!(binary.to_string(), "a\\000\\233\\n\\\"");
+	assert_eq!(format!("{:?}", LuaString::from("tab\t")), "\"tab\\t\"");
+
+	let mut proto = lua51::Proto::default();
+	proto.source = Some(LuaString(b"@caf\xe9.lua".to_vec()));
+	proto.constants.push(lua51::Constants::String(binary.clone()));
+	proto.upvals = Some(vec![LuaString(vec![0xff])]);
+	let header = lua51::Header::default();
+	let bytes = lua51::serialize_bytecode(&header, &proto);
+	let (_, read) = lua51::deserialize_bytecode(&bytes);
+	assert_eq!(read.source, proto.source);
+	assert_eq!(read.constants, vec![lua51::Constants::String(binary.clone())]);
+	assert_eq!(read.upvals, proto.upvals);
+	assert_eq!(lua51::serialize_bytecode(&header, &read), bytes);
+
+	let mut proto = lua53::Proto::default();
+	proto.constants.push(lua53::Constants::String(binary.clone()));
+	let header = lua53::Header::default();
+	let bytes = lua53::serialize_bytecode(&header, &proto);
+	let (_, read) = lua53::deserialize_bytecode(&bytes);
+	assert_eq!(read.constants, vec![lua53::Constants::String(binary)]);
+}
+
+#[test]
+fn lua52_chunks() {
+	use bytecode::{DecodeErrorKind, Field, lua52::{UpvalueDesc, instruction::Opcode, deserialize_bytecode, serialize_bytecode, try_deserialize_bytecode}};
+
+	// local a = 1 print(a)
+	let test_out = vec![0x1B, 0x4C, 0x75, 0x61, 0x52, 0x00, 0x01, 0x04, 0x08, 0x04, 0x08, 0x00, 0x19, 0x93, 0x0D, 0x0A,
+								0x1A, 0x0A, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x03, 0x05, 0x00, 0x00,
+								0x00, 0x01, 0x00, 0x00, 0x00, 0x46, 0x40, 0x40, 0x00, 0x80, 0x00, 0x00, 0x00, 0x5D, 0x40, 0x00,
+								0x01, 0x1F, 0x00, 0x80, 0x00, 0x02, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+								0xF0, 0x3F, 0x04, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x70, 0x72, 0x69, 0x6E, 0x74,
+								0x00, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x01, 0x00, 0x07, 0x00, 0x00, 0x00, 0x00,
+								0x00, 0x00, 0x00, 0x40, 0x74, 0x2E, 0x6C, 0x75, 0x61, 0x00, 0x05, 0x00, 0x00, 0x00, 0x01, 0x00,
+								0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x02, 0x00,
+								0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x61, 0x00,
+								0x01, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00,
+								0x00, 0x00, 0x00, 0x00, 0x5F, 0x45, 0x4E, 0x56, 0x00];
+	let (header, proto) = deserialize_bytecode(test_out.as_slice());
+	assert_eq!(serialize_bytecode(&header, &proto), test_out);
+
+	// chunk from the reference 5.2 luac round trips exactly
+	let test_out = std::fs::read("examples/test_file_52.out").unwrap();
+	let (header, proto) = deserialize_bytecode(&test_out);
+	assert_eq!(serialize_bytecode(&header, &proto), test_out);
+
+	assert_eq!(proto.source.as_ref().unwrap(), "@test_file.lua");
+	assert_eq!(proto.upvalues, vec![UpvalueDesc { in_stack: true, index: 0 }]);
+	assert_eq!(proto.upvals.as_deref(), Some(&["_ENV".into()][..]));
+	assert!(proto.instructions.iter().any(|instr| instr.0 == Opcode::GetTabUp));
+	assert_eq!(proto.prototypes.len(), 1);
+
+	// stripped chunks dump a NULL source
+	let mut stripped = proto.clone();
+	stripped.source = None;
+	stripped.prototypes[0].source = None;
+	let bytes = serialize_bytecode(&header, &stripped);
+	let (_, read) = deserialize_bytecode(&bytes);
+	assert_eq!(read.source, None);
+	assert_eq!(read.prototypes[0].source, None);
+	assert_eq!(serialize_bytecode(&header, &read), bytes);
+
+	// a chunk mangled by newline conversion
+	let mut bad = test_out.clone();
+	bad.remove(14);
+	let err = try_deserialize_bytecode(&bad).unwrap_err();
+	assert_eq!((err.kind, err.field, err.offset), (DecodeErrorKind::BadTail, Field::Header, 12));
+}
+
+#[test]
+fn lua53_chunks() {
+	use bytecode::{Endianness, DecodeErrorKind, lua53::{Constants, Header, deserialize_bytecode, serialize_bytecode, try_deserialize_bytecode}};
+	use bytecode::lua53::instruction::{Instruction, Instr, BinOp, UnOp, Reg, RegKst, Kst};
+
+	// chunk from the reference 5.3 luac round trips exactly
+	let test_out = std::fs::read("examples/test_file_53.out").unwrap();
+	let (header, proto) = deserialize_bytecode(&test_out);
+	assert_eq!(header, Header::default());
+	assert_eq!(serialize_bytecode(&header, &proto), test_out);
+
+	// integers stay integers
+	assert_eq!(proto.constants[..2], [Constants::Integer(0), Constants::Integer(20)]);
+	assert_eq!(proto.prototypes[0].source, None); // same as the parent's
+
+	// integers, floats and long strings in other layouts
+	let mut proto = proto;
+	proto.constants.push(Constants::Integer(i64::MIN));
+	proto.constants.push(Constants::Float(0.5));
+	proto.constants.push(Constants::String("x".repeat(300).into()));
+	proto.instructions.insert(0, Instruction::new(Instr::BinOp(Reg(0), RegKst::R(Reg(1)), BinOp::Shl, RegKst::K(Kst(0)))));
+	proto.instructions.insert(0, Instruction::new(Instr::UnOp(Reg(0), UnOp::BNot, Reg(1))));
+	for layout in [Header { endianness: Endianness::Big, ..header }, Header { integer_size: 4, number_size: 4, size_t_size: 4, ..header }] {
+		let bytes = serialize_bytecode(&layout, &proto);
+		let (read_header, read_proto) = deserialize_bytecode(&bytes);
+		assert_eq!(read_header, layout);
+		assert_eq!(serialize_bytecode(&layout, &read_proto), bytes);
+	}
+
+	// broken float format
+	let mut bad = test_out.clone();
+	bad[26] ^= 0x80;
+	let err = try_deserialize_bytecode(&bad).unwrap_err();
+	assert_eq!((err.kind, err.offset), (DecodeErrorKind::BadCheckValue("LUAC_NUM"), 25));
+}
+
+#[test]
+fn lua54_chunks() {
+	use bytecode::lua54::{ABSLINEINFO, AbsLineInfo, Header, Proto, UpvalueKind, deserialize_bytecode, serialize_bytecode};
+	use bytecode::lua54::instruction::{Instr, Opcode};
+
+	// chunk from the reference 5.4 luac round trips exactly
+	let test_out = std::fs::read("examples/test_file_54.out").unwrap();
+	let (header, proto) = deserialize_bytecode(&test_out);
+	assert_eq!(header, Header::default());
+	assert_eq!(serialize_bytecode(&header, &proto), test_out);
+
+	assert_eq!(proto.instructions[0].0, Opcode::VarArgPrep);
+	assert!(proto.instructions.iter().any(|instr| matches!(instr.1, Instr::EqK(..) | Instr::CondOpI(..))));
+	assert_eq!(proto.upvalues[0].kind, UpvalueKind::Regular);
+	assert_eq!(proto.line(0), Some(1));
+
+	// deltas pick up again after an absolute entry
+	let mut proto = Proto {
+		line_defined: 300, // two byte varint
+		line_info: Some(vec![1, ABSLINEINFO, 2, -1]),
+		abs_line_info: Some(vec![AbsLineInfo { pc: 1, line: 70000 }]),
+		..Proto::default()
+	};
+	proto.upvalues[0].kind = UpvalueKind::ToClose;
+	assert_eq!((0..5).map(|pc| proto.line(pc)).collect::<Vec<_>>(), [Some(301), Some(70000), Some(70002), Some(70001), None]);
+
+	let bytes = serialize_bytecode(&header, &proto);
+	let (_, read) = deserialize_bytecode(&bytes);
+	assert_eq!(read.line_defined, 300);
+	assert_eq!(read.abs_line_info, proto.abs_line_info);
+	assert_eq!(read.upvalues, proto.upvalues);
+	assert_eq!(read.line(2), Some(70002));
+}
+
+#[test]
+fn luajit_chunks() {
+	use bytecode::{DecodeErrorKind, Endianness};
+	use bytecode::luajit::{GcConstant, Header, NumConstant, Proto, TableConstant, deserialize_bytecode, serialize_bytecode, try_deserialize_bytecode};
+	use bytecode::luajit::instruction::{Instruction, Opcode, Opmode, Operand};
+
+	// chunk from `luajit -bg` run in examples/ round trips exactly, debug info included
+	let test_out = std::fs::read("examples/test_file_jit.out").unwrap();
+	let (header, proto) = deserialize_bytecode(&test_out);
+	assert_eq!(header.version, 2);
+	assert_eq!(header.chunk_name.as_ref().map(|name| name.0.as_slice()), Some(&b"@test_file.lua"[..]));
+	assert_eq!(serialize_bytecode(&header, &proto), test_out);
+
+	assert_eq!(proto.instructions[0].0, Opcode::TDup);
+	// the template of `{ [0] = 0 }` keeps slot 0 in its array part
+	let Some(Operand::Gc(k)) = proto.instructions[0].d() else { panic!() };
+	assert!(matches!(&proto.kgc[k.0 as usize], GcConstant::Table { array, .. } if array[..] == [TableConstant::Int(0)]));
+	assert_eq!(proto.kgc.iter().filter(|k| matches!(k, GcConstant::Child)).count(), proto.prototypes.len());
+	let debug = proto.debug.as_ref().unwrap();
+	assert_eq!(debug.line_info.len(), proto.instructions.len());
+
+	// stripping drops the debug info of every proto
+	let stripped = Header { chunk_name: None, ..header.clone() };
+	let (_, read) = deserialize_bytecode(&serialize_bytecode(&stripped, &proto));
+	assert!(read.debug.is_none() && read.prototypes.iter().all(|p| p.debug.is_none()));
+
+	// big endian, wide numbers and 64 bit cdata constants
+	let mut proto = Proto {
+		knum: vec![NumConstant::Int(-1), NumConstant::Num(0.5)],
+		kgc: vec![GcConstant::I64(-2), GcConstant::U64(u64::MAX), GcConstant::Complex(1.0, -1.0)],
+		..Proto::default()
+	};
+	proto.instructions.push(Instruction::new(Opcode::KNum, Opmode::AD(0, 1)));
+	proto.instructions.push(Instruction::new(Opcode::Ret0, Opmode::AD(0, 1)));
+	let header = Header { endianness: Endianness::Big, ..Header::default() };
+	let bytes = serialize_bytecode(&header, &proto);
+	let (read_header, read) = deserialize_bytecode(&bytes);
+	assert_eq!(read_header, header);
+	assert_eq!(read.knum, proto.knum);
+	assert_eq!(read.kgc, proto.kgc);
+	assert!(matches!(read.instructions[0].d(), Some(Operand::Num(k)) if k.0 == 1));
+
+	// 2.0 chunks have no ISTYPE/ISNUM/TGETR/TSETR, so later opcodes shift down
+	let v1 = Header { version: 1, fr2: false, ..Header::default() };
+	let bytes = serialize_bytecode(&v1, &proto);
+	assert_eq!(deserialize_bytecode(&bytes).1.instructions[1].0, Opcode::Ret0);
+	assert_ne!(bytes, serialize_bytecode(&Header { version: 2, fr2: false, ..Header::default() }, &proto));
+
+	// a proto length that doesn't match its body
+	let mut bytes = serialize_bytecode(&Header::default(), &proto);
+	bytes[5] += 1;
+	assert_eq!(try_deserialize_bytecode(&bytes).unwrap_err().kind, DecodeErrorKind::BadProtoLength);
+}
+
+#[test]
+fn luau_chunks() {
+	use bytecode::DecodeErrorKind;
+	use bytecode::luau::{Chunk, Constants, Header, LineInfo, Proto, TypeInfo, TypedLocal, UserdataType, deserialize_bytecode, import_id, import_path, serialize_bytecode, try_deserialize_bytecode};
+	use bytecode::luau::instruction::{Instr, Instruction, Kst, Opcode, Reg};
+
+	// chunk from the reference compiler at debug level 2 round trips exactly
+	let test_out = std::fs::read("examples/test_file_luau.out").unwrap();
+	let (header, chunk) = deserialize_bytecode(&test_out);
+	assert_eq!(header, Header::default());
+	assert_eq!(serialize_bytecode(&header, &chunk), test_out);
+
+	let main = chunk.main();
+	assert_eq!(main.instructions[0].0, Opcode::PrepVarArgs);
+	assert_eq!(main.line(0), Some(1));
+	assert!(main.debug.is_some());
+	assert_eq!(main.code_size(), main.line_info.as_ref().unwrap().line_offsets.len());
+
+	// `pairs` is loaded through an import
+	let import = main.constants.iter().find_map(|k| match k { Constants::Import(id) => Some(*id), _ => None }).unwrap();
+	let path = import_path(import);
+	assert_eq!(import_id(&path), import);
+	let Constants::String(name) = main.constants[path[0] as usize] else { panic!() };
+	assert_eq!(chunk.string(name).unwrap(), "pairs");
+
+	// AUX words are folded into their instruction but still count as code words
+	let mut proto = Proto {
+		type_info: Some(TypeInfo {
+			function: vec![5, 0],
+			upvalues: vec![],
+			locals: vec![TypedLocal { ty: 64, reg: 0, start_pc: 1, end_pc: 3 }]
+		}),
+		line_info: Some(LineInfo { gap_log2: 1, line_offsets: vec![0, 0, 2, 1], abs_line_info: vec![10, 300] }),
+		constants: vec![Constants::String(1), Constants::Vector(1.0, 2.0, 3.0, 0.0)],
+		..Proto::default()
+	};
+	proto.instructions.push(Instruction::new(Instr::GetGlobal(Reg(0), 0, Kst(0))));
+	proto.instructions.push(Instruction::new(Instr::JumpXEqKS(Reg(0), 1, Kst(0), true)));
+	assert_eq!(proto.code_size(), 4);
+	assert_eq!((0..5).map(|pc| proto.line(pc)).collect::<Vec<_>>(), [Some(10), Some(10), Some(302), Some(301), None]);
+
+	let chunk = Chunk {
+		strings: vec!["x".into(), "Instance".into()],
+		userdata_types: vec![UserdataType { index: 1, name: 2 }],
+		protos: vec![proto],
+		main: 0
+	};
+	let header = Header { version: 6, types_version: 3 };
+	let bytes = serialize_bytecode(&header, &chunk);
+	let (read_header, read) = deserialize_bytecode(&bytes);
+	assert_eq!(read_header, header);
+	assert_eq!(read.userdata_types, chunk.userdata_types);
+	assert_eq!(read.main().type_info, chunk.main().type_info);
+	assert_eq!(read.main().constants, chunk.main().constants);
+	assert!(matches!(read.main().instructions[1].1, Instr::JumpXEqKS(Reg(0), 1, Kst(0), true)));
+	assert_eq!(serialize_bytecode(&header, &read), bytes);
+
+	// version 3 has no type info at all
+	let v3 = Header { version: 3, types_version: 0 };
+	let (_, read) = deserialize_bytecode(&serialize_bytecode(&v3, &chunk));
+	assert!(read.main().type_info.is_none());
+
+	// an AUX word cut off by the end of the code
+	let mut bytes = serialize_bytecode(&header, &chunk);
+	let at = bytes.windows(2).position(|w| w == [4, Opcode::GetGlobal as u8]).unwrap();
+	bytes[at] = 1;
+	assert_eq!(try_deserialize_bytecode(&bytes).unwrap_err().kind, DecodeErrorKind::MissingAuxWord);
+
+	// compile errors come back as a chunk with version 0
+	let err = try_deserialize_bytecode(b"\0:1: syntax error").unwrap_err();
+	assert_eq!(err.kind, DecodeErrorKind::CompileError(":1: syntax error".to_string()));
+}